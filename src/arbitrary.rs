@@ -0,0 +1,54 @@
+// Helpers shared by the `quickcheck` and `proptest` support.
+//
+// Every structure is generated by running its ordinary operations on random
+// input, and shrunk by shrinking that input and building the structure again,
+// so shrinking can never produce a value that violates an invariant.
+
+use compare::Compare;
+use heap::LeftistHeap;
+use pointer::SharedPointerKind;
+use queue::BatchedQueue;
+use rbtree::RBTree;
+use traits::{Heap, Queue, Set};
+use tree::Tree;
+
+pub(crate) fn tree_from_vec<V: Ord, P: SharedPointerKind>(items: Vec<V>) -> Tree<V, P> {
+    let mut t = Tree::empty();
+    for v in items {
+        t.add(v);
+    }
+    t
+}
+
+pub(crate) fn rbtree_from_vec<V, P, C>(items: Vec<V>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    let mut t = RBTree::empty();
+    for v in items {
+        t.add(v);
+    }
+    t
+}
+
+pub(crate) fn heap_from_vec<V: Ord, P: SharedPointerKind>(items: Vec<V>) -> LeftistHeap<V, P> {
+    let mut h = LeftistHeap::empty();
+    for v in items {
+        h.add(v);
+    }
+    h
+}
+
+// Build a queue containing `items`. Pushing the `discarded` items first and
+// then popping them off means the items end up split between the front and
+// back lists in different ways.
+pub(crate) fn queue_from_vecs<T, P: SharedPointerKind>(discarded: Vec<T>, items: Vec<T>) -> BatchedQueue<T, P> {
+    let n = discarded.len();
+    let mut q = BatchedQueue::empty();
+    for v in discarded.into_iter().chain(items) {
+        q.push_back(v);
+    }
+    for _ in 0..n {
+        q = q.tail().unwrap();
+    }
+    q
+}
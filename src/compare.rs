@@ -0,0 +1,57 @@
+//! Orderings for sorted collections.
+//!
+//! Ordered collections such as `RBTree` take a comparator as a type parameter.
+//! It defaults to `Natural`, which uses the values' own `Ord` implementation.
+//! To sort by something else, define a comparator type:
+//!
+//! ```ignore
+//! enum ById {}
+//!
+//! impl Compare<Employee> for ById {
+//!     fn compare(a: &Employee, b: &Employee) -> Ordering {
+//!         a.id.cmp(&b.id)
+//!     }
+//! }
+//!
+//! let staff: RBTree<Employee, RcKind, ById> = RBTree::empty();
+//! ```
+//!
+//! Comparators are types rather than values, so a collection never has to
+//! carry a comparator around, and two collections of the same type are always
+//! sorted the same way.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A total order on values of type `T`.
+pub trait Compare<T: ?Sized> {
+    /// Compare two values.
+    fn compare(a: &T, b: &T) -> Ordering;
+}
+
+/// The order given by `T`'s `Ord` implementation. This is the default.
+#[derive(Debug)]
+pub enum Natural {}
+
+impl<T: Ord + ?Sized> Compare<T> for Natural {
+    fn compare(a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// The reverse of the order given by comparator `C`.
+pub struct Reverse<C = Natural>(PhantomData<fn() -> C>);
+
+impl<T: ?Sized, C: Compare<T>> Compare<T> for Reverse<C> {
+    fn compare(a: &T, b: &T) -> Ordering {
+        C::compare(b, a)
+    }
+}
+
+// `derive(Debug)` would require `C: Debug`.
+impl<C> fmt::Debug for Reverse<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Reverse")
+    }
+}
@@ -0,0 +1,168 @@
+//! Multisets that count how many times each value occurs.
+
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
+use std::iter::FromIterator;
+use compare::Compare;
+use pointer::{SharedPointerKind, RcKind};
+use rbtree::{RBTree, RBTreeFinger};
+use topk::TopK;
+use traits::Set;
+
+// A value and its count, which is always positive. Tallies are ordered by
+// value alone, so they can be looked up, replaced and removed by value.
+struct Tally<T> {
+    key: T,
+    count: usize
+}
+
+enum ByKey {}
+
+impl<T: Ord> Compare<Tally<T>> for ByKey {
+    fn compare(a: &Tally<T>, b: &Tally<T>) -> Ordering {
+        a.key.cmp(&b.key)
+    }
+}
+
+/// A persistent frequency counter: a map from values to positive counts.
+pub struct Counter<T, P: SharedPointerKind = RcKind> {
+    tallies: RBTree<Tally<T>, P, ByKey>,
+    // The sum of the counts.
+    total: usize
+}
+
+impl<T, P: SharedPointerKind> Clone for Counter<T, P> {
+    fn clone(&self) -> Counter<T, P> {
+        Counter { tallies: self.tallies.clone(), total: self.total }
+    }
+}
+
+impl<T: Ord + Clone> Counter<T> {
+    /// Return an empty counter that shares nodes using `Rc`.
+    pub fn new() -> Counter<T> {
+        Counter::empty()
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> Default for Counter<T, P> {
+    fn default() -> Counter<T, P> {
+        Counter::empty()
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> Counter<T, P> {
+    /// Return a counter in which every count is zero.
+    pub fn empty() -> Counter<T, P> {
+        Counter { tallies: RBTree::empty(), total: 0 }
+    }
+
+    /// The number of values with a positive count.
+    pub fn len(&self) -> usize {
+        self.tallies.len()
+    }
+
+    /// Return true if every count is zero.
+    pub fn is_empty(&self) -> bool {
+        self.tallies.is_empty()
+    }
+
+    /// The sum of all the counts.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The number of times `key` has been counted.
+    pub fn count(&self, key: &T) -> usize {
+        self.tally(key).map_or(0, |t| t.count)
+    }
+
+    /// Return a counter with the count for `key` increased by one.
+    pub fn increment(&self, key: T) -> Counter<T, P> {
+        self.add(key, 1)
+    }
+
+    /// Return a counter with the count for `key` increased by `n`.
+    pub fn add(&self, key: T, n: usize) -> Counter<T, P> {
+        if n == 0 {
+            return self.clone();
+        }
+        let count = self.count(&key) + n;
+        Counter { tallies: self.tallies.replace(Tally { key, count }), total: self.total + n }
+    }
+
+    /// Return a counter with the count for `key` decreased by one, unless it
+    /// is already zero.
+    pub fn decrement_saturating(&self, key: &T) -> Counter<T, P> {
+        let tallies = match self.count(key) {
+            0 => return self.clone(),
+            1 => self.tallies.remove_by(|t| key.cmp(&t.key)).expect("key is in the counter"),
+            count => self.tallies.replace(Tally { key: key.clone(), count: count - 1 })
+        };
+        Counter { tallies, total: self.total - 1 }
+    }
+
+    /// The `k` values with the highest counts, and their counts, highest
+    /// first. Values with equal counts come in increasing order. This takes
+    /// O(n log k) time.
+    pub fn most_common(&self, k: usize) -> Vec<(&T, usize)> {
+        let mut top = TopK::new(k);
+        top.extend(self.iter().map(|(key, count)| (count, Reverse(key))));
+        top.into_sorted_list().into_iter().map(|(count, Reverse(key))| (key, count)).collect()
+    }
+
+    /// Return a counter whose counts are the sums of the counts in `self` and
+    /// `other`. This adds the smaller counter's values to the larger one,
+    /// one at a time.
+    pub fn merge(&self, other: &Counter<T, P>) -> Counter<T, P> {
+        let (big, small) = if self.len() >= other.len() { (self, other) } else { (other, self) };
+        small.iter().fold(big.clone(), |c, (key, n)| c.add(key.clone(), n))
+    }
+
+    /// Iterate over the values with positive counts, in order, with their
+    /// counts.
+    pub fn iter(&self) -> Iter<'_, T, P> {
+        Iter { finger: self.tallies.finger() }
+    }
+
+    fn tally(&self, key: &T) -> Option<&Tally<T>> {
+        self.tallies.find_by(|t| key.cmp(&t.key))
+    }
+}
+
+/// An iterator over the values in a `Counter` and their counts.
+pub struct Iter<'a, T: 'a, P: SharedPointerKind + 'a = RcKind> {
+    finger: RBTreeFinger<'a, Tally<T>, P, ByKey>
+}
+
+impl<'a, T, P: SharedPointerKind> Iterator for Iter<'a, T, P> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<(&'a T, usize)> {
+        let tally = self.finger.get()?;
+        self.finger.advance();
+        Some((&tally.key, tally.count))
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> FromIterator<T> for Counter<T, P> {
+    fn from_iter<Iterable: IntoIterator<Item=T>>(iterator: Iterable) -> Counter<T, P> {
+        let mut counter = Counter::empty();
+        counter.extend(iterator);
+        counter
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> Extend<T> for Counter<T, P> {
+    fn extend<Iterable: IntoIterator<Item=T>>(&mut self, iterator: Iterable) {
+        for v in iterator {
+            *self = self.increment(v);
+        }
+    }
+}
+
+impl<T: Ord + Clone + fmt::Debug, P: SharedPointerKind> fmt::Debug for Counter<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Counter")?;
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,48 @@
+//! The recommended structure for each kind of collection.
+//!
+//! Code that just needs "a set" or "a queue" can name one of these aliases
+//! instead of a particular structure, and pick up a better one if the crate
+//! adds it, or if a feature flag selects a different one:
+//!
+//! | Alias          | Trait   | Default        | With feature                          |
+//! |----------------|---------|----------------|---------------------------------------|
+//! | `DefaultSeq`   | `Stack` | `List`         |                                       |
+//! | `DefaultQueue` | `Queue` | `BatchedQueue` |                                       |
+//! | `DefaultSet`   | `Set`   | `RBTree`       | `default-set-hash`: `HashSet`         |
+//! | `DefaultHeap`  | `Heap`  | `LeftistHeap`  | `default-heap-pairing`: `PairingHeap` |
+//!
+//! The defaults are the ones whose bounds hold however old versions are
+//! reused. `PairingHeap` is often faster, but its bounds are amortized and
+//! can fail when old versions are reused. `HashSet` is faster for large
+//! sets, but requires `Hash` rather than `Ord` and doesn't keep its items in
+//! order.
+//!
+//! Since the structures behind an alias can change, code that uses one should
+//! stick to the trait's methods. Like the structures themselves, each alias
+//! takes a pointer kind, defaulting to `RcKind`.
+//!
+//! These aliases are re-exported at the crate root.
+
+use pointer::RcKind;
+
+/// The recommended `Stack`.
+pub type DefaultSeq<T, P = RcKind> = ::list::List<T, P>;
+
+/// The recommended `Queue`.
+pub type DefaultQueue<T, P = RcKind> = ::queue::BatchedQueue<T, P>;
+
+/// The recommended `Set`.
+#[cfg(not(feature = "default-set-hash"))]
+pub type DefaultSet<T, P = RcKind> = ::rbtree::RBTree<T, P>;
+
+/// The recommended `Set`.
+#[cfg(feature = "default-set-hash")]
+pub type DefaultSet<T, P = RcKind> = ::hashset::HashSet<T, P>;
+
+/// The recommended `Heap`.
+#[cfg(not(feature = "default-heap-pairing"))]
+pub type DefaultHeap<T, P = RcKind> = ::heap::LeftistHeap<T, P>;
+
+/// The recommended `Heap`.
+#[cfg(feature = "default-heap-pairing")]
+pub type DefaultHeap<T, P = RcKind> = ::pairing::PairingHeap<T, P>;
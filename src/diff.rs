@@ -0,0 +1,183 @@
+//! Comparing two versions of a set or map.
+//!
+//! When one version of a tree is made from another, the two share every
+//! subtree the changes didn't touch. `diff` walks both trees in order, side by
+//! side, and skips any subtree that is shared, so the time it takes depends on
+//! how much changed rather than on the size of the sets: roughly O(k log² n)
+//! for k changes. Unrelated trees with the same values are still compared
+//! correctly, just without the shortcut.
+//!
+//! Besides values that were added or removed, a diff reports values that
+//! were changed: ones that hold the same place in the order in both versions
+//! but aren't equal. That can only happen in a set whose comparator looks at
+//! part of each value, which is how an `RBMap` stores its entries, so
+//! `RBMap::diff` reports each key whose value changed.
+
+use std::cmp::Ordering;
+use sharing::Linked;
+
+/// The differences between an old and a new version of a set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diff<'a, V: 'a> {
+    /// Values in the new version but not the old, in order.
+    pub added: Vec<&'a V>,
+    /// Values in the old version but not the new, in order.
+    pub removed: Vec<&'a V>,
+    /// Values that compare equal to each other but aren't equal, as (old,
+    /// new) pairs, in order.
+    pub changed: Vec<(&'a V, &'a V)>
+}
+
+impl<'a, V> Diff<'a, V> {
+    /// Return true if the two versions have the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between an old and a new version of a map. See
+/// `RBMap::diff`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapDiff<'a, K: 'a, V: 'a> {
+    /// Entries whose keys are in the new version but not the old, in order by
+    /// key.
+    pub added: Vec<(&'a K, &'a V)>,
+    /// Entries whose keys are in the old version but not the new, in order by
+    /// key.
+    pub removed: Vec<(&'a K, &'a V)>,
+    /// Keys in both versions whose values differ, with the old value and the
+    /// new, in order by key.
+    pub changed: Vec<(&'a K, &'a V, &'a V)>
+}
+
+impl<'a, K, V> MapDiff<'a, K, V> {
+    /// Return true if the two versions have the same entries.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Every value `d` touches, in order: a value equal to it from the old
+// version if there was one, else from the new, and what the new version has
+// in its place, or None if it was removed. `merge3` uses this to line up two
+// diffs from the same base.
+pub(crate) fn changes<'a, V, F>(d: &Diff<'a, V>, compare: F) -> Vec<(&'a V, Option<&'a V>)>
+    where F: Fn(&V, &V) -> Ordering
+{
+    let mut out: Vec<(&'a V, Option<&'a V>)> = d.added.iter().map(|&v| (v, Some(v)))
+        .chain(d.removed.iter().map(|&v| (v, None)))
+        .chain(d.changed.iter().map(|&(old, new)| (old, Some(new))))
+        .collect();
+    out.sort_by(|x, y| compare(x.0, y.0));
+    out
+}
+
+pub(crate) trait SortedTree: Linked {
+    type Value;
+
+    // The value at the root. Only called on nonempty trees.
+    fn root_value(&self) -> &Self::Value;
+}
+
+// The rest of one tree still to be visited: a stack of whole subtrees and
+// single values, with the next in order on top. Subtrees are never empty.
+enum Pending<'a, T: 'a + SortedTree> {
+    Tree(&'a T),
+    Value(&'a T::Value)
+}
+
+fn push_tree<'a, T: SortedTree>(stack: &mut Vec<Pending<'a, T>>, tree: &'a T) {
+    if tree.root_addr().is_some() {
+        stack.push(Pending::Tree(tree));
+    }
+}
+
+// Replace the subtree on top of the stack with its left subtree, root value,
+// and right subtree.
+fn expand<'a, T: SortedTree>(stack: &mut Vec<Pending<'a, T>>) {
+    if let Some(Pending::Tree(tree)) = stack.pop() {
+        let (left, right) = tree.children();
+        if let Some(right) = right {
+            push_tree(stack, right);
+        }
+        stack.push(Pending::Value(tree.root_value()));
+        if let Some(left) = left {
+            push_tree(stack, left);
+        }
+    }
+}
+
+// The number of nodes on the path to the leftmost node. When one side's next
+// subtree is shared with the other side, it is somewhere on the other side's
+// left spine, so expanding the side with the longer spine finds it.
+fn left_spine<T: SortedTree>(mut tree: &T) -> usize {
+    let mut n = 0;
+    while tree.root_addr().is_some() {
+        n += 1;
+        match tree.children().0 {
+            Some(left) => tree = left,
+            None => break
+        }
+    }
+    n
+}
+
+// `same` says whether two values that compare equal are really the same, or
+// whether the value was changed.
+pub(crate) fn diff<'a, T, F, G>(old: &'a T, new: &'a T, compare: F, same: G) -> Diff<'a, T::Value>
+    where T: SortedTree, F: Fn(&T::Value, &T::Value) -> Ordering, G: Fn(&T::Value, &T::Value) -> bool
+{
+    let mut result = Diff { added: vec![], removed: vec![], changed: vec![] };
+    let mut a = vec![];
+    let mut b = vec![];
+    push_tree(&mut a, old);
+    push_tree(&mut b, new);
+    loop {
+        match (a.last(), b.last()) {
+            (None, None) => return result,
+            (Some(&Pending::Tree(x)), Some(&Pending::Tree(y))) => {
+                if x.root_addr() == y.root_addr() {
+                    a.pop();
+                    b.pop();
+                } else {
+                    let (dx, dy) = (left_spine(x), left_spine(y));
+                    if dx >= dy {
+                        expand(&mut a);
+                    }
+                    if dy >= dx {
+                        expand(&mut b);
+                    }
+                }
+            }
+            (Some(&Pending::Tree(_)), _) => expand(&mut a),
+            (_, Some(&Pending::Tree(_))) => expand(&mut b),
+            (Some(&Pending::Value(x)), Some(&Pending::Value(y))) => {
+                match compare(x, y) {
+                    Ordering::Less => {
+                        result.removed.push(x);
+                        a.pop();
+                    }
+                    Ordering::Greater => {
+                        result.added.push(y);
+                        b.pop();
+                    }
+                    Ordering::Equal => {
+                        if !same(x, y) {
+                            result.changed.push((x, y));
+                        }
+                        a.pop();
+                        b.pop();
+                    }
+                }
+            }
+            (Some(&Pending::Value(x)), None) => {
+                result.removed.push(x);
+                a.pop();
+            }
+            (None, Some(&Pending::Value(y))) => {
+                result.added.push(y);
+                b.pop();
+            }
+        }
+    }
+}
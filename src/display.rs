@@ -0,0 +1,36 @@
+//! Printing sequences for people.
+//!
+//! `List` and `BatchedQueue` implement `Display` by writing their elements in
+//! order, separated by `", "`. For a different separator, use `display_with`:
+//! `format!("{}", path.display_with(" -> "))`. Nothing is collected into a
+//! temporary string along the way.
+
+use std::fmt;
+
+/// A sequence paired with a separator, for formatting with `{}`. Returned by
+/// the `display_with` method of each sequence type.
+pub struct DisplayWith<'a, S: 'a + ?Sized> {
+    pub(crate) seq: &'a S,
+    pub(crate) sep: &'a str
+}
+
+// `derive(Clone, Copy)` would require `S: Clone`.
+impl<'a, S: ?Sized> Clone for DisplayWith<'a, S> {
+    fn clone(&self) -> DisplayWith<'a, S> { *self }
+}
+
+impl<'a, S: ?Sized> Copy for DisplayWith<'a, S> {}
+
+// Write `items` with `sep` between them. Each item is formatted with the same
+// flags as the whole sequence, so `{:.2}` applies to every element.
+pub(crate) fn write_separated<'a, T, I>(f: &mut fmt::Formatter, items: I, sep: &str) -> fmt::Result
+    where T: fmt::Display + 'a, I: IntoIterator<Item=&'a T>
+{
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(sep)?;
+        }
+        fmt::Display::fmt(item, f)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Graphviz export, for `to_dot()` and `versions_to_dot()`.
+//
+// The versions are first encoded as a `Snapshot`, which already gives each
+// distinct node one index no matter how many versions share it. Each node
+// becomes one vertex in the graph, and each version gets a box pointing at its
+// root, so sharing between versions shows up as arrows converging on the same
+// vertices.
+
+use std::fmt::{self, Write};
+use snapshot::{HeapRecord, ListRecord, RBTreeRecord, Snapshot, TreeRecord};
+
+pub(crate) trait DotRecord {
+    // Index of the node's value in the snapshot.
+    fn value(&self) -> usize;
+
+    // Extra text for the vertex label, after the value.
+    fn note(&self) -> Option<String> { None }
+
+    // Extra vertex attributes.
+    fn attrs(&self) -> &'static str { "" }
+
+    // Outgoing edges, as (tail port, child index) pairs.
+    fn edges(&self) -> Vec<(&'static str, usize)>;
+}
+
+fn tree_edges(left: Option<usize>, right: Option<usize>) -> Vec<(&'static str, usize)> {
+    left.map(|l| (":sw", l)).into_iter().chain(right.map(|r| (":se", r))).collect()
+}
+
+impl DotRecord for ListRecord {
+    fn value(&self) -> usize { self.value }
+
+    fn edges(&self) -> Vec<(&'static str, usize)> {
+        self.tail.map(|t| ("", t)).into_iter().collect()
+    }
+}
+
+impl DotRecord for TreeRecord {
+    fn value(&self) -> usize { self.value }
+
+    fn edges(&self) -> Vec<(&'static str, usize)> { tree_edges(self.left, self.right) }
+}
+
+impl DotRecord for RBTreeRecord {
+    fn value(&self) -> usize { self.value }
+
+    fn attrs(&self) -> &'static str {
+        if self.red {
+            ", style=filled, fillcolor=red"
+        } else {
+            ", style=filled, fillcolor=black, fontcolor=white"
+        }
+    }
+
+    fn edges(&self) -> Vec<(&'static str, usize)> { tree_edges(self.left, self.right) }
+}
+
+impl DotRecord for HeapRecord {
+    fn value(&self) -> usize { self.value }
+
+    fn note(&self) -> Option<String> { Some(format!("rank {}", self.rank)) }
+
+    fn edges(&self) -> Vec<(&'static str, usize)> { tree_edges(self.left, self.right) }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub(crate) fn render<V: fmt::Debug, N: DotRecord>(name: &str, snapshot: &Snapshot<V, N>) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail.
+    let _ = write_dot(&mut out, name, snapshot);
+    out
+}
+
+fn write_dot<V: fmt::Debug, N: DotRecord>(out: &mut String, name: &str, snapshot: &Snapshot<V, N>) -> fmt::Result {
+    writeln!(out, "digraph {} {{", name)?;
+    writeln!(out, "    node [shape=ellipse];")?;
+    for (i, root) in snapshot.roots.iter().enumerate() {
+        writeln!(out, "    v{} [label=\"version {}\", shape=box];", i, i)?;
+        match *root {
+            Some(r) => writeln!(out, "    v{} -> n{};", i, r)?,
+            None => {
+                writeln!(out, "    empty{} [label=\"empty\", shape=plaintext];", i)?;
+                writeln!(out, "    v{} -> empty{};", i, i)?;
+            }
+        }
+    }
+    for (i, node) in snapshot.nodes.iter().enumerate() {
+        let mut label = escape(&format!("{:?}", snapshot.values[node.value()]));
+        if let Some(note) = node.note() {
+            label = format!("{}\\n{}", label, note);
+        }
+        writeln!(out, "    n{} [label=\"{}\"{}];", i, label, node.attrs())?;
+        for (port, child) in node.edges() {
+            writeln!(out, "    n{}{} -> n{};", i, port, child)?;
+        }
+    }
+    writeln!(out, "}}")
+}
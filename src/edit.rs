@@ -0,0 +1,147 @@
+//! Batches of changes to a set, applied all at once.
+//!
+//! Adding values to a tree one at a time copies the search path for each one:
+//! with k changes to a tree of n values, about k log n nodes. A set's `edit`
+//! method collects the changes first, then applies them whichever way copies
+//! fewer nodes: one at a time, or by merging them with the tree's values and
+//! rebuilding the tree bottom-up, which copies all n nodes but never clones a
+//! value. Either way the original tree is unchanged.
+//!
+//! ```ignore
+//! let after = before.edit(|e| {
+//!     for k in updates {
+//!         e.insert(k);
+//!     }
+//!     e.remove(obsolete);
+//! });
+//! ```
+//!
+//! An `RBTree` can remove a single value by splitting the tree around it and
+//! joining the halves, which copies about twice as many nodes as an insertion,
+//! so a small batch with removals is still applied one change at a time. The
+//! unbalanced `Tree` has no way to remove a single value, so for it a batch
+//! that removes anything is always applied by rebuilding.
+
+use std::cmp::Ordering;
+use std::ops::Deref;
+use sharing::Linked;
+
+/// A list of values to add to or remove from a set. See the module
+/// documentation.
+#[derive(Clone, Debug)]
+pub struct Edit<V> {
+    // Each value, and true to insert it or false to remove it, in the order
+    // the changes were made.
+    changes: Vec<(V, bool)>
+}
+
+impl<V> Edit<V> {
+    pub(crate) fn new() -> Edit<V> {
+        Edit { changes: vec![] }
+    }
+
+    /// Add `value` to the set, unless an equal value is already there.
+    pub fn insert(&mut self, value: V) {
+        self.changes.push((value, true));
+    }
+
+    /// Remove the value equal to `value` from the set, if any.
+    pub fn remove(&mut self, value: V) {
+        self.changes.push((value, false));
+    }
+
+    /// The number of changes so far.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Return true if no changes have been made.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+// Sort the changes, keeping only the last change to each value.
+pub(crate) fn sorted<V, F>(edit: Edit<V>, compare: F) -> Vec<(V, bool)>
+    where F: Fn(&V, &V) -> Ordering
+{
+    let mut changes = edit.changes;
+    changes.sort_by(|a, b| compare(&a.0, &b.0));  // stable, so later changes stay later
+    let mut out: Vec<(V, bool)> = Vec::with_capacity(changes.len());
+    for change in changes {
+        if let Some(last) = out.last_mut() {
+            if compare(&last.0, &change.0) == Ordering::Equal {
+                *last = change;
+                continue;
+            }
+        }
+        out.push(change);
+    }
+    out
+}
+
+// Decide whether to apply `changes` to `tree` by rebuilding it. Inserting k
+// values one at a time copies about k * (mean depth + 1) nodes; rebuilding
+// copies every node. A removal cuts the tree in two and joins the halves,
+// which copies about twice as many nodes as an insertion, so it counts
+// double. Trees that can't remove single values (`!can_remove`) have to
+// rebuild for any removal. Counting stops early once the tree is clearly too
+// big to be worth rebuilding.
+pub(crate) fn should_rebuild<T: Linked, V>(tree: &T, changes: &[(V, bool)], can_remove: bool) -> bool {
+    let removals = changes.iter().filter(|c| !c.1).count();
+    if removals > 0 && !can_remove {
+        return true;
+    }
+    let k = changes.len() + removals;
+    if k == 0 {
+        return false;
+    }
+    let limit = k.saturating_mul(128);
+    let mut count = 0;
+    let mut depth_sum = 0;
+    let mut stack = vec![(tree, 0)];
+    while let Some((t, depth)) = stack.pop() {
+        if t.root_addr().is_some() {
+            count += 1;
+            if count > limit {
+                return false;
+            }
+            depth_sum += depth;
+            let (a, b) = t.children();
+            stack.extend(a.map(|a| (a, depth + 1)));
+            stack.extend(b.map(|b| (b, depth + 1)));
+        }
+    }
+    count as u128 * count as u128 <= k as u128 * (depth_sum + count) as u128
+}
+
+// Merge sorted changes into pointers to a set's values, in order. Inserting a
+// value that's already present keeps the old one, as `plus` does.
+pub(crate) fn merge<V, Ptr, F, W>(old: Vec<Ptr>, changes: Vec<(V, bool)>, compare: F, wrap: W) -> Vec<Ptr>
+    where Ptr: Deref<Target=V>, F: Fn(&V, &V) -> Ordering, W: Fn(V) -> Ptr
+{
+    let mut out = Vec::with_capacity(old.len() + changes.len());
+    let mut old = old.into_iter().peekable();
+    let mut changes = changes.into_iter().peekable();
+    loop {
+        let order = match (old.peek(), changes.peek()) {
+            (Some(a), Some(b)) => compare(a, &b.0),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return out
+        };
+        match order {
+            Ordering::Less => out.extend(old.next()),
+            Ordering::Greater => {
+                if let Some((value, true)) = changes.next() {
+                    out.push(wrap(value));
+                }
+            }
+            Ordering::Equal => {
+                if let Some((_, false)) = changes.next() {
+                    old.next();
+                }
+            }
+        }
+    }
+}
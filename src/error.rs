@@ -0,0 +1,38 @@
+//! The crate's error type.
+//!
+//! Most operations can't fail, and those that can return `Option`, since
+//! there's only one way for them to go wrong. The `try_` methods return a
+//! `Result<_, Error>` instead, which says what went wrong, for programs that
+//! pass errors up with `?`.
+
+use std::error;
+use std::fmt;
+
+/// An error from one of the crate's `try_` methods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// An index was not less than the length of the sequence.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The collection was empty.
+    Empty,
+    /// A key was too large for a map whose keys must be less than `limit`.
+    KeyOutOfRange { key: u32, limit: u32 },
+    /// A structure that can hold only `capacity` items was full.
+    CapacityExceeded { capacity: usize }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::IndexOutOfBounds { index, len } =>
+                write!(f, "index {} is out of bounds for length {}", index, len),
+            Error::Empty => f.write_str("collection is empty"),
+            Error::KeyOutOfRange { key, limit } =>
+                write!(f, "key {} is out of range; keys must be less than {}", key, limit),
+            Error::CapacityExceeded { capacity } =>
+                write!(f, "capacity of {} exceeded", capacity)
+        }
+    }
+}
+
+impl error::Error for Error {}
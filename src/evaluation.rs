@@ -0,0 +1,50 @@
+//! Evaluation strategies, for structures that come in eager, lazy and
+//! scheduled variants.
+//!
+//! Okasaki gives several versions of some structures that differ only in
+//! when they do their work: right away, in suspensions that are forced when
+//! the result is needed, or a little at a time on a schedule, so that every
+//! operation has a worst-case bound. Rather than being separate types, these
+//! variants take the strategy as a type parameter, the way every structure
+//! takes a pointer kind (see the `pointer` module). Switching strategies is
+//! then a one-line change, and code such as benchmarks can be generic over
+//! the strategy.
+//!
+//! The strategies are uninhabited types used only as type parameters. Each
+//! structure says which ones it supports with a trait of its own, such as
+//! `mergesort::SortStrategy`.
+
+/// A way of scheduling a structure's work. Implemented by `Eager`, `Lazy`
+/// and `Scheduled`.
+pub trait Strategy {
+    /// The strategy's name, for `Debug` output.
+    const NAME: &'static str;
+}
+
+/// Do all the work as soon as possible. The bounds are usually amortized,
+/// and can fail when old versions are reused.
+#[derive(Debug)]
+pub enum Eager {}
+
+/// Suspend the work until its result is needed, and share the result with
+/// every version that needs it. The bounds are amortized, but hold even when
+/// old versions are reused.
+#[derive(Debug)]
+pub enum Lazy {}
+
+/// Suspend the work, and force it a little at a time, so that the bounds are
+/// worst-case rather than amortized.
+#[derive(Debug)]
+pub enum Scheduled {}
+
+impl Strategy for Eager {
+    const NAME: &'static str = "Eager";
+}
+
+impl Strategy for Lazy {
+    const NAME: &'static str = "Lazy";
+}
+
+impl Strategy for Scheduled {
+    const NAME: &'static str = "Scheduled";
+}
@@ -0,0 +1,182 @@
+//! Persistent directed graphs.
+//!
+//! A `Graph` is two `IntMap`s: one from node ids to node weights, and one from
+//! node ids to each node's outgoing edges, themselves an `IntMap` from target
+//! ids to edge weights. Every change copies a few small trie nodes, so
+//! keeping old versions around is cheap: to snapshot a graph, clone it.
+
+use std::fmt;
+use error::Error;
+use intmap::{self, IntMap};
+use pointer::{SharedPointerKind, RcKind};
+
+/// Identifies a node in a `Graph`. Ids are never reused within a graph or
+/// the graphs made from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+/// A persistent directed graph with weights `N` on nodes and `E` on edges.
+/// There is at most one edge from one node to another. See the module
+/// documentation.
+pub struct Graph<N, E, P: SharedPointerKind = RcKind> {
+    nodes: IntMap<N, P>,
+    edges: IntMap<IntMap<E, P>, P>,
+    edge_count: usize,
+    next: u32
+}
+
+impl<N, E, P: SharedPointerKind> Clone for Graph<N, E, P> {
+    fn clone(&self) -> Graph<N, E, P> {
+        Graph {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            edge_count: self.edge_count,
+            next: self.next
+        }
+    }
+}
+
+impl<N, E> Graph<N, E> {
+    /// Return an empty graph that shares nodes using `Rc`.
+    pub fn new() -> Graph<N, E> {
+        Graph::empty()
+    }
+}
+
+impl<N, E, P: SharedPointerKind> Default for Graph<N, E, P> {
+    fn default() -> Graph<N, E, P> {
+        Graph::empty()
+    }
+}
+
+impl<N, E, P: SharedPointerKind> Graph<N, E, P> {
+    /// Return an empty graph.
+    pub fn empty() -> Graph<N, E, P> {
+        Graph { nodes: IntMap::empty(), edges: IntMap::empty(), edge_count: 0, next: 0 }
+    }
+
+    /// The number of nodes.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The number of edges.
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    /// Return a graph with a new node, and the new node's id.
+    ///
+    /// # Panics
+    ///
+    /// If `intmap::KEY_LIMIT` (2^20) nodes have already been added.
+    pub fn add_node(&self, weight: N) -> (Graph<N, E, P>, NodeId) {
+        let id = NodeId(self.next);
+        let graph = Graph {
+            nodes: self.nodes.insert(id.0, weight),
+            edges: self.edges.clone(),
+            edge_count: self.edge_count,
+            next: self.next + 1
+        };
+        (graph, id)
+    }
+
+    /// Like `add_node`, but with an error instead of a panic if the graph
+    /// can't have any more nodes.
+    pub fn try_add_node(&self, weight: N) -> Result<(Graph<N, E, P>, NodeId), Error> {
+        if self.next >= intmap::KEY_LIMIT {
+            return Err(Error::CapacityExceeded { capacity: intmap::KEY_LIMIT as usize });
+        }
+        Ok(self.add_node(weight))
+    }
+
+    /// The weight of the node `id`, or None if there is no such node.
+    pub fn node(&self, id: NodeId) -> Option<&N> {
+        self.nodes.get(id.0)
+    }
+
+    /// Return a graph with an edge from `from` to `to`, replacing any edge
+    /// already there. Returns None if either node isn't in the graph.
+    pub fn add_edge(&self, from: NodeId, to: NodeId, weight: E) -> Option<Graph<N, E, P>> {
+        if !self.nodes.contains_key(from.0) || !self.nodes.contains_key(to.0) {
+            return None;
+        }
+        let out = self.out_edges(from);
+        let added = !out.contains_key(to.0);
+        Some(Graph {
+            nodes: self.nodes.clone(),
+            edges: self.edges.insert(from.0, out.insert(to.0, weight)),
+            edge_count: if added { self.edge_count + 1 } else { self.edge_count },
+            next: self.next
+        })
+    }
+
+    /// Return a graph without the edge from `from` to `to`. If there is no
+    /// such edge, this returns a copy of `self`.
+    pub fn remove_edge(&self, from: NodeId, to: NodeId) -> Graph<N, E, P> {
+        if self.edge(from, to).is_none() {
+            return self.clone();
+        }
+        Graph {
+            nodes: self.nodes.clone(),
+            edges: self.edges.insert(from.0, self.out_edges(from).remove(to.0)),
+            edge_count: self.edge_count - 1,
+            next: self.next
+        }
+    }
+
+    /// The weight of the edge from `from` to `to`, or None if there is no
+    /// such edge.
+    pub fn edge(&self, from: NodeId, to: NodeId) -> Option<&E> {
+        self.edges.get(from.0)?.get(to.0)
+    }
+
+    /// Iterate over the nodes and their weights, in the order they were added.
+    pub fn nodes(&self) -> Nodes<'_, N, P> {
+        Nodes(self.nodes.iter())
+    }
+
+    /// Iterate over the edges leaving `id`: each target node and the edge's
+    /// weight. If there is no such node, the iterator is empty.
+    pub fn neighbors(&self, id: NodeId) -> Neighbors<'_, E, P> {
+        Neighbors(self.edges.get(id.0).map(IntMap::iter))
+    }
+
+    fn out_edges(&self, from: NodeId) -> IntMap<E, P> {
+        self.edges.get(from.0).cloned().unwrap_or_default()
+    }
+}
+
+/// An iterator over the nodes of a `Graph`.
+pub struct Nodes<'a, N: 'a, P: SharedPointerKind + 'a = RcKind>(intmap::Iter<'a, N, P>);
+
+impl<'a, N, P: SharedPointerKind> Iterator for Nodes<'a, N, P> {
+    type Item = (NodeId, &'a N);
+
+    fn next(&mut self) -> Option<(NodeId, &'a N)> {
+        self.0.next().map(|(id, weight)| (NodeId(id), weight))
+    }
+}
+
+/// An iterator over the edges leaving a node of a `Graph`.
+pub struct Neighbors<'a, E: 'a, P: SharedPointerKind + 'a = RcKind>(Option<intmap::Iter<'a, E, P>>);
+
+impl<'a, E, P: SharedPointerKind> Iterator for Neighbors<'a, E, P> {
+    type Item = (NodeId, &'a E);
+
+    fn next(&mut self) -> Option<(NodeId, &'a E)> {
+        self.0.as_mut()?.next().map(|(id, weight)| (NodeId(id), weight))
+    }
+}
+
+impl<N: fmt::Debug, E: fmt::Debug, P: SharedPointerKind> fmt::Debug for Graph<N, E, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let edges: Vec<_> = self.nodes()
+            .flat_map(|(from, _)| self.neighbors(from).map(move |(to, e)| (from.0, to.0, e)))
+            .collect();
+        f.debug_struct("Graph")
+            .field("nodes", &self.nodes)
+            .field("edges", &edges)
+            .finish()
+    }
+}
@@ -0,0 +1,577 @@
+//! Hash array mapped tries
+//!
+//! Not from Okasaki: this is Bagwell's HAMT, for values that can be hashed
+//! but not ordered. A `HashSet` is a trie on the bits of each value's hash,
+//! five bits per level, so it is never more than 13 levels deep. Each branch
+//! node has a 32-bit bitmap saying which of its 32 possible children exist,
+//! and stores only those, in order. Values whose hashes are entirely equal
+//! share a collision node at the bottom.
+//!
+//! Every set hashes with `DefaultHasher::new()`, so that the same value lands
+//! in the same place in every set. That's what lets `union` and
+//! `intersection` work node by node, and skip any subtrie the two sets share.
+//! The flip side is that the hash is predictable, so don't build these from
+//! untrusted input that could be crafted to collide.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::slice;
+use pointer::{SharedPointerKind, RcKind};
+use traits::Set;
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The bit for `hash` in the bitmap of a branch at the given level.
+fn bit(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+// The position among a branch's entries of the entry for `bit`.
+fn position(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+struct Leaf<T, P: SharedPointerKind> {
+    hash: u64,
+    value: P::Pointer<T>
+}
+
+impl<T, P: SharedPointerKind> Clone for Leaf<T, P> {
+    fn clone(&self) -> Leaf<T, P> {
+        Leaf { hash: self.hash, value: self.value.clone() }
+    }
+}
+
+enum Entry<T, P: SharedPointerKind> {
+    Leaf(Leaf<T, P>),
+    Node(P::Pointer<Node<T, P>>)
+}
+
+impl<T, P: SharedPointerKind> Clone for Entry<T, P> {
+    fn clone(&self) -> Entry<T, P> {
+        match *self {
+            Entry::Leaf(ref leaf) => Entry::Leaf(leaf.clone()),
+            Entry::Node(ref node) => Entry::Node(node.clone())
+        }
+    }
+}
+
+impl<T, P: SharedPointerKind> Entry<T, P> {
+    fn size(&self) -> usize {
+        match *self {
+            Entry::Leaf(_) => 1,
+            Entry::Node(ref node) => node.size()
+        }
+    }
+}
+
+enum Node<T, P: SharedPointerKind> {
+    // `size` is the number of values in this subtrie, so that `len` and
+    // `intersection` can count without walking.
+    Branch { bitmap: u32, size: usize, entries: Vec<Entry<T, P>> },
+    Collision { hash: u64, leaves: Vec<Leaf<T, P>> }
+}
+
+use self::Node::*;
+
+impl<T, P: SharedPointerKind> Node<T, P> {
+    fn size(&self) -> usize {
+        match *self {
+            Branch { size, .. } => size,
+            Collision { ref leaves, .. } => leaves.len()
+        }
+    }
+}
+
+fn branch<T, P: SharedPointerKind>(bitmap: u32, entries: Vec<Entry<T, P>>) -> Node<T, P> {
+    let size = entries.iter().map(Entry::size).sum();
+    Branch { bitmap, size, entries }
+}
+
+// Make a node at level `shift` holding two entries with different hashes.
+fn join<T, P: SharedPointerKind>(a: (u64, Entry<T, P>), b: (u64, Entry<T, P>), shift: u32) -> Node<T, P> {
+    let (bit_a, bit_b) = (bit(a.0, shift), bit(b.0, shift));
+    if bit_a == bit_b {
+        branch(bit_a, vec![Entry::Node(P::new(join(a, b, shift + BITS)))])
+    } else if bit_a < bit_b {
+        branch(bit_a | bit_b, vec![a.1, b.1])
+    } else {
+        branch(bit_a | bit_b, vec![b.1, a.1])
+    }
+}
+
+// Make a node at level `shift` holding two different values.
+fn pair<T, P: SharedPointerKind>(a: Leaf<T, P>, b: Leaf<T, P>, shift: u32) -> Node<T, P> {
+    if a.hash == b.hash {
+        Collision { hash: a.hash, leaves: vec![a, b] }
+    } else {
+        join((a.hash, Entry::Leaf(a)), (b.hash, Entry::Leaf(b)), shift)
+    }
+}
+
+fn same<T: Eq, P: SharedPointerKind>(leaf: &Leaf<T, P>, hash: u64, value: &T) -> bool {
+    leaf.hash == hash && *leaf.value == *value
+}
+
+// Find the leaf for `value`, which has the given hash, in the subtrie `node`
+// at level `shift`.
+fn find<'a, T: Eq, P: SharedPointerKind>(mut node: &'a Node<T, P>, hash: u64, value: &T, mut shift: u32) -> Option<&'a Leaf<T, P>> {
+    loop {
+        match *node {
+            Branch { bitmap, ref entries, .. } => {
+                let b = bit(hash, shift);
+                if bitmap & b == 0 {
+                    return None;
+                }
+                match entries[position(bitmap, b)] {
+                    Entry::Leaf(ref l) => return if same(l, hash, value) { Some(l) } else { None },
+                    Entry::Node(ref child) => {
+                        node = child;
+                        shift += BITS;
+                    }
+                }
+            }
+            Collision { ref leaves, .. } => return leaves.iter().find(|l| same(l, hash, value))
+        }
+    }
+}
+
+// Add `leaf` to the subtrie `node` at level `shift`. If an equal value is
+// already there, replace it if `replace` is true, or else return None, meaning
+// no change.
+fn insert<T: Eq, P: SharedPointerKind>(node: &P::Pointer<Node<T, P>>, leaf: Leaf<T, P>, shift: u32, replace: bool) -> Option<Node<T, P>> {
+    match **node {
+        Branch { bitmap, ref entries, .. } => {
+            let b = bit(leaf.hash, shift);
+            let i = position(bitmap, b);
+            let mut entries = entries.clone();
+            if bitmap & b == 0 {
+                entries.insert(i, Entry::Leaf(leaf));
+                return Some(branch(bitmap | b, entries));
+            }
+            entries[i] = match entries[i] {
+                Entry::Leaf(ref old) if same(old, leaf.hash, &leaf.value) => {
+                    if !replace {
+                        return None;
+                    }
+                    Entry::Leaf(leaf)
+                }
+                Entry::Leaf(ref old) => Entry::Node(P::new(pair(old.clone(), leaf, shift + BITS))),
+                Entry::Node(ref child) => Entry::Node(P::new(insert(child, leaf, shift + BITS, replace)?))
+            };
+            Some(branch(bitmap, entries))
+        }
+        Collision { hash, ref leaves } => {
+            if leaf.hash != hash {
+                return Some(join((hash, Entry::Node(node.clone())), (leaf.hash, Entry::Leaf(leaf)), shift));
+            }
+            let mut leaves = leaves.clone();
+            match leaves.iter().position(|l| same(l, leaf.hash, &leaf.value)) {
+                Some(_) if !replace => return None,
+                Some(i) => leaves[i] = leaf,
+                None => leaves.push(leaf)
+            }
+            Some(Collision { hash, leaves })
+        }
+    }
+}
+
+fn modify<T, P, F>(node: &Node<T, P>, hash: u64, key: &T, shift: u32, f: F) -> Option<Node<T, P>>
+    where T: Hash + Eq, P: SharedPointerKind, F: FnOnce(&T) -> T
+{
+    match *node {
+        Branch { bitmap, ref entries, .. } => {
+            let b = bit(hash, shift);
+            if bitmap & b == 0 {
+                return None;
+            }
+            let i = position(bitmap, b);
+            let mut entries = entries.clone();
+            entries[i] = match entries[i] {
+                Entry::Leaf(ref old) if same(old, hash, key) => Entry::Leaf(update(old, f)?),
+                Entry::Leaf(_) => return None,
+                Entry::Node(ref child) => Entry::Node(P::new(modify(child, hash, key, shift + BITS, f)?))
+            };
+            Some(branch(bitmap, entries))
+        }
+        Collision { hash, ref leaves } => {
+            let i = leaves.iter().position(|l| same(l, hash, key))?;
+            let mut leaves = leaves.clone();
+            leaves[i] = update(&leaves[i], f)?;
+            Some(Collision { hash, leaves })
+        }
+    }
+}
+
+// Remove `value`, which has the given hash, from the subtrie `node` at level
+// `shift`. Returns None, meaning no change, if it isn't there.
+fn remove<T: Eq, P: SharedPointerKind>(node: &Node<T, P>, hash: u64, value: &T, shift: u32) -> Option<Node<T, P>> {
+    match *node {
+        Branch { bitmap, ref entries, .. } => {
+            let b = bit(hash, shift);
+            if bitmap & b == 0 {
+                return None;
+            }
+            let i = position(bitmap, b);
+            let rest = match entries[i] {
+                Entry::Leaf(ref old) if same(old, hash, value) => None,
+                Entry::Leaf(_) => return None,
+                Entry::Node(ref child) => shrink(P::new(remove(child, hash, value, shift + BITS)?))
+            };
+            let mut entries = entries.clone();
+            match rest {
+                None => {
+                    entries.remove(i);
+                    Some(branch(bitmap & !b, entries))
+                }
+                Some(entry) => {
+                    entries[i] = entry;
+                    Some(branch(bitmap, entries))
+                }
+            }
+        }
+        Collision { hash: h, ref leaves } => {
+            let i = leaves.iter().position(|l| same(l, hash, value))?;
+            let mut leaves = leaves.clone();
+            leaves.remove(i);
+            Some(Collision { hash: h, leaves })
+        }
+    }
+}
+
+// Replace the value in `old` with `f(value)`, which must be equal.
+fn update<T, P, F>(old: &Leaf<T, P>, f: F) -> Option<Leaf<T, P>>
+    where T: Hash + Eq, P: SharedPointerKind, F: FnOnce(&T) -> T
+{
+    let value = f(&old.value);
+    if value == *old.value && hash_of(&value) == old.hash {
+        Some(Leaf { hash: old.hash, value: P::new(value) })
+    } else {
+        None
+    }
+}
+
+// The union of two subtries at level `shift`. Where both have equal values,
+// the one from `a` is kept.
+fn union<T: Eq, P: SharedPointerKind>(a: &P::Pointer<Node<T, P>>, b: &P::Pointer<Node<T, P>>, shift: u32) -> P::Pointer<Node<T, P>> {
+    if P::ptr_eq(a, b) {
+        return a.clone();
+    }
+    match (&**a, &**b) {
+        (&Branch { bitmap: bitmap_a, entries: ref entries_a, .. },
+         &Branch { bitmap: bitmap_b, entries: ref entries_b, .. }) => {
+            let bitmap = bitmap_a | bitmap_b;
+            let mut entries = Vec::with_capacity(bitmap.count_ones() as usize);
+            let (mut ia, mut ib) = (entries_a.iter(), entries_b.iter());
+            for i in 0..32 {
+                let b = 1 << i;
+                match (bitmap_a & b != 0, bitmap_b & b != 0) {
+                    (false, false) => {}
+                    (true, false) => entries.extend(ia.next().cloned()),
+                    (false, true) => entries.extend(ib.next().cloned()),
+                    (true, true) => {
+                        let (ea, eb) = (ia.next().expect("bitmap"), ib.next().expect("bitmap"));
+                        entries.push(union_entries(ea, eb, shift + BITS));
+                    }
+                }
+            }
+            P::new(branch(bitmap, entries))
+        }
+        (Collision { leaves, .. }, _) => {
+            leaves.iter().fold(b.clone(), |acc, leaf| match insert(&acc, leaf.clone(), shift, true) {
+                Some(node) => P::new(node),
+                None => acc
+            })
+        }
+        (_, Collision { leaves, .. }) => {
+            leaves.iter().fold(a.clone(), |acc, leaf| match insert(&acc, leaf.clone(), shift, false) {
+                Some(node) => P::new(node),
+                None => acc
+            })
+        }
+    }
+}
+
+fn union_entries<T: Eq, P: SharedPointerKind>(a: &Entry<T, P>, b: &Entry<T, P>, shift: u32) -> Entry<T, P> {
+    match (a, b) {
+        (Entry::Leaf(la), Entry::Leaf(lb)) => {
+            if same(la, lb.hash, &lb.value) {
+                a.clone()
+            } else {
+                Entry::Node(P::new(pair(la.clone(), lb.clone(), shift)))
+            }
+        }
+        (Entry::Leaf(la), Entry::Node(nb)) => match insert(nb, la.clone(), shift, true) {
+            Some(node) => Entry::Node(P::new(node)),
+            None => b.clone()
+        },
+        (Entry::Node(na), Entry::Leaf(lb)) => match insert(na, lb.clone(), shift, false) {
+            Some(node) => Entry::Node(P::new(node)),
+            None => a.clone()
+        },
+        (Entry::Node(na), Entry::Node(nb)) => Entry::Node(union::<T, P>(na, nb, shift))
+    }
+}
+
+// The values of subtrie `a` that are also in `b`, both at level `shift`.
+fn intersection<T: Eq, P: SharedPointerKind>(a: &P::Pointer<Node<T, P>>, b: &P::Pointer<Node<T, P>>, shift: u32) -> P::Pointer<Node<T, P>> {
+    if P::ptr_eq(a, b) {
+        return a.clone();
+    }
+    match (&**a, &**b) {
+        (&Branch { bitmap: bitmap_a, entries: ref entries_a, .. },
+         &Branch { bitmap: bitmap_b, entries: ref entries_b, .. }) => {
+            let mut bitmap = 0;
+            let mut entries = vec![];
+            let (mut ia, mut ib) = (entries_a.iter(), entries_b.iter());
+            for i in 0..32 {
+                let b = 1 << i;
+                let ea = if bitmap_a & b != 0 { ia.next() } else { None };
+                let eb = if bitmap_b & b != 0 { ib.next() } else { None };
+                if let (Some(ea), Some(eb)) = (ea, eb) {
+                    if let Some(e) = intersect_entries(ea, eb, shift + BITS) {
+                        bitmap |= b;
+                        entries.push(e);
+                    }
+                }
+            }
+            P::new(branch(bitmap, entries))
+        }
+        // Collisions are rare and small, so look each of the colliding values
+        // up in the other subtrie.
+        (Collision { leaves, .. }, _) => {
+            let kept = leaves.iter().filter(|l| find(b, l.hash, &l.value, shift).is_some());
+            build(kept.cloned(), shift)
+        }
+        (_, Collision { leaves, .. }) => {
+            let kept = leaves.iter().filter_map(|l| find(a, l.hash, &l.value, shift));
+            build(kept.cloned(), shift)
+        }
+    }
+}
+
+fn intersect_entries<T: Eq, P: SharedPointerKind>(a: &Entry<T, P>, b: &Entry<T, P>, shift: u32) -> Option<Entry<T, P>> {
+    match (a, b) {
+        (Entry::Leaf(la), Entry::Leaf(lb)) => if same(la, lb.hash, &lb.value) { Some(a.clone()) } else { None },
+        (Entry::Leaf(la), Entry::Node(nb)) => find(nb, la.hash, &la.value, shift).map(|_| a.clone()),
+        (Entry::Node(na), Entry::Leaf(lb)) => find(na, lb.hash, &lb.value, shift).map(|l| Entry::Leaf(l.clone())),
+        (Entry::Node(na), Entry::Node(nb)) => shrink(intersection::<T, P>(na, nb, shift))
+    }
+}
+
+// Make a subtrie at level `shift` from distinct leaves.
+fn build<T: Eq, P, I>(leaves: I, shift: u32) -> P::Pointer<Node<T, P>>
+    where P: SharedPointerKind, I: Iterator<Item=Leaf<T, P>>
+{
+    leaves.fold(P::new(branch(0, vec![])), |acc, leaf| match insert(&acc, leaf, shift, false) {
+        Some(node) => P::new(node),
+        None => acc
+    })
+}
+
+// Turn a subtrie back into an entry, replacing nodes with fewer than two
+// values by what they hold.
+fn shrink<T, P: SharedPointerKind>(node: P::Pointer<Node<T, P>>) -> Option<Entry<T, P>> {
+    match *node {
+        Branch { ref entries, .. } if entries.is_empty() => None,
+        Branch { ref entries, .. } if entries.len() == 1 => match entries[0] {
+            Entry::Leaf(ref leaf) => Some(Entry::Leaf(leaf.clone())),
+            Entry::Node(_) => Some(Entry::Node(node.clone()))
+        },
+        Collision { ref leaves, .. } if leaves.len() == 1 => Some(Entry::Leaf(leaves[0].clone())),
+        _ => Some(Entry::Node(node.clone()))
+    }
+}
+
+/// A persistent set of hashable values, implemented as a hash array mapped
+/// trie. See the module documentation.
+pub struct HashSet<T, P: SharedPointerKind = RcKind> {
+    root: P::Pointer<Node<T, P>>
+}
+
+impl<T, P: SharedPointerKind> Clone for HashSet<T, P> {
+    fn clone(&self) -> HashSet<T, P> {
+        HashSet { root: self.root.clone() }
+    }
+}
+
+impl<T: Hash + Eq> HashSet<T> {
+    /// Return an empty set that shares nodes using `Rc`.
+    pub fn new() -> HashSet<T> {
+        Set::empty()
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> Default for HashSet<T, P> {
+    fn default() -> HashSet<T, P> {
+        Set::empty()
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> Set for HashSet<T, P> {
+    type Item = T;
+
+    fn empty() -> HashSet<T, P> {
+        HashSet { root: P::new(branch(0, vec![])) }
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+
+    fn plus(&self, value: T) -> HashSet<T, P> {
+        let leaf = Leaf { hash: hash_of(&value), value: P::new(value) };
+        match insert(&self.root, leaf, 0, false) {
+            None => self.clone(),
+            Some(root) => HashSet { root: P::new(root) }
+        }
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        find(&self.root, hash_of(value), value, 0).is_some()
+    }
+
+    fn modify<F: FnOnce(&T) -> T>(&self, key: &T, f: F) -> Option<HashSet<T, P>> {
+        let root = modify(&self.root, hash_of(key), key, 0, f)?;
+        Some(HashSet { root: P::new(root) })
+    }
+
+    fn minus_all<I: IntoIterator<Item=T>>(&self, values: I) -> HashSet<T, P> {
+        let mut root = self.root.clone();
+        for value in values {
+            if let Some(node) = remove(&root, hash_of(&value), &value, 0) {
+                root = P::new(node);
+            }
+        }
+        HashSet { root }
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> HashSet<T, P> {
+    /// The number of values in the set. This takes constant time.
+    pub fn len(&self) -> usize {
+        self.root.size()
+    }
+
+    /// Return true if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the values in the set, in no particular order.
+    pub fn iter(&self) -> Iter<'_, T, P> {
+        let mut iter = Iter { stack: vec![], collision: [].iter() };
+        iter.push_node(&self.root);
+        iter
+    }
+
+    /// Return the union of two sets. Where both have equal values, the one
+    /// from `self` is kept.
+    ///
+    /// Subtries the two sets share are reused without being visited, so the
+    /// union of a set with a slightly changed copy of itself is cheap.
+    pub fn union(&self, other: &HashSet<T, P>) -> HashSet<T, P> {
+        HashSet { root: union::<T, P>(&self.root, &other.root, 0) }
+    }
+
+    /// Return the set of values in both `self` and `other`. Like `union`,
+    /// this reuses shared subtries without visiting them.
+    pub fn intersection(&self, other: &HashSet<T, P>) -> HashSet<T, P> {
+        HashSet { root: intersection::<T, P>(&self.root, &other.root, 0) }
+    }
+}
+
+/// An iterator over the values of a `HashSet`.
+pub struct Iter<'a, T, P: SharedPointerKind = RcKind> {
+    stack: Vec<&'a Entry<T, P>>,
+    collision: slice::Iter<'a, Leaf<T, P>>
+}
+
+impl<'a, T, P: SharedPointerKind> Iter<'a, T, P> {
+    fn push_node(&mut self, node: &'a Node<T, P>) {
+        match *node {
+            Branch { ref entries, .. } => self.stack.extend(entries.iter().rev()),
+            Collision { ref leaves, .. } => self.collision = leaves.iter()
+        }
+    }
+
+    fn next_leaf(&mut self) -> Option<&'a Leaf<T, P>> {
+        loop {
+            if let Some(leaf) = self.collision.next() {
+                return Some(leaf);
+            }
+            match *self.stack.pop()? {
+                Entry::Leaf(ref leaf) => return Some(leaf),
+                Entry::Node(ref node) => self.push_node(node)
+            }
+        }
+    }
+}
+
+impl<'a, T, P: SharedPointerKind> Iterator for Iter<'a, T, P> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.next_leaf().map(|leaf| &*leaf.value)
+    }
+}
+
+impl<'a, T: Hash + Eq, P: SharedPointerKind> IntoIterator for &'a HashSet<T, P> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, P>;
+
+    fn into_iter(self) -> Iter<'a, T, P> {
+        self.iter()
+    }
+}
+
+impl<T: Hash + Eq + Clone, P: SharedPointerKind> IntoIterator for HashSet<T, P> {
+    type Item = T;
+    type IntoIter = <Vec<T> as IntoIterator>::IntoIter;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter().cloned().collect::<Vec<T>>().into_iter()
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> FromIterator<T> for HashSet<T, P> {
+    fn from_iter<Iterable: IntoIterator<Item=T>>(iterator: Iterable) -> HashSet<T, P> {
+        let mut set = HashSet::empty();
+        set.extend(iterator);
+        set
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> Extend<T> for HashSet<T, P> {
+    fn extend<Iterable: IntoIterator<Item=T>>(&mut self, iterator: Iterable) {
+        for v in iterator {
+            self.add(v);
+        }
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> PartialEq for HashSet<T, P> {
+    fn eq(&self, other: &HashSet<T, P>) -> bool {
+        P::ptr_eq(&self.root, &other.root)
+            || (self.len() == other.len() && self.iter().all(|v| other.contains(v)))
+    }
+}
+
+impl<T: Hash + Eq, P: SharedPointerKind> Eq for HashSet<T, P> {}
+
+impl<T: fmt::Debug, P: SharedPointerKind> fmt::Debug for HashSet<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("HashSet")?;
+        let mut iter = Iter { stack: vec![], collision: [].iter() };
+        iter.push_node(&self.root);
+        f.debug_set().entries(iter).finish()
+    }
+}
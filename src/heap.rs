@@ -1,51 +1,105 @@
 //! 3.1 Leftist heaps
 
 use std::cmp::Ordering;
-use std::rc::Rc;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+use dot;
+use pointer::{SharedPointerKind, RcKind, ArcKind};
+use pretty::{pretty, Shape};
+use sharing::{self, Linked};
+use snapshot::{decoded, DecodeError, Encoder, HeapRecord, Snapshot};
+use stats::HeapStats;
 use traits::Heap;
+use validate::{InvariantError, Violation};
 
-struct HeapNode<V> {
+// Values are behind their own pointers so that `merge` can copy nodes without
+// cloning values.
+struct HeapNode<V, P: SharedPointerKind> {
     rank: usize,
-    value: V,
-    left: LeftistHeap<V>,
-    right: LeftistHeap<V>
+    value: P::Pointer<V>,
+    left: LeftistHeap<V, P>,
+    right: LeftistHeap<V, P>
 }
 
-#[derive(Clone)]
-enum HeapImpl<V> {
+enum HeapImpl<V, P: SharedPointerKind> {
     Empty,
-    NonEmpty(Rc<HeapNode<V>>)
+    NonEmpty(P::Pointer<HeapNode<V, P>>)
 }
 
 use self::HeapImpl::*;
 
+// Only the right spine is short; a heap built by inserting values in
+// decreasing order is one long left spine. So, as in `tree`, dropping a node
+// unlinks its children and takes apart any that nothing else shares from an
+// explicit stack, rather than recursing.
+impl<V, P: SharedPointerKind> Drop for HeapNode<V, P> {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        self.unlink_children(&mut stack);
+        while let Some(mut rc) = stack.pop() {
+            if let Some(node) = P::get_mut(&mut rc) {
+                node.unlink_children(&mut stack);
+            }
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind> HeapNode<V, P> {
+    fn unlink_children(&mut self, stack: &mut Vec<P::Pointer<HeapNode<V, P>>>) {
+        for child in [&mut self.left, &mut self.right] {
+            if let NonEmpty(rc) = mem::replace(&mut child.0, Empty) {
+                stack.push(rc);
+            }
+        }
+    }
+}
+
 /// Simple persistent heap implementation. For documentation, see the `Heap` trait.
-#[derive(Clone)]
-pub struct LeftistHeap<V>(HeapImpl<V>);
+pub struct LeftistHeap<V, P: SharedPointerKind = RcKind>(HeapImpl<V, P>);
+
+impl<V, P: SharedPointerKind> Clone for LeftistHeap<V, P> {
+    fn clone(&self) -> LeftistHeap<V, P> {
+        match self.0 {
+            Empty => LeftistHeap(Empty),
+            NonEmpty(ref rc) => LeftistHeap(NonEmpty(rc.clone()))
+        }
+    }
+}
+
+impl<V> LeftistHeap<V> {
+    /// Return an empty heap that shares nodes using `Rc`.
+    pub fn new() -> LeftistHeap<V> { LeftistHeap(Empty) }
+}
+
+impl<V, P: SharedPointerKind> Default for LeftistHeap<V, P> {
+    fn default() -> LeftistHeap<V, P> { LeftistHeap(Empty) }
+}
 
-impl<V> HeapImpl<V> {
+impl<V, P: SharedPointerKind> HeapImpl<V, P> {
     fn rank(&self) -> usize {
         match *self {
             Empty => 0,
-            NonEmpty(ref rc) => (*rc).rank
+            NonEmpty(ref rc) => rc.rank
         }
     }
 }
 
-fn make_heap<V: Clone>(x: V, a: LeftistHeap<V>, b: LeftistHeap<V>) -> LeftistHeap<V> {
+fn make_heap<V, P: SharedPointerKind>(x: P::Pointer<V>, a: LeftistHeap<V, P>, b: LeftistHeap<V, P>) -> LeftistHeap<V, P> {
     let LeftistHeap(ai) = a;
     let LeftistHeap(bi) = b;
     let ra = ai.rank();
     let rb = bi.rank();
     if ra >= rb {
-        LeftistHeap(NonEmpty(Rc::new(HeapNode {
+        LeftistHeap(NonEmpty(P::new(HeapNode {
             rank: rb + 1,
             value: x,
             left: LeftistHeap(ai),
             right: LeftistHeap(bi)
         })))
     } else {
-        LeftistHeap(NonEmpty(Rc::new(HeapNode {
+        LeftistHeap(NonEmpty(P::new(HeapNode {
             rank: ra + 1,
             value: x,
             left: LeftistHeap(bi),
@@ -54,20 +108,21 @@ fn make_heap<V: Clone>(x: V, a: LeftistHeap<V>, b: LeftistHeap<V>) -> LeftistHea
     }
 }
 
-impl<V: Clone + Ord> Heap for LeftistHeap<V> {
+impl<V: Ord, P: SharedPointerKind> Heap for LeftistHeap<V, P> {
     type Item = V;
 
-    fn empty() -> LeftistHeap<V> { LeftistHeap(Empty) }
+    fn empty() -> LeftistHeap<V, P> { LeftistHeap(Empty) }
 
     fn is_empty(&self) -> bool {
-        match *self {
-            LeftistHeap(Empty) => true,
-            _ => false
-        }
+        matches!(*self, LeftistHeap(Empty))
     }
 
-    fn merge(h1: LeftistHeap<V>, h2: LeftistHeap<V>) -> LeftistHeap<V> {
-        match (h1, h2) {
+    /// This recurses once per node on the right spines of the two heaps,
+    /// and a right spine is never longer than log2(n + 1), so the recursion
+    /// is shallow even for huge heaps.
+    ///
+    fn merge(h1: LeftistHeap<V, P>, h2: LeftistHeap<V, P>) -> LeftistHeap<V, P> {
+        let result = match (h1, h2) {
             (LeftistHeap(Empty), h) => h,
             (h, LeftistHeap(Empty)) => h,
             (LeftistHeap(NonEmpty(n1)), LeftistHeap(NonEmpty(n2))) => {
@@ -83,13 +138,15 @@ impl<V: Clone + Ord> Heap for LeftistHeap<V> {
                                                  LeftistHeap(NonEmpty(n2))))
                 }
             }
-        }
+        };
+        strict_check!(result);
+        result
     }
 
-    fn insert(&self, value: V) -> LeftistHeap<V> {
-        LeftistHeap::merge(self.clone(), LeftistHeap(NonEmpty(Rc::new(HeapNode {
+    fn insert(&self, value: V) -> LeftistHeap<V, P> {
+        LeftistHeap::merge(self.clone(), LeftistHeap(NonEmpty(P::new(HeapNode {
             rank: 1,
-            value: value,
+            value: P::new(value),
             left: LeftistHeap(Empty),
             right: LeftistHeap(Empty)
         }))))
@@ -102,10 +159,385 @@ impl<V: Clone + Ord> Heap for LeftistHeap<V> {
         }
     }
 
-    fn without_min(&self) -> LeftistHeap<V> {
+    fn without_min(&self) -> LeftistHeap<V, P> {
         match *self {
             LeftistHeap(Empty) => LeftistHeap(Empty),
             LeftistHeap(NonEmpty(ref n)) => LeftistHeap::merge(n.left.clone(), n.right.clone())
         }
     }
 }
+
+impl<V: Ord, P: SharedPointerKind> FromIterator<V> for LeftistHeap<V, P> {
+    /// Build a heap in O(n) time, as in exercise 3.3: make a singleton heap
+    /// of each value, then merge the heaps in pairs, over and over, until
+    /// only one is left.
+    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> LeftistHeap<V, P> {
+        let mut heaps: Vec<LeftistHeap<V, P>> = iterator.into_iter()
+            .map(|v| LeftistHeap::empty().insert(v))
+            .collect();
+        while heaps.len() > 1 {
+            let mut pairs = Vec::with_capacity(heaps.len().div_ceil(2));
+            let mut rest = heaps.into_iter();
+            while let Some(a) = rest.next() {
+                pairs.push(match rest.next() {
+                    Some(b) => LeftistHeap::merge(a, b),
+                    None => a
+                });
+            }
+            heaps = pairs;
+        }
+        heaps.pop().unwrap_or_default()
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Extend<V> for LeftistHeap<V, P> {
+    /// Build a heap of the new values, then merge it in. This takes O(k + log
+    /// n) time for k new values.
+    fn extend<Iterable: IntoIterator<Item=V>>(&mut self, iterator: Iterable) {
+        let new: LeftistHeap<V, P> = iterator.into_iter().collect();
+        *self = LeftistHeap::merge(self.clone(), new);
+    }
+}
+
+
+impl<V: Ord, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Check that no value is less than its parent, and that every node has
+    /// the correct rank, no greater than the rank of its left subtree. See
+    /// the `validate` module.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        // Each node's stored rank is checked against its right child's, so
+        // if every check passes, every stored rank is correct, and the
+        // leftist check can use them. As in `Tree::validate`, `path` leads
+        // to the subtree checked last; each entry says how much of it leads
+        // to the entry's parent, and the step from there.
+        let mut path = String::new();
+        let mut stack = vec![(self, None, 0, None)];
+        while let Some((heap, parent, len, step)) = stack.pop() {
+            path.truncate(len);
+            path.extend(step);
+            if let NonEmpty(ref rc) = heap.0 {
+                let v = &*rc.value;
+                if parent.is_some_and(|p| v < p) {
+                    return Err(InvariantError::new(Violation::HeapOrder, &path));
+                }
+                if rc.left.0.rank() < rc.right.0.rank() {
+                    return Err(InvariantError::new(Violation::Leftist, &path));
+                }
+                if rc.rank != rc.right.0.rank() + 1 {
+                    return Err(InvariantError::new(Violation::Rank, &path));
+                }
+                stack.push((&rc.right, Some(v), path.len(), Some('R')));
+                stack.push((&rc.left, Some(v), path.len(), Some('L')));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Return a copy of this heap, with the same shape, that shares nodes
+    /// using pointer kind `Q`.
+    ///
+    /// This copies every node and clones every value once.
+    ///
+    pub fn to_pointer_kind<Q: SharedPointerKind>(&self) -> LeftistHeap<V, Q> {
+        self.map_shape(&mut |v: &V| v.clone())
+    }
+}
+
+impl<V: Clone> LeftistHeap<V> {
+    /// Return a thread-safe copy of this heap.
+    pub fn to_shared(&self) -> LeftistHeap<V, ArcKind> { self.to_pointer_kind() }
+}
+
+impl<V: Clone> LeftistHeap<V, ArcKind> {
+    /// Return a copy of this heap that uses `Rc` to share nodes.
+    pub fn to_local(&self) -> LeftistHeap<V> { self.to_pointer_kind() }
+}
+
+impl<V, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Return a heap of the same shape holding `f` of each value.
+    ///
+    /// This takes O(n) time and does no comparisons, but the result is a
+    /// valid heap only if `f` is monotone: `a <= b` must imply `f(a) <=
+    /// f(b)`. Adding a constant to every priority is the usual example.
+    ///
+    pub fn map_monotone<U: Ord, F: FnMut(&V) -> U>(&self, mut f: F) -> LeftistHeap<U, P> {
+        let result = self.map_monotone_by(&mut f);
+        strict_check!(result);
+        result
+    }
+
+    fn map_monotone_by<U, F: FnMut(&V) -> U>(&self, f: &mut F) -> LeftistHeap<U, P> {
+        self.map_shape(f)
+    }
+
+    // Build a heap of the same shape and ranks, using pointer kind `Q`,
+    // holding `f` of each value. `f` is called on each parent before its
+    // children.
+    fn map_shape<U, Q: SharedPointerKind, F: FnMut(&V) -> U>(&self, f: &mut F) -> LeftistHeap<U, Q> {
+        // Each node is on the stack twice: once to map its value, and again
+        // (with its rank) to build it once its subtrees are built.
+        let mut stack = vec![(self, None)];
+        let mut heaps = vec![];
+        let mut values = vec![];
+        while let Some((heap, rank)) = stack.pop() {
+            match (&heap.0, rank) {
+                (Empty, _) => heaps.push(LeftistHeap(Empty)),
+                (NonEmpty(n), None) => {
+                    values.push(f(&n.value));
+                    stack.push((heap, Some(n.rank)));
+                    stack.push((&n.right, None));
+                    stack.push((&n.left, None));
+                }
+                (NonEmpty(_), Some(rank)) => {
+                    let right = heaps.pop().expect("right subtree was built");
+                    let left = heaps.pop().expect("left subtree was built");
+                    let value = values.pop().expect("value was mapped");
+                    heaps.push(LeftistHeap(NonEmpty(Q::new(HeapNode {
+                        rank,
+                        value: Q::new(value),
+                        left,
+                        right
+                    }))));
+                }
+            }
+        }
+        heaps.pop().expect("heap was built")
+    }
+
+    /// Return a heap holding `f` of each value. Unlike `map_monotone`, this
+    /// works for any `f`, by building a new heap from scratch, in O(n) time.
+    pub fn map<U: Ord, F: FnMut(&V) -> U>(&self, f: F) -> LeftistHeap<U, P> {
+        self.value_refs().into_iter().map(f).collect()
+    }
+
+    /// Return all the values, in no particular order. Values are moved out
+    /// of nodes no other heap shares, and cloned from the rest. This visits
+    /// each node once, taking O(n) time; popping the values in order would
+    /// take O(n log n).
+    pub fn into_vec_unordered(self) -> Vec<V>
+        where V: Clone
+    {
+        let mut values = vec![];
+        let mut stack = vec![];
+        if let NonEmpty(rc) = self.0 {
+            stack.push(rc);
+        }
+        while let Some(rc) = stack.pop() {
+            match P::try_unwrap(rc) {
+                Ok(mut node) => {
+                    node.unlink_children(&mut stack);
+                    // `HeapNode` has a `Drop` impl, so the value's pointer
+                    // can't be moved out; copy it, and once the node is gone
+                    // the copy may be the only one.
+                    let value = node.value.clone();
+                    drop(node);
+                    values.push(P::try_unwrap(value).unwrap_or_else(|v| (*v).clone()));
+                }
+                Err(rc) => {
+                    values.push((*rc.value).clone());
+                    for child in [&rc.left, &rc.right] {
+                        if let NonEmpty(ref child) = child.0 {
+                            stack.push(child.clone());
+                        }
+                    }
+                }
+            }
+        }
+        values
+    }
+
+    // Return references to all the values, in no particular order.
+    fn value_refs(&self) -> Vec<&V> {
+        let mut values = vec![];
+        let mut stack = vec![self];
+        while let Some(heap) = stack.pop() {
+            if let NonEmpty(ref n) = heap.0 {
+                values.push(&*n.value);
+                stack.push(&n.right);
+                stack.push(&n.left);
+            }
+        }
+        values
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Encode several versions of a heap as a `Snapshot`. Nodes and values
+    /// shared between versions are written only once.
+    pub fn encode(versions: &[LeftistHeap<V, P>]) -> Snapshot<V, HeapRecord> {
+        let mut encoder = Encoder::new();
+        for h in versions {
+            let id = h.encode_node(&mut encoder);
+            encoder.root(id);
+        }
+        encoder.finish()
+    }
+
+    // Encode the nodes of this heap that aren't already encoded, children
+    // first, and return the root's id. See `Tree::encode_node`.
+    fn encode_node(&self, encoder: &mut Encoder<V, HeapRecord>) -> Option<usize> {
+        let mut stack = vec![(self, false)];
+        let mut ids = vec![];
+        while let Some((heap, subtrees_done)) = stack.pop() {
+            let node: &HeapNode<V, P> = match heap.0 {
+                Empty => {
+                    ids.push(None);
+                    continue;
+                }
+                NonEmpty(ref rc) => rc
+            };
+            if subtrees_done {
+                let right = ids.pop().expect("right subtree was encoded");
+                let left = ids.pop().expect("left subtree was encoded");
+                let value = encoder.value(&node.value);
+                ids.push(Some(encoder.node(node, HeapRecord { rank: node.rank, value, left, right })));
+            } else if let Some(id) = encoder.lookup(node) {
+                ids.push(Some(id));
+            } else {
+                stack.push((heap, true));
+                stack.push((&node.right, false));
+                stack.push((&node.left, false));
+            }
+        }
+        ids.pop().expect("root was encoded")
+    }
+
+    /// Rebuild the versions stored in a snapshot, restoring the sharing
+    /// between them.
+    pub fn decode(snapshot: &Snapshot<V, HeapRecord>) -> Result<Vec<LeftistHeap<V, P>>, DecodeError> {
+        let values: Vec<P::Pointer<V>> = snapshot.values.iter().map(|v| P::new(v.clone())).collect();
+        let mut heaps: Vec<LeftistHeap<V, P>> = Vec::with_capacity(snapshot.nodes.len());
+        for (i, r) in snapshot.nodes.iter().enumerate() {
+            snapshot.check_value(i, r.value)?;
+            snapshot.check_child(i, r.left)?;
+            snapshot.check_child(i, r.right)?;
+            heaps.push(LeftistHeap(NonEmpty(P::new(HeapNode {
+                rank: r.rank,
+                value: values[r.value].clone(),
+                left: decoded(&heaps, r.left),
+                right: decoded(&heaps, r.right)
+            }))));
+        }
+        snapshot.roots.iter().map(|&root| {
+            snapshot.check_root(root)?;
+            Ok(decoded(&heaps, root))
+        }).collect()
+    }
+}
+
+impl<V, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Return the distribution of node depths and ranks in this heap. See the
+    /// `stats` module.
+    pub fn stats(&self) -> HeapStats {
+        let mut stats = HeapStats::default();
+        let mut stack = vec![(self, 0)];
+        while let Some((heap, depth)) = stack.pop() {
+            if let NonEmpty(ref rc) = heap.0 {
+                stats.shape.add(depth);
+                if stats.ranks.len() <= rc.rank {
+                    stats.ranks.resize(rc.rank + 1, 0);
+                }
+                stats.ranks[rc.rank] += 1;
+                stack.push((&rc.right, depth + 1));
+                stack.push((&rc.left, depth + 1));
+            }
+        }
+        stats
+    }
+}
+
+impl<V, P: SharedPointerKind> Linked for LeftistHeap<V, P> {
+    fn root_addr(&self) -> Option<usize> {
+        match self.0 {
+            Empty => None,
+            NonEmpty(ref rc) => Some(&**rc as *const HeapNode<V, P> as usize)
+        }
+    }
+
+    fn children(&self) -> (Option<&LeftistHeap<V, P>>, Option<&LeftistHeap<V, P>>) {
+        match self.0 {
+            Empty => (None, None),
+            NonEmpty(ref rc) => (Some(&rc.left), Some(&rc.right))
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Return true if `self` and `other` are the same version of the heap:
+    /// both empty, or with the same root node. This takes constant time. If it
+    /// returns true, the two are certainly equal; if not, they may still be
+    /// equal, and `==` will say.
+    pub fn same(&self, other: &LeftistHeap<V, P>) -> bool {
+        sharing::same(self, other)
+    }
+
+    /// Return true if `self` and `other` have any nodes in common, as when
+    /// one was made from the other. This takes time proportional to the
+    /// number of nodes in both.
+    ///
+    pub fn shares_structure_with(&self, other: &LeftistHeap<V, P>) -> bool {
+        sharing::shares_structure(&[self], &[other])
+    }
+
+    /// Return the number of nodes `self` and `other` have in common. This
+    /// takes time proportional to the number of nodes in both.
+    pub fn shared_node_count(&self, other: &LeftistHeap<V, P>) -> usize {
+        sharing::shared_count(&[self], &[other])
+    }
+
+    /// Return the number of nodes in `self` that are not also in `other`:
+    /// how much memory keeping `self` costs, on top of keeping `other`.
+    pub fn unique_node_count(&self, other: &LeftistHeap<V, P>) -> usize {
+        sharing::unique_count(&[self], &[other])
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> Shape for LeftistHeap<V, P> {
+    fn node(&self) -> Option<(String, &LeftistHeap<V, P>, &LeftistHeap<V, P>)> {
+        match self.0 {
+            Empty => None,
+            NonEmpty(ref rc) => Some((format!("{:?} rank {}", *rc.value, rc.rank), &rc.left, &rc.right))
+        }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Draw the shape of this heap as indented text, one node per line, with
+    /// each node's rank, for debugging. An empty subtree whose sibling isn't
+    /// empty is shown as `·`.
+    pub fn pretty(&self) -> String {
+        pretty(self)
+    }
+}
+
+impl<V: Clone + fmt::Debug, P: SharedPointerKind> LeftistHeap<V, P> {
+    /// Render this heap as a Graphviz graph, in DOT format, with one vertex
+    /// per node.
+    pub fn to_dot(&self) -> String {
+        LeftistHeap::versions_to_dot(slice::from_ref(self))
+    }
+
+    /// Render several versions of a heap as one Graphviz graph, in DOT
+    /// format. Nodes shared by more than one version appear only once, so the
+    /// graph shows exactly how much structure the versions share.
+    ///
+    pub fn versions_to_dot(versions: &[LeftistHeap<V, P>]) -> String {
+        dot::render("LeftistHeap", &LeftistHeap::encode(versions))
+    }
+}
+
+impl<V: Ord + fmt::Debug, P: SharedPointerKind> fmt::Debug for LeftistHeap<V, P> {
+    /// Heaps are shown in sorted order. This costs O(n log n) time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("LeftistHeap")?;
+        let mut entries = f.debug_list();
+        let mut h = self.clone();
+        while let Some(v) = h.min() {
+            entries.entry(v);
+            h = h.without_min();
+        }
+        entries.finish()
+    }
+}
@@ -0,0 +1,101 @@
+//! Undo and redo.
+//!
+//! Keeping an old version of a persistent structure costs nothing but a
+//! pointer copy, so an undo stack is just a list of versions. `History` keeps
+//! one, with a limit on how far back it goes.
+//!
+//! ```ignore
+//! let mut doc = History::new(RBTree::new(), 100);
+//! doc.update(|s| s.plus("alpha"));
+//! doc.update(|s| s.plus("beta"));
+//! doc.undo();
+//! assert!(!doc.current().contains(&"beta"));
+//! doc.redo();
+//! ```
+
+use std::collections::VecDeque;
+use std::mem;
+
+/// A current version of some structure, plus earlier versions to go back to
+/// and undone versions to go forward to.
+#[derive(Clone, Debug)]
+pub struct History<T> {
+    // Oldest first.
+    past: VecDeque<T>,
+    present: T,
+    // Most recently undone last.
+    future: Vec<T>,
+    limit: usize
+}
+
+impl<T> History<T> {
+    /// Start a history at `initial`, remembering at most `limit` earlier
+    /// versions. Once there are more, the oldest are forgotten.
+    pub fn new(initial: T, limit: usize) -> History<T> {
+        History {
+            past: VecDeque::new(),
+            present: initial,
+            future: vec![],
+            limit
+        }
+    }
+
+    /// The current version.
+    pub fn current(&self) -> &T {
+        &self.present
+    }
+
+    /// Make `version` the current version. The previous one becomes undoable,
+    /// and anything that was undone can no longer be redone.
+    pub fn checkpoint(&mut self, version: T) {
+        self.future.clear();
+        self.push_past(version);
+    }
+
+    /// Make a new version by applying `f` to the current one, and checkpoint
+    /// it.
+    pub fn update<F: FnOnce(&T) -> T>(&mut self, f: F) {
+        let version = f(&self.present);
+        self.checkpoint(version);
+    }
+
+    /// Go back to the previous version, and return it. If there is no
+    /// previous version, nothing changes, and this returns None.
+    pub fn undo(&mut self) -> Option<&T> {
+        let version = self.past.pop_back()?;
+        let undone = mem::replace(&mut self.present, version);
+        self.future.push(undone);
+        Some(&self.present)
+    }
+
+    /// Go forward to the most recently undone version, and return it. If
+    /// nothing has been undone since the last checkpoint, nothing changes, and
+    /// this returns None.
+    pub fn redo(&mut self) -> Option<&T> {
+        let version = self.future.pop()?;
+        self.push_past(version);
+        Some(&self.present)
+    }
+
+    /// The number of versions `undo` can go back to.
+    pub fn undo_len(&self) -> usize {
+        self.past.len()
+    }
+
+    /// The number of versions `redo` can go forward to.
+    pub fn redo_len(&self) -> usize {
+        self.future.len()
+    }
+
+    // Make `version` current, saving the old current version as the most
+    // recent past one.
+    fn push_past(&mut self, version: T) {
+        let old = mem::replace(&mut self.present, version);
+        if self.limit > 0 {
+            if self.past.len() == self.limit {
+                self.past.pop_front();
+            }
+            self.past.push_back(old);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Queues of keyed items that can be removed by key.
+//!
+//! An `IndexedQueue` is a `BatchedQueue` of items plus an index saying which
+//! item, if any, each key currently refers to. Removing an item only updates
+//! the index; the item stays in the queue, stale, until it reaches the front,
+//! where `split` discards it. So `remove` and `contains_key` take O(log n)
+//! time, and `split` takes O(log n) amortized time.
+
+use std::cmp::Ordering;
+use std::fmt;
+use compare::Compare;
+use pointer::{SharedPointerKind, RcKind};
+use queue::BatchedQueue;
+use rbtree::RBTree;
+use traits::{Queue, Set};
+
+// An item in the queue, with the serial number that makes it distinguishable
+// from earlier items with the same key.
+struct Job<K, V> {
+    item: (K, V),
+    serial: u64
+}
+
+// The serial number of the live item for each key in the queue. Slots are
+// ordered by key alone, so they can be looked up, replaced and removed by key.
+struct Slot<K> {
+    key: K,
+    serial: u64
+}
+
+enum ByKey {}
+
+impl<K: Ord> Compare<Slot<K>> for ByKey {
+    fn compare(a: &Slot<K>, b: &Slot<K>) -> Ordering {
+        a.key.cmp(&b.key)
+    }
+}
+
+/// A persistent FIFO queue of `(key, value)` pairs, supporting removal by key.
+/// See the module documentation.
+///
+/// Each key is in the queue at most once: `snoc` with a key that's already
+/// present removes the old item, and the new one goes to the back.
+pub struct IndexedQueue<K, V, P: SharedPointerKind = RcKind> {
+    jobs: BatchedQueue<Job<K, V>, P>,
+    index: RBTree<Slot<K>, P, ByKey>,
+    next: u64,
+    len: usize
+}
+
+impl<K, V, P: SharedPointerKind> Clone for IndexedQueue<K, V, P> {
+    fn clone(&self) -> IndexedQueue<K, V, P> {
+        IndexedQueue {
+            jobs: self.jobs.clone(),
+            index: self.index.clone(),
+            next: self.next,
+            len: self.len
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> IndexedQueue<K, V> {
+    /// Return an empty queue that shares nodes using `Rc`.
+    pub fn new() -> IndexedQueue<K, V> {
+        Queue::empty()
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> Default for IndexedQueue<K, V, P> {
+    fn default() -> IndexedQueue<K, V, P> {
+        Queue::empty()
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> Queue for IndexedQueue<K, V, P> {
+    type Item = (K, V);
+
+    fn empty() -> IndexedQueue<K, V, P> {
+        IndexedQueue { jobs: BatchedQueue::empty(), index: RBTree::empty(), next: 0, len: 0 }
+    }
+
+    /// Add an item to the back of the queue, removing any item with the same
+    /// key.
+    fn snoc(self, item: (K, V)) -> IndexedQueue<K, V, P> {
+        let serial = self.next;
+        let key = item.0.clone();
+        let len = if self.contains_key(&key) { self.len } else { self.len + 1 };
+        IndexedQueue {
+            jobs: Queue::snoc(self.jobs, Job { item, serial }),
+            index: self.index.replace(Slot { key, serial }),
+            next: serial + 1,
+            len
+        }.cleaned()
+    }
+
+    fn split(&self) -> Option<(&(K, V), IndexedQueue<K, V, P>)> {
+        let (job, jobs) = self.jobs.split()?;
+        let rest = IndexedQueue {
+            jobs,
+            index: self.index.remove_by(|slot| job.item.0.cmp(&slot.key))
+                .expect("the head is live"),
+            next: self.next,
+            len: self.len - 1
+        };
+        Some((&job.item, rest.cleaned()))
+    }
+
+    fn head(&self) -> Option<&(K, V)> {
+        self.jobs.head().map(|job| &job.item)
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> IndexedQueue<K, V, P> {
+    /// The number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return true if the queue has an item with the given key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.live_serial(key).is_some()
+    }
+
+    /// Return a queue without the item that has the given key, or None if
+    /// there is no such item. The other items keep their order.
+    pub fn remove(&self, key: &K) -> Option<IndexedQueue<K, V, P>> {
+        Some(IndexedQueue {
+            jobs: self.jobs.clone(),
+            index: self.index.remove_by(|slot| key.cmp(&slot.key))?,
+            next: self.next,
+            len: self.len - 1
+        }.cleaned())
+    }
+
+    fn live_serial(&self, key: &K) -> Option<u64> {
+        self.index.find_by(|slot| key.cmp(&slot.key)).map(|slot| slot.serial)
+    }
+
+    // Drop stale items from the front of the queue, so that `head` is always
+    // live.
+    fn cleaned(mut self) -> IndexedQueue<K, V, P> {
+        while let Some(job) = self.jobs.head() {
+            if self.live_serial(&job.item.0) == Some(job.serial) {
+                break;
+            }
+            self.jobs = self.jobs.tail().unwrap_or_default();
+        }
+        self
+    }
+}
+
+impl<K, V, P> fmt::Debug for IndexedQueue<K, V, P>
+    where K: Ord + Clone + fmt::Debug, V: fmt::Debug, P: SharedPointerKind
+{
+    /// Shown as a map from keys to values, in queue order.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("IndexedQueue")?;
+        let mut map = f.debug_map();
+        let mut q = self.clone();
+        while let Some((item, rest)) = q.split() {
+            map.entry(&item.0, &item.1);
+            q = rest;
+        }
+        map.finish()
+    }
+}
@@ -0,0 +1,427 @@
+//! Maps keyed by small integers
+//!
+//! An `IntMap` is a trie on the bits of its keys, five bits per level, most
+//! significant first, so iterating visits the keys in order. Keys are less
+//! than 2^20, so the trie is always exactly four levels deep, and finding a
+//! key takes four steps with no comparisons. As in `hashset`, each node has a
+//! 32-bit bitmap saying which of its 32 possible children exist, and stores
+//! only those.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+use error::Error;
+use pointer::{SharedPointerKind, RcKind};
+use traits::Nested;
+
+/// Keys in an `IntMap` must be less than this.
+pub const KEY_LIMIT: u32 = 1 << 20;
+
+const BITS: u32 = 5;
+const MASK: u32 = (1 << BITS) - 1;
+const TOP_SHIFT: u32 = 15;
+
+fn bit(key: u32, shift: u32) -> u32 {
+    1 << ((key >> shift) & MASK)
+}
+
+fn position(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+// Nodes at shift 0 hold values; the others hold nodes. Neither kind is ever
+// empty, except the root.
+enum Node<V, P: SharedPointerKind> {
+    Branch { bitmap: u32, children: Vec<P::Pointer<Node<V, P>>> },
+    Leaf { bitmap: u32, values: Vec<P::Pointer<V>> }
+}
+
+use self::Node::*;
+
+impl<V, P: SharedPointerKind> Node<V, P> {
+    fn bitmap(&self) -> u32 {
+        match *self {
+            Branch { bitmap, .. } | Leaf { bitmap, .. } => bitmap
+        }
+    }
+}
+
+// A path of nodes holding just the one value.
+fn singleton<V, P: SharedPointerKind>(key: u32, value: P::Pointer<V>, shift: u32) -> Node<V, P> {
+    if shift == 0 {
+        Leaf { bitmap: bit(key, 0), values: vec![value] }
+    } else {
+        Branch { bitmap: bit(key, shift), children: vec![P::new(singleton(key, value, shift - BITS))] }
+    }
+}
+
+// Return the node with `key` set to `value`, and true if the key is new.
+fn insert<V, P: SharedPointerKind>(node: &Node<V, P>, key: u32, value: P::Pointer<V>, shift: u32) -> (Node<V, P>, bool) {
+    let b = bit(key, shift);
+    let bitmap = node.bitmap();
+    let i = position(bitmap, b);
+    let present = bitmap & b != 0;
+    match *node {
+        Leaf { ref values, .. } => {
+            let mut values = values.clone();
+            if present {
+                values[i] = value;
+            } else {
+                values.insert(i, value);
+            }
+            (Leaf { bitmap: bitmap | b, values }, !present)
+        }
+        Branch { ref children, .. } => {
+            let mut children = children.clone();
+            let added = if present {
+                let (child, added) = insert(&children[i], key, value, shift - BITS);
+                children[i] = P::new(child);
+                added
+            } else {
+                children.insert(i, P::new(singleton(key, value, shift - BITS)));
+                true
+            };
+            (Branch { bitmap: bitmap | b, children }, added)
+        }
+    }
+}
+
+// Return the node without `key`, or None if that leaves it empty. The caller
+// has already checked that the key is present.
+fn remove<V, P: SharedPointerKind>(node: &Node<V, P>, key: u32, shift: u32) -> Option<Node<V, P>> {
+    let b = bit(key, shift);
+    let bitmap = node.bitmap();
+    let i = position(bitmap, b);
+    match *node {
+        Leaf { ref values, .. } => {
+            if bitmap == b {
+                return None;
+            }
+            let mut values = values.clone();
+            values.remove(i);
+            Some(Leaf { bitmap: bitmap & !b, values })
+        }
+        Branch { ref children, .. } => {
+            let mut children = children.clone();
+            match remove(&children[i], key, shift - BITS) {
+                Some(child) => {
+                    children[i] = P::new(child);
+                    Some(Branch { bitmap, children })
+                }
+                None if bitmap == b => None,
+                None => {
+                    children.remove(i);
+                    Some(Branch { bitmap: bitmap & !b, children })
+                }
+            }
+        }
+    }
+}
+
+// Move the entries under `node` into `out`, in order by key. Nodes and values
+// that nothing else shares are taken apart; shared ones are cloned.
+fn take_entries<V: Clone, P: SharedPointerKind>(node: P::Pointer<Node<V, P>>, prefix: u32, shift: u32, out: &mut Vec<(u32, V)>) {
+    let node = P::try_unwrap(node).unwrap_or_else(|node| match *node {
+        Branch { bitmap, ref children } => Branch { bitmap, children: children.clone() },
+        Leaf { bitmap, ref values } => Leaf { bitmap, values: values.clone() }
+    });
+    let mut bits = node.bitmap();
+    match node {
+        Branch { children, .. } => {
+            for child in children {
+                let low = bits.trailing_zeros();
+                bits &= bits - 1;
+                take_entries::<V, P>(child, prefix | (low << shift), shift - BITS, out);
+            }
+        }
+        Leaf { values, .. } => {
+            for value in values {
+                let low = bits.trailing_zeros();
+                bits &= bits - 1;
+                out.push((prefix | low, P::try_unwrap(value).unwrap_or_else(|v| (*v).clone())));
+            }
+        }
+    }
+}
+
+/// A persistent map from integers less than `KEY_LIMIT` (2^20) to values.
+/// See the module documentation.
+///
+/// For keys like these, this is faster and more compact than a search tree:
+/// every operation takes four steps, and nodes hold up to 32 entries.
+pub struct IntMap<V, P: SharedPointerKind = RcKind> {
+    root: P::Pointer<Node<V, P>>,
+    len: usize
+}
+
+impl<V, P: SharedPointerKind> Clone for IntMap<V, P> {
+    fn clone(&self) -> IntMap<V, P> {
+        IntMap { root: self.root.clone(), len: self.len }
+    }
+}
+
+impl<V> IntMap<V> {
+    /// Return an empty map that shares nodes using `Rc`.
+    pub fn new() -> IntMap<V> {
+        IntMap::empty()
+    }
+}
+
+impl<V, P: SharedPointerKind> Default for IntMap<V, P> {
+    fn default() -> IntMap<V, P> {
+        IntMap::empty()
+    }
+}
+
+impl<V, P: SharedPointerKind> IntMap<V, P> {
+    /// Return an empty map.
+    pub fn empty() -> IntMap<V, P> {
+        IntMap { root: P::new(Branch { bitmap: 0, children: vec![] }), len: 0 }
+    }
+
+    /// The number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The value for `key`, or None if the key isn't in the map.
+    pub fn get(&self, key: u32) -> Option<&V> {
+        if key >= KEY_LIMIT {
+            return None;
+        }
+        let mut node = &*self.root;
+        let mut shift = TOP_SHIFT;
+        loop {
+            let b = bit(key, shift);
+            if node.bitmap() & b == 0 {
+                return None;
+            }
+            let i = position(node.bitmap(), b);
+            match *node {
+                Leaf { ref values, .. } => return Some(&values[i]),
+                Branch { ref children, .. } => {
+                    node = &children[i];
+                    shift -= BITS;
+                }
+            }
+        }
+    }
+
+    /// Return true if `key` is in the map.
+    pub fn contains_key(&self, key: u32) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Return a map with `key` set to `value`, replacing any old value.
+    ///
+    /// # Panics
+    ///
+    /// If `key` is not less than `KEY_LIMIT`.
+    pub fn insert(&self, key: u32, value: V) -> IntMap<V, P> {
+        assert!(key < KEY_LIMIT, "IntMap key {} is out of range", key);
+        let (root, added) = insert(&self.root, key, P::new(value), TOP_SHIFT);
+        IntMap { root: P::new(root), len: if added { self.len + 1 } else { self.len } }
+    }
+
+    /// Like `insert`, but with an error instead of a panic if `key` is not
+    /// less than `KEY_LIMIT`.
+    pub fn try_insert(&self, key: u32, value: V) -> Result<IntMap<V, P>, Error> {
+        if key >= KEY_LIMIT {
+            return Err(Error::KeyOutOfRange { key, limit: KEY_LIMIT });
+        }
+        Ok(self.insert(key, value))
+    }
+
+    /// Return a map without `key`. If the key isn't in the map, this returns
+    /// a copy of `self`.
+    pub fn remove(&self, key: u32) -> IntMap<V, P> {
+        if !self.contains_key(key) {
+            return self.clone();
+        }
+        match remove(&self.root, key, TOP_SHIFT) {
+            Some(root) => IntMap { root: P::new(root), len: self.len - 1 },
+            None => IntMap::empty()
+        }
+    }
+
+    /// Return a map with the value for `key` replaced by `f(old)`, where
+    /// `old` is the current value or None. If `f` returns None, the key is
+    /// removed.
+    ///
+    /// # Panics
+    ///
+    /// If `f` returns a value and `key` is not less than `KEY_LIMIT`.
+    pub fn update<F>(&self, key: u32, f: F) -> IntMap<V, P>
+        where F: FnOnce(Option<&V>) -> Option<V>
+    {
+        match f(self.get(key)) {
+            Some(value) => self.insert(key, value),
+            None => self.remove(key)
+        }
+    }
+
+    /// Update a value in maps nested inside this one, as with
+    /// `RBMap::update_in`. Only the maps along `path` are rebuilt.
+    ///
+    /// # Panics
+    ///
+    /// If `path` is empty, or a key in it is not less than `KEY_LIMIT`.
+    pub fn update_in<F>(&self, path: &[u32], f: F) -> IntMap<V, P>
+        where V: Nested<IntMap<V, P>>, F: FnOnce(Option<&V>) -> Option<V>
+    {
+        let (&key, rest) = path.split_first().expect("update_in: empty path");
+        if rest.is_empty() {
+            return self.update(key, f);
+        }
+        let child = match self.get(key).and_then(V::as_map) {
+            Some(map) => map.update_in(rest, f),
+            None => IntMap::empty().update_in(rest, f)
+        };
+        self.insert(key, V::from_map(child))
+    }
+
+    /// Iterate over the keys and values in the map, in order by key.
+    pub fn iter(&self) -> Iter<'_, V, P> {
+        Iter { stack: vec![(&self.root, 0, TOP_SHIFT)], values: [].iter(), leaf_key: 0, bitmap: 0 }
+    }
+
+    /// Consume the map and iterate over its keys, in order.
+    pub fn into_keys(self) -> IntoKeys {
+        let keys = self.iter().map(|(key, _)| key).collect::<Vec<u32>>();
+        IntoKeys(keys.into_iter())
+    }
+
+    /// Consume the map and iterate over its values, in order by key. Values
+    /// are moved out of any part of the map that no other map shares, and
+    /// cloned from the rest.
+    pub fn into_values(self) -> IntoValues<V>
+        where V: Clone
+    {
+        let mut entries = Vec::with_capacity(self.len);
+        take_entries::<V, P>(self.root, 0, TOP_SHIFT, &mut entries);
+        IntoValues(entries.into_iter())
+    }
+}
+
+/// An iterator over the entries of an `IntMap`, in order by key.
+pub struct Iter<'a, V: 'a, P: SharedPointerKind + 'a = RcKind> {
+    // Nodes still to visit, with the key bits above them and their shifts.
+    // The next one is on top.
+    stack: Vec<(&'a Node<V, P>, u32, u32)>,
+    // The rest of the current leaf: its values, the key bits above it, and the
+    // bits of its bitmap not yet visited.
+    values: slice::Iter<'a, P::Pointer<V>>,
+    leaf_key: u32,
+    bitmap: u32
+}
+
+impl<'a, V, P: SharedPointerKind> Iterator for Iter<'a, V, P> {
+    type Item = (u32, &'a V);
+
+    fn next(&mut self) -> Option<(u32, &'a V)> {
+        loop {
+            if let Some(value) = self.values.next() {
+                let low = self.bitmap.trailing_zeros();
+                self.bitmap &= self.bitmap - 1;
+                return Some((self.leaf_key | low, value));
+            }
+            let (node, prefix, shift) = self.stack.pop()?;
+            match *node {
+                Leaf { bitmap, ref values } => {
+                    self.values = values.iter();
+                    self.leaf_key = prefix;
+                    self.bitmap = bitmap;
+                }
+                Branch { bitmap, ref children } => {
+                    let mut bits = bitmap;
+                    let start = self.stack.len();
+                    for child in children {
+                        let low = bits.trailing_zeros();
+                        bits &= bits - 1;
+                        self.stack.push((child, prefix | (low << shift), shift - BITS));
+                    }
+                    self.stack[start..].reverse();
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over the keys of an `IntMap`, in order. See
+/// `IntMap::into_keys`.
+pub struct IntoKeys(vec::IntoIter<u32>);
+
+impl Iterator for IntoKeys {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An iterator that moves the values out of an `IntMap`, in order by key.
+/// See `IntMap::into_values`.
+pub struct IntoValues<V>(vec::IntoIter<(u32, V)>);
+
+impl<V> Iterator for IntoValues<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.0.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> IntoIterator for &'a IntMap<V, P> {
+    type Item = (u32, &'a V);
+    type IntoIter = Iter<'a, V, P>;
+
+    fn into_iter(self) -> Iter<'a, V, P> {
+        self.iter()
+    }
+}
+
+impl<V, P: SharedPointerKind> FromIterator<(u32, V)> for IntMap<V, P> {
+    fn from_iter<Iterable: IntoIterator<Item=(u32, V)>>(iterator: Iterable) -> IntMap<V, P> {
+        let mut map = IntMap::empty();
+        map.extend(iterator);
+        map
+    }
+}
+
+impl<V, P: SharedPointerKind> Extend<(u32, V)> for IntMap<V, P> {
+    fn extend<Iterable: IntoIterator<Item=(u32, V)>>(&mut self, iterator: Iterable) {
+        for (k, v) in iterator {
+            *self = self.insert(k, v);
+        }
+    }
+}
+
+impl<V: PartialEq, P: SharedPointerKind> PartialEq for IntMap<V, P> {
+    fn eq(&self, other: &IntMap<V, P>) -> bool {
+        P::ptr_eq(&self.root, &other.root)
+            || (self.len == other.len && self.iter().eq(other.iter()))
+    }
+}
+
+impl<V: Eq, P: SharedPointerKind> Eq for IntMap<V, P> {}
+
+impl<V: fmt::Debug, P: SharedPointerKind> fmt::Debug for IntMap<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("IntMap")?;
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,345 @@
+//! Algebraic laws every implementation of the collection traits should obey.
+//!
+//! The `testing` module compares a structure against a model from `std`.
+//! The checks here are the other half: each `check_*_laws` function states
+//! the equations that tie a trait's methods together, such as "the head of
+//! `cons(x, s)` is `x`" or "merging two heaps and draining the result gives
+//! the same items as draining both", and tries them on a fixed batch of
+//! sample inputs, including empty collections, duplicates, and items added
+//! in ascending, descending, and scrambled orders.
+//!
+//! They are generic, so a downstream implementation of `Queue` can be
+//! checked the same way as `BatchedQueue`:
+//!
+//! ```ignore
+//! check_queue_laws::<MyQueue<u32>>().unwrap();
+//! check_set_laws::<RBTree<u32>>().unwrap();
+//! ```
+//!
+//! Every check also makes sure that deriving a new version leaves the old
+//! one unchanged.
+
+use std::error::Error;
+use std::fmt;
+use traits::{Heap, Queue, Set, Stack};
+
+/// The error returned when an implementation breaks a law.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LawViolation {
+    /// A short statement of the law that was broken.
+    pub law: &'static str,
+    /// The input that broke it, and what happened.
+    pub message: String
+}
+
+impl fmt::Display for LawViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.law, self.message)
+    }
+}
+
+impl Error for LawViolation {}
+
+// Fail with `law` unless `ok`. The message is only formatted on failure.
+fn check<F: FnOnce() -> String>(ok: bool, law: &'static str, message: F) -> Result<(), LawViolation> {
+    if ok {
+        Ok(())
+    } else {
+        Err(LawViolation { law, message: message() })
+    }
+}
+
+// The inputs every law is tried on.
+fn samples() -> Vec<Vec<u32>> {
+    vec![
+        vec![],
+        vec![0],
+        vec![1, 0],
+        vec![7, 7],
+        vec![3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5],
+        (0..20).collect(),
+        (0..20).rev().collect(),
+        (0..40).map(|i| i * 37 % 23).collect()
+    ]
+}
+
+// Values worth asking a set or heap about, given the items in it: every item,
+// plus some that aren't there.
+fn probes(items: &[u32]) -> Vec<u32> {
+    let top = items.iter().cloned().max().map_or(0, |m| m + 2);
+    (0..=top).collect()
+}
+
+fn sorted(items: &[u32]) -> Vec<u32> {
+    let mut v = items.to_vec();
+    v.sort();
+    v
+}
+
+/* Stacks */
+
+fn stack_from<S: Stack<Item=u32>>(items: &[u32]) -> S {
+    items.iter().rev().fold(S::empty(), |s, &v| S::cons(v, s))
+}
+
+fn stack_items<S: Stack<Item=u32>>(s: &S) -> Vec<u32> {
+    s.iter_refs().cloned().collect()
+}
+
+/// Check the `Stack` laws against `S`.
+pub fn check_stack_laws<S: Stack<Item=u32> + Clone>() -> Result<(), LawViolation> {
+    let empty = S::empty();
+    check(empty.is_empty() && empty.split().is_none(),
+          "the empty stack has no items", || "split returned Some".to_string())?;
+
+    for items in samples() {
+        let s: S = stack_from(&items);
+        check(stack_items(&s) == items, "iter_refs gives items top first",
+              || format!("built from {:?}, got {:?}", items, stack_items(&s)))?;
+        check(s.is_empty() == items.is_empty(), "is_empty agrees with split",
+              || format!("{:?}: is_empty returned {}", items, s.is_empty()))?;
+
+        let c = S::cons(99, s.clone());
+        let ok = match c.split() {
+            Some((&h, t)) => h == 99 && stack_items(t) == items,
+            None => false
+        };
+        check(ok, "split(cons(x, s)) is (x, s)", || format!("s = {:?}", items))?;
+        check(c.head() == Some(&99) && c.tail().map(stack_items) == Some(items.clone()),
+              "head and tail agree with split", || format!("s = {:?}", items))?;
+        check(stack_items(&s.clone().prepend(99)) == stack_items(&c), "prepend is cons",
+              || format!("s = {:?}", items))?;
+
+        let mut m = s.clone();
+        m.push(99);
+        check(stack_items(&m) == stack_items(&c), "push is cons", || format!("s = {:?}", items))?;
+        let popped = m.pop();
+        check(popped == Some(99) && stack_items(&m) == items, "pop undoes push",
+              || format!("s = {:?}, pop returned {:?}", items, popped))?;
+
+        let mut reversed = items.clone();
+        reversed.reverse();
+        check(stack_items(&s.reverse()) == reversed, "reverse reverses the items",
+              || format!("s = {:?}", items))?;
+        check(stack_items(&s.reverse().reverse()) == items, "reverse is its own inverse",
+              || format!("s = {:?}", items))?;
+
+        for other in samples() {
+            let joined = s.append(stack_from(&other));
+            let expected: Vec<u32> = items.iter().chain(&other).cloned().collect();
+            check(stack_items(&joined) == expected, "append concatenates",
+                  || format!("{:?} ++ {:?} gave {:?}", items, other, stack_items(&joined)))?;
+        }
+
+        check(stack_items(&s) == items, "deriving a stack leaves the original unchanged",
+              || format!("{:?} became {:?}", items, stack_items(&s)))?;
+    }
+    Ok(())
+}
+
+/* Queues */
+
+fn queue_from<Q: Queue<Item=u32>>(items: &[u32]) -> Q {
+    items.iter().fold(Q::empty(), |q, &v| q.snoc(v))
+}
+
+fn queue_items<Q: Queue<Item=u32> + Clone>(q: &Q) -> Vec<u32> {
+    let mut items = vec![];
+    let mut q = q.clone();
+    while let Some((&v, rest)) = q.split() {
+        items.push(v);
+        q = rest;
+    }
+    items
+}
+
+/// Check the `Queue` laws against `Q`.
+pub fn check_queue_laws<Q: Queue<Item=u32> + Clone>() -> Result<(), LawViolation> {
+    let empty = Q::empty();
+    check(empty.is_empty() && empty.split().is_none() && empty.head().is_none()
+              && empty.tail().is_none(),
+          "the empty queue has no items", || "head, tail or split returned Some".to_string())?;
+
+    for items in samples() {
+        let q: Q = queue_from(&items);
+        check(queue_items(&q) == items, "items come out in the order they went in",
+              || format!("put in {:?}, got {:?}", items, queue_items(&q)))?;
+        check(q.is_empty() == items.is_empty(), "is_empty agrees with split",
+              || format!("{:?}: is_empty returned {}", items, q.is_empty()))?;
+        check(q.head() == items.first(), "head agrees with split",
+              || format!("{:?}: head returned {:?}", items, q.head()))?;
+        check(q.tail().map(|t| queue_items(&t)) == q.split().map(|(_, t)| queue_items(&t)),
+              "tail agrees with split", || format!("q = {:?}", items))?;
+
+        let s = q.clone().snoc(99);
+        check(!s.is_empty(), "snoc makes a nonempty queue", || format!("q = {:?}", items))?;
+        let expected_head = if items.is_empty() { Some(&99) } else { items.first() };
+        check(s.head() == expected_head, "snoc doesn't change the head of a nonempty queue",
+              || format!("q = {:?}, head of q.snoc(99) is {:?}", items, s.head()))?;
+        if let Some(t) = q.tail() {
+            check(s.tail().map(|st| queue_items(&st)) == Some(queue_items(&t.snoc(99))),
+                  "tail(q.snoc(x)) is tail(q).snoc(x)", || format!("q = {:?}", items))?;
+        }
+
+        let mut pushed = q.clone();
+        pushed.push_back(99);
+        check(queue_items(&pushed) == queue_items(&s), "push_back is snoc",
+              || format!("q = {:?}", items))?;
+
+        for n in 0..=items.len() + 1 {
+            let (first, rest) = q.clone().split_at(n);
+            let k = n.min(items.len());
+            check(queue_items(&first) == items[..k] && queue_items(&rest) == items[k..],
+                  "split_at(n) divides the queue after n items",
+                  || format!("q = {:?}, n = {}: got {:?} and {:?}",
+                             items, n, queue_items(&first), queue_items(&rest)))?;
+        }
+
+        check(queue_items(&q) == items, "deriving a queue leaves the original unchanged",
+              || format!("{:?} became {:?}", items, queue_items(&q)))?;
+    }
+    Ok(())
+}
+
+/* Sets */
+
+fn set_from<S: Set<Item=u32>>(items: &[u32]) -> S {
+    items.iter().fold(S::empty(), |s, &v| s.plus(v))
+}
+
+// The set's members among `probes`, the only way to observe a `Set`.
+fn members<S: Set<Item=u32>>(s: &S, probes: &[u32]) -> Vec<u32> {
+    probes.iter().cloned().filter(|v| s.contains(v)).collect()
+}
+
+/// Check the `Set` laws against `S`.
+///
+/// Sets are observed only through `contains`, and two items count as the
+/// same if they are equal as `u32`s, so this is for sets with the usual
+/// notion of equality, not ones with a custom comparator.
+pub fn check_set_laws<S>() -> Result<(), LawViolation>
+    where S: Set<Item=u32> + Clone + IntoIterator<Item=u32>
+{
+    let empty = S::empty();
+    check(members(&empty, &probes(&[])).is_empty() && empty.len() == 0 && empty.is_empty(),
+          "the empty set contains nothing", || "contains or len says otherwise".to_string())?;
+
+    for items in samples() {
+        let probes = probes(&items);
+        let s: S = set_from(&items);
+        let mut expected = sorted(&items);
+        expected.dedup();
+        check(members(&s, &probes) == expected, "a set contains exactly what was added",
+              || format!("added {:?}, contains {:?}", items, members(&s, &probes)))?;
+        check(s.len() == expected.len(), "len counts each distinct item once",
+              || format!("added {:?}, len returned {}", items, s.len()))?;
+        check(s.is_empty() == expected.is_empty(), "is_empty agrees with len",
+              || format!("added {:?}, is_empty returned {}", items, s.is_empty()))?;
+
+        for &x in &probes {
+            let p = s.plus(x);
+            let ok = probes.iter().all(|y| p.contains(y) == (*y == x || s.contains(y)));
+            check(ok, "s.plus(x) contains y iff y == x or s contains y",
+                  || format!("s = {:?}, x = {}", items, x))?;
+            check(members(&p.plus(x), &probes) == members(&p, &probes), "plus is idempotent",
+                  || format!("s = {:?}, x = {}", items, x))?;
+            check(members(&s.plus(x).plus(99), &probes) == members(&s.plus(99).plus(x), &probes),
+                  "plus is commutative", || format!("s = {:?}, x = {}", items, x))?;
+
+            let mut added = s.clone();
+            added.add(x);
+            check(members(&added, &probes) == members(&p, &probes), "add is plus",
+                  || format!("s = {:?}, x = {}", items, x))?;
+
+            let modified = s.modify(&x, |v| *v);
+            check(modified.is_some() == s.contains(&x),
+                  "modify finds exactly the items the set contains",
+                  || format!("s = {:?}, x = {}", items, x))?;
+            if let Some(m) = modified {
+                check(members(&m, &probes) == members(&s, &probes),
+                      "modifying an item to an equal one doesn't change membership",
+                      || format!("s = {:?}, x = {}", items, x))?;
+            }
+        }
+
+        let removed: Vec<u32> = probes.iter().cloned().filter(|v| v % 3 == 0).collect();
+        let m = s.minus_all(removed.iter().cloned());
+        let ok = probes.iter().all(|y| m.contains(y) == (s.contains(y) && !removed.contains(y)));
+        check(ok, "s.minus_all(r) contains y iff s contains y and r doesn't",
+              || format!("s = {:?}, r = {:?}", items, removed))?;
+
+        check(members(&s, &probes) == expected, "deriving a set leaves the original unchanged",
+              || format!("{:?} became {:?}", expected, members(&s, &probes)))?;
+    }
+    Ok(())
+}
+
+/* Heaps */
+
+fn heap_from<H: Heap<Item=u32>>(items: &[u32]) -> H {
+    items.iter().fold(H::empty(), |h, &v| h.insert(v))
+}
+
+fn heap_items<H: Heap<Item=u32> + Clone>(h: &H) -> Vec<u32> {
+    let mut items = vec![];
+    let mut h = h.clone();
+    while let Some(&v) = h.min() {
+        items.push(v);
+        h = h.without_min();
+    }
+    items
+}
+
+/// Check the `Heap` laws against `H`.
+pub fn check_heap_laws<H: Heap<Item=u32> + Clone>() -> Result<(), LawViolation> {
+    let empty = H::empty();
+    check(empty.is_empty() && empty.min().is_none(), "the empty heap has no minimum",
+          || "min returned Some".to_string())?;
+    check(empty.without_min().is_empty(), "without_min of the empty heap is empty",
+          || "it isn't".to_string())?;
+
+    for items in samples() {
+        let h: H = heap_from(&items);
+        check(heap_items(&h) == sorted(&items), "items come out in ascending order",
+              || format!("put in {:?}, got {:?}", items, heap_items(&h)))?;
+        check(h.is_empty() == items.is_empty() && h.min() == items.iter().min(),
+              "min is the least item", || format!("{:?}: min returned {:?}", items, h.min()))?;
+
+        for &x in &probes(&items) {
+            let i = h.insert(x);
+            let expected = items.iter().cloned().chain(Some(x)).min();
+            check(i.min().cloned() == expected, "min(h.insert(x)) is the lesser of min(h) and x",
+                  || format!("h = {:?}, x = {}", items, x))?;
+        }
+
+        let mut popped = h.clone();
+        let mut drained = vec![];
+        while let Some(v) = popped.pop() {
+            drained.push(v);
+        }
+        check(drained == sorted(&items), "pop removes items in ascending order",
+              || format!("put in {:?}, popped {:?}", items, drained))?;
+
+        let mut added = H::empty();
+        for &v in &items {
+            added.add(v);
+        }
+        check(heap_items(&added) == heap_items(&h), "add is insert",
+              || format!("h = {:?}", items))?;
+
+        check(heap_items(&H::merge(h.clone(), H::empty())) == sorted(&items)
+                  && heap_items(&H::merge(H::empty(), h.clone())) == sorted(&items),
+              "merging with the empty heap changes nothing", || format!("h = {:?}", items))?;
+        for other in samples() {
+            let merged = H::merge(h.clone(), heap_from(&other));
+            let mut all = items.clone();
+            all.extend(&other);
+            check(heap_items(&merged) == sorted(&all), "merge keeps every item of both heaps",
+                  || format!("merged {:?} and {:?}, got {:?}", items, other, heap_items(&merged)))?;
+        }
+
+        check(heap_items(&h) == sorted(&items), "deriving a heap leaves the original unchanged",
+              || format!("{:?} became {:?}", sorted(&items), heap_items(&h)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! 4.1 Lazy evaluation
+//!
+//! A `Lazy<T>` is a suspended computation. It isn't run until someone asks for
+//! its value with `force`, and then the result is memoized, so the computation
+//! runs at most once no matter how many times the suspension is forced.
+//!
+//! Suspensions are always thread-safe. Memoization is built on `OnceLock`, so
+//! a `Lazy<T>` is `Send + Sync` whenever `T` is, and if several threads force
+//! the same suspension at once, one of them runs the computation and the rest
+//! wait for the result. The price is that suspended closures must be `Send`.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+
+type Thunk<T> = Box<dyn FnOnce() -> T + Send>;
+
+/// A memoized suspension.
+pub struct Lazy<T> {
+    value: OnceLock<T>,
+    thunk: Mutex<Option<Thunk<T>>>
+}
+
+impl<T> Lazy<T> {
+    /// Suspend the computation `f`. It will not be run until the first call to
+    /// `force`.
+    pub fn new<F>(f: F) -> Lazy<T>
+        where F: FnOnce() -> T + Send + 'static
+    {
+        Lazy {
+            value: OnceLock::new(),
+            thunk: Mutex::new(Some(Box::new(f)))
+        }
+    }
+
+    /// Return a suspension that has already been evaluated.
+    pub fn from_value(value: T) -> Lazy<T> {
+        Lazy {
+            value: OnceLock::from(value),
+            thunk: Mutex::new(None)
+        }
+    }
+
+    /// Run the suspended computation, if it hasn't been run already, and
+    /// return a reference to the result.
+    ///
+    /// Panics if an earlier attempt to force this suspension panicked.
+    ///
+    pub fn force(&self) -> &T {
+        self.value.get_or_init(|| {
+            let thunk = self.thunk.lock().unwrap().take()
+                .expect("suspension forced again after its computation panicked");
+            thunk()
+        })
+    }
+
+    /// Return true if the computation has already been run.
+    pub fn is_forced(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    // Move the value out, if the computation has been run, leaving this
+    // suspension as if it had been forced and panicked.
+    pub(crate) fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+}
+
+impl<T> Deref for Lazy<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.force()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Lazy<T> {
+    /// Formatting a suspension never forces it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.debug_tuple("Lazy").field(&format_args!("<unforced>")).finish()
+        }
+    }
+}
@@ -1,8 +1,93 @@
 //! Data structures from Okasaki, Chris, _Purely Functional Data Structures_, 1998.
+//!
+//! # Stack use
+//!
+//! Nothing in this crate recurses more than O(log n) deep, so it's all safe
+//! to use on a small, fixed-size stack. The balanced structures (`RBTree`,
+//! `HashSet`, `IntMap`) recurse at most once per level, and a leftist heap's
+//! `merge` once per node on its right spines, which are never longer than
+//! log2(n + 1). Structures that can be as deep as they are large (`List`,
+//! `Stream`, the unbalanced `Tree`, and both kinds of heap) are walked with
+//! loops and explicit stacks instead, and that includes dropping them. So is
+//! the chain of suspended merges in a lazy `MergeSort`, one for each `add`
+//! since it was last sorted.
+//!
+//! The one exception is laziness. Forcing a stream cell runs its suspension,
+//! which may force other cells, so a stream built by stacking up n lazy
+//! operations (say, n calls to `append`, each on the result of the last)
+//! takes n nested calls to force. That depth depends on how the stream was
+//! built, not on how long it is.
 
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "quickcheck")]
+extern crate quickcheck;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rand")]
+extern crate rand;
+
+#[macro_use]
+mod macros;
+
+#[macro_use]
+pub mod validate;
+
+pub mod error;
+pub use error::Error;
+
+pub mod pointer;
+pub mod compare;
 pub mod traits;
+pub mod prelude;
 pub mod list;
 pub mod tree;
 pub mod rbtree;
+pub mod rbmap;
+pub mod hashset;
+pub mod intmap;
+pub mod graph;
 pub mod heap;
+pub mod pairing;
+pub mod minmax;
+pub mod topk;
+pub mod counter;
 pub mod queue;
+pub mod roundrobin;
+pub mod multimap;
+pub mod indexedqueue;
+pub mod lazy;
+pub mod evaluation;
+pub mod stream;
+pub mod mergesort;
+pub mod numeric;
+pub mod sort;
+pub mod sync;
+pub mod snapshot;
+pub mod display;
+pub mod testing;
+pub mod laws;
+pub mod stats;
+pub mod history;
+pub mod versioned;
+pub mod diff;
+pub mod edit;
+pub mod optics;
+pub mod defaults;
+pub use defaults::{DefaultHeap, DefaultQueue, DefaultSeq, DefaultSet};
+mod pretty;
+mod dot;
+mod sharing;
+
+#[cfg(any(feature = "quickcheck", feature = "proptest"))]
+mod arbitrary;
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "rand")]
+mod sample;
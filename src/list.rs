@@ -1,21 +1,34 @@
 //! 2.1 Lists
 
-use std::rc::Rc;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+use display::{write_separated, DisplayWith};
+use dot;
+use error::Error;
+use pointer::{SharedPointerKind, RcKind, ArcKind};
+use sharing::{self, Linked};
+use snapshot::{decoded, DecodeError, Encoder, ListRecord, Snapshot};
 use traits::Stack;
 
-pub enum List<T> {
+#[derive(Default)]
+pub enum List<T, P: SharedPointerKind = RcKind> {
+    #[default]
     Nil,
-    Cons(Rc<(T, List<T>)>)
+    Cons(P::Pointer<(T, List<T, P>)>)
 }
 
 use self::List::*;
 
-impl<V> Clone for List<V> {
+impl<V, P: SharedPointerKind> Clone for List<V, P> {
     // `#[derive(Clone)]` doesn't work on List because it (not-very-smartly)
     // drives `impl <V: Clone> Clone for List<V>` instead of the more
     // general implementation we want.
-    fn clone(&self) -> List<V> {
+    fn clone(&self) -> List<V, P> {
         match *self {
             Nil => Nil,
             Cons(ref rc) => Cons((*rc).clone())
@@ -23,23 +36,75 @@ impl<V> Clone for List<V> {
     }
 }
 
-impl<V> Stack for List<V> {
+// The default drop would recurse once per node. Instead, unlink the nodes
+// one at a time, stopping at the first one that another list still shares.
+// Each node dropped in the loop has already lost its tail, so its own drop
+// returns at once.
+impl<V, P: SharedPointerKind> Drop for List<V, P> {
+    fn drop(&mut self) {
+        let mut rest = match self.take_unshared_tail() {
+            Some(rest) => rest,
+            None => return
+        };
+        while let Some(next) = rest.take_unshared_tail() {
+            rest = next;
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind> List<V, P> {
+    // If the first node belongs to this list alone, detach and return its
+    // tail.
+    fn take_unshared_tail(&mut self) -> Option<List<V, P>> {
+        match *self {
+            Nil => None,
+            Cons(ref mut rc) => P::get_mut(rc).map(|node| mem::replace(&mut node.1, Nil))
+        }
+    }
+}
+
+impl<V> List<V> {
+    /// Return an empty list that shares nodes using `Rc`.
+    pub fn new() -> List<V> { Nil }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Remove the first item and return it, or None if the list is empty.
+    ///
+    /// If no other list shares the first node, the item is moved out of it;
+    /// otherwise it is cloned, and the node is left to the lists that still
+    /// use it. So popping every item of a list that shares nothing clones
+    /// nothing.
+    pub fn pop(&mut self) -> Option<V> {
+        // `List` has a `Drop` impl, so its node can't be moved out of it.
+        // Take a second pointer to the node instead; once `self` moves on to
+        // the tail, that pointer is the only one left if `self` was.
+        let rc = match *self {
+            Nil => return None,
+            Cons(ref rc) => rc.clone()
+        };
+        *self = rc.1.clone();
+        Some(match P::try_unwrap(rc) {
+            Ok((head, _)) => head,
+            Err(rc) => rc.0.clone()
+        })
+    }
+}
+
+impl<V, P: SharedPointerKind> Stack for List<V, P> {
     type Item = V;
 
-    fn empty() -> List<V> { Nil }
+    fn empty() -> List<V, P> { Nil }
 
     fn is_empty(&self) -> bool {
-        match *self {
-            Nil => true,
-            _ => false
-        }
+        matches!(*self, Nil)
     }
 
-    fn cons(head: V, tail: List<V>) -> List<V> {
-        Cons(Rc::new((head, tail)))
+    fn cons(head: V, tail: List<V, P>) -> List<V, P> {
+        Cons(P::new((head, tail)))
     }
 
-    fn split(&self) -> Option<(&V, &List<V>)> {
+    fn split(&self) -> Option<(&V, &List<V, P>)> {
         match *self {
             Nil => None,
             Cons(ref rc) => {
@@ -48,34 +113,70 @@ impl<V> Stack for List<V> {
             }
         }
     }
+
+    /// Like `List::pop`: moves the item out if the node isn't shared.
+    fn pop(&mut self) -> Option<V>
+        where V: Clone
+    {
+        List::pop(self)
+    }
 }
 
-pub struct ListIterator<V>(List<V>);
+/// An iterator that takes a list apart, yielding its items first to last.
+/// Items are moved out of nodes no other list shares, and cloned from the
+/// rest; see `List::pop`.
+pub struct ListIterator<V, P: SharedPointerKind = RcKind> {
+    list: List<V, P>,
+    // A list can only be walked from the front. The first call to
+    // `next_back` moves the remaining items here, so that both ends are
+    // reachable; after that, `list` is empty.
+    both_ends: Option<VecDeque<V>>
+}
 
-impl<V> List<V> {
-    pub fn iter(&self) -> ListIterator<V> {
-        ListIterator(self.clone())
+impl<V, P: SharedPointerKind> List<V, P> {
+    /// Iterate over references to the items, first to last.
+    pub fn iter(&self) -> Iter<'_, V, P> {
+        Iter(self)
     }
 }
 
-impl<V: Clone> IntoIterator for List<V> {
+impl<V: Clone, P: SharedPointerKind> IntoIterator for List<V, P> {
     type Item = V;
-    type IntoIter = ListIterator<V>;
-    fn into_iter(self) -> ListIterator<V> {
-        ListIterator(self)
+    type IntoIter = ListIterator<V, P>;
+    fn into_iter(self) -> ListIterator<V, P> {
+        ListIterator { list: self, both_ends: None }
     }
 }
 
-impl<V: Clone> Iterator for ListIterator<V> {
+impl<V: Clone, P: SharedPointerKind> Iterator for ListIterator<V, P> {
     type Item = V;
 
     fn next(&mut self) -> Option<V> {
-        self.0.pop()
+        match self.both_ends {
+            None => self.list.pop(),
+            Some(ref mut items) => items.pop_front()
+        }
     }
 }
 
-impl<V> FromIterator<V> for List<V> {
-    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> List<V>
+impl<V: Clone, P: SharedPointerKind> DoubleEndedIterator for ListIterator<V, P> {
+    /// The first call takes time and space proportional to the number of
+    /// items left, moving or cloning each one as `next` would; after that,
+    /// this and `next` take constant time.
+    fn next_back(&mut self) -> Option<V> {
+        if self.both_ends.is_none() {
+            let mut items = VecDeque::new();
+            while let Some(v) = self.list.pop() {
+                items.push_back(v);
+            }
+            self.both_ends = Some(items);
+        }
+        self.both_ends.as_mut()?.pop_back()
+    }
+}
+
+impl<V, P: SharedPointerKind> FromIterator<V> for List<V, P> {
+    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> List<V, P>
     {
         let mut result = List::empty();
         let v: Vec<_> = iterator.into_iter().collect();
@@ -86,7 +187,37 @@ impl<V> FromIterator<V> for List<V> {
     }
 }
 
-impl<V> List<V> {
+impl<V, P: SharedPointerKind> List<V, P> {
+    /// The first item and the rest of the list, or None if the list is
+    /// empty. This is `Stack::split` under its usual functional name, so it
+    /// works without importing `Stack`.
+    pub fn uncons(&self) -> Option<(&V, &List<V, P>)> {
+        self.split()
+    }
+
+    /// Like `uncons`, but with an error if the list is empty.
+    pub fn try_split(&self) -> Result<(&V, &List<V, P>), Error> {
+        self.split().ok_or(Error::Empty)
+    }
+
+    /// The item at `index`, or an error if the list isn't that long. This
+    /// takes time proportional to `index`.
+    pub fn try_lookup(&self, index: usize) -> Result<&V, Error> {
+        let mut p = self;
+        for len in 0..index {
+            p = p.tail().ok_or(Error::IndexOutOfBounds { index, len })?;
+        }
+        p.head().ok_or(Error::IndexOutOfBounds { index, len: index })
+    }
+
+    /// The first two items and the rest of the list, or None if the list has
+    /// fewer than two items. For more, see the `as_head_tail!` macro.
+    pub fn uncons2(&self) -> Option<(&V, &V, &List<V, P>)> {
+        let (a, rest) = self.split()?;
+        let (b, rest) = rest.split()?;
+        Some((a, b, rest))
+    }
+
     pub fn length(&self) -> usize {
         let mut p = self.clone();
         let mut len = 0;
@@ -99,56 +230,740 @@ impl<V> List<V> {
             len += 1;
         }
     }
+
+    /// Combine the items, first to last, using `f`, stopping at the first
+    /// error and returning it.
+    pub fn try_fold<A, E, F>(&self, init: A, f: F) -> Result<A, E>
+        where F: FnMut(A, &V) -> Result<A, E>
+    {
+        Iter(self).try_fold(init, f)
+    }
+
+    /// Return a list of the results of calling `f` on each item, or the
+    /// first error `f` returns. `f` is not called on the items after that.
+    pub fn try_map<U, E, F>(&self, f: F) -> Result<List<U, P>, E>
+        where F: FnMut(&V) -> Result<U, E>
+    {
+        let mapped = Iter(self).map(f).collect::<Result<Vec<U>, E>>()?;
+        Ok(mapped.into_iter().rev().fold(Nil, |list, v| List::cons(v, list)))
+    }
+
+    /// Return a list of the results of calling `f` on the items of this list
+    /// and `other`, pairwise. The result is as long as the shorter list.
+    pub fn zip_with<W, U, F>(&self, other: &List<W, P>, mut f: F) -> List<U, P>
+        where F: FnMut(&V, &W) -> U
+    {
+        let zipped: Vec<U> = Iter(self).zip(Iter(other)).map(|(v, w)| f(v, w)).collect();
+        zipped.into_iter().rev().fold(Nil, |list, u| List::cons(u, list))
+    }
 }
 
-impl<V: Clone> List<V> {
+impl<A: Clone, B: Clone, P: SharedPointerKind> List<(A, B), P> {
+    /// Split a list of pairs into a list of the first halves and a list of
+    /// the second halves, cloning each.
+    pub fn unzip(&self) -> (List<A, P>, List<B, P>) {
+        let pairs: Vec<&(A, B)> = Iter(self).collect();
+        pairs.into_iter().rev().fold((Nil, Nil), |(xs, ys), (a, b)| {
+            (List::cons(a.clone(), xs), List::cons(b.clone(), ys))
+        })
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
     /// Split a list into its head and tail, or `None` if the list is empty.
     ///
     /// This requires the item type to be cloneable because 
-    pub fn split_into(&self) -> Option<(V, List<V>)> {
+    pub fn split_into(&self) -> Option<(V, List<V, P>)> {
         match *self {
             Nil => None,
             Cons(ref rc) => Some((**rc).clone())
         }
     }
+
+    /// Return a copy of this list with `value` inserted before the item at
+    /// `index`, or None if the list is shorter than `index`. An `index` equal
+    /// to the length puts `value` at the end.
+    ///
+    /// This takes O(index) time: it copies the first `index` nodes, cloning
+    /// their items, and shares the rest of the list.
+    ///
+    pub fn insert_at(&self, index: usize, value: V) -> Option<List<V, P>> {
+        let (prefix, rest) = self.take_prefix(index)?;
+        Some(List::cons_all(prefix, List::cons(value, rest.clone())))
+    }
+
+    /// Return the item at `index` and a copy of this list without it, or None
+    /// if the list is too short to have an item at `index`.
+    ///
+    /// This takes O(index) time: it copies the first `index` nodes, cloning
+    /// their items, and shares the rest of the list.
+    ///
+    pub fn remove_at(&self, index: usize) -> Option<(&V, List<V, P>)> {
+        let (prefix, rest) = self.take_prefix(index)?;
+        let (item, tail) = rest.split()?;
+        Some((item, List::cons_all(prefix, tail.clone())))
+    }
+
+    /// Return the last item and a copy of this list without it, or None if
+    /// the list is empty.
+    ///
+    /// This walks the list once, and copies every node but the last.
+    ///
+    pub fn split_last(&self) -> Option<(&V, List<V, P>)> {
+        let mut items: Vec<&V> = Iter(self).collect();
+        let last = items.pop()?;
+        Some((last, List::cons_all(items, Nil)))
+    }
+
+    /// Return a copy of this list without its last item, or None if the list
+    /// is empty. See `split_last`.
+    ///
+    pub fn init(&self) -> Option<List<V, P>> {
+        self.split_last().map(|(_, init)| init)
+    }
+
+    // Like `remove_at` followed by `insert_at`, but copying the prefix once.
+    pub(crate) fn replace_at(&self, index: usize, value: V) -> Option<List<V, P>> {
+        let (prefix, rest) = self.take_prefix(index)?;
+        let tail = rest.tail()?;
+        Some(List::cons_all(prefix, List::cons(value, tail.clone())))
+    }
+
+    // Return references to the first `n` items and the list after them, or
+    // None if the list has fewer than `n` items.
+    fn take_prefix(&self, n: usize) -> Option<(Vec<&V>, &List<V, P>)> {
+        // Don't reserve `n` slots up front: `n` can be far past the end.
+        let mut prefix = vec![];
+        let mut p = self;
+        while prefix.len() < n {
+            let (first, rest) = p.split()?;
+            prefix.push(first);
+            p = rest;
+        }
+        Some((prefix, p))
+    }
+
+    // Put clones of `items` in front of `tail`, in the same order.
+    fn cons_all(items: Vec<&V>, tail: List<V, P>) -> List<V, P> {
+        items.into_iter().rev().fold(tail, |list, v| List::cons(v.clone(), list))
+    }
+}
+
+// Merge two sorted lists. When one runs out, the rest of the other is shared
+// rather than copied. Where values are equal, those from `xs` come first.
+fn merge_by<V, P, F>(xs: &List<V, P>, ys: &List<V, P>, compare: &mut F) -> List<V, P>
+    where V: Clone, P: SharedPointerKind, F: FnMut(&V, &V) -> Ordering
+{
+    let mut prefix = vec![];
+    let (mut xs, mut ys) = (xs, ys);
+    let rest = loop {
+        match (xs.split(), ys.split()) {
+            (None, _) => break ys,
+            (_, None) => break xs,
+            (Some((x, xt)), Some((y, yt))) => {
+                if compare(y, x) == Ordering::Less {
+                    prefix.push(y);
+                    ys = yt;
+                } else {
+                    prefix.push(x);
+                    xs = xt;
+                }
+            }
+        }
+    };
+    List::cons_all(prefix, rest.clone())
 }
 
-/// Reverse a list.
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Return a copy of this list sorted by `compare`. The sort is stable:
+    /// equal items stay in the same order.
+    ///
+    /// This is a natural merge sort. It finds the runs of items that are
+    /// already in order, then merges neighboring runs until one is left, in
+    /// O(n log r) time for r runs. The last run is used in place rather than
+    /// copied, and each merge shares whatever is left of one list when the
+    /// other runs out, so a list whose largest items are already sorted at
+    /// the end shares those nodes with the result.
+    ///
+    pub fn sort_by<F: FnMut(&V, &V) -> Ordering>(&self, mut compare: F) -> List<V, P> {
+        let mut runs = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            let mut run = vec![first];
+            let mut q = rest;
+            while let Some((next, rest)) = q.split() {
+                if compare(run[run.len() - 1], next) == Ordering::Greater {
+                    break;
+                }
+                run.push(next);
+                q = rest;
+            }
+            if q.is_empty() {
+                runs.push(p.clone());
+                break;
+            }
+            runs.push(List::cons_all(run, Nil));
+            p = q;
+        }
+
+        while runs.len() > 1 {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(2));
+            let mut pairs = runs.chunks(2);
+            for pair in &mut pairs {
+                merged.push(match *pair {
+                    [ref a, ref b] => merge_by(a, b, &mut compare),
+                    _ => pair[0].clone()
+                });
+            }
+            runs = merged;
+        }
+        runs.pop().unwrap_or(Nil)
+    }
+
+    /// Return a copy of this list sorted by the key `f` extracts from each
+    /// item. The sort is stable. See `sort_by`.
+    pub fn sort_by_key<K: Ord, F: FnMut(&V) -> K>(&self, mut f: F) -> List<V, P> {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
+}
+
+/// Merge two sorted lists into one sorted list, in O(n + m) time. Where
+/// items are equal, those from `a` come first.
 ///
-/// This copies the entire list and all the items.
+/// When one list runs out, the rest of the other is shared rather than
+/// copied.
 ///
-pub fn reverse<V: Clone>(s: List<V>) -> List<V> {
-    let mut result = Nil;
-    let mut current = &s;
-    loop {
-        match current.split() {
-            None => {
-                return result;
-            },
-            Some((first, rest)) => {
-                result.push((*first).clone());
-                current = rest;
+pub fn merge_sorted<V: Ord + Clone, P: SharedPointerKind>(a: &List<V, P>, b: &List<V, P>) -> List<V, P> {
+    merge_by(a, b, &mut V::cmp)
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Split this list into groups of neighboring items, starting a new group
+    /// wherever `same(previous, next)` is false. For example, grouping
+    /// `[1, 1, 2, 3, 3]` by `==` gives `[[1, 1], [2], [3, 3]]`.
+    ///
+    /// The last group is the tail of this list, shared rather than copied;
+    /// the other groups are copies.
+    ///
+    pub fn group_by<F: FnMut(&V, &V) -> bool>(&self, mut same: F) -> List<List<V, P>, P> {
+        let mut groups = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            let mut group = vec![first];
+            let mut q = rest;
+            while let Some((next, rest)) = q.split() {
+                if !same(group[group.len() - 1], next) {
+                    break;
+                }
+                group.push(next);
+                q = rest;
+            }
+            groups.push(if q.is_empty() { p.clone() } else { List::cons_all(group, Nil) });
+            p = q;
+        }
+        groups.into_iter().rev().fold(Nil, |list, group| List::cons(group, list))
+    }
+
+    /// Split this list into groups of `size` items. The last group has fewer
+    /// if the length isn't a multiple of `size`. As with `group_by`, the last
+    /// group is shared with this list.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    pub fn chunks(&self, size: usize) -> List<List<V, P>, P> {
+        assert!(size != 0, "chunk size must be nonzero");
+        // `group_by` calls this once for each pair of neighbors, in order.
+        let mut n = 0;
+        self.group_by(|_, _| {
+            n += 1;
+            n % size != 0
+        })
+    }
+
+    /// Return the list of each item paired with the one after it:
+    /// `list![1, 2, 3].pairs()` is `list![(1, 2), (2, 3)]`. Every item but
+    /// the first and last is cloned twice.
+    ///
+    pub fn pairs(&self) -> List<(V, V), P> {
+        let mut pairs = vec![];
+        let mut p = self;
+        while let Some((a, rest)) = p.split() {
+            match rest.head() {
+                None => break,
+                Some(b) => pairs.push((a.clone(), b.clone()))
+            }
+            p = rest;
+        }
+        pairs.into_iter().rev().fold(Nil, |list, pair| List::cons(pair, list))
+    }
+
+    /// Return the list of every run of `size` neighboring items, in order.
+    /// If this list has fewer than `size` items, the result is empty. The
+    /// last window is a suffix of this list, and is shared with it; the
+    /// others are copies.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    pub fn windows(&self, size: usize) -> List<List<V, P>, P> {
+        assert!(size != 0, "window size must be nonzero");
+        let mut items = vec![];
+        let mut suffixes = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            items.push(first);
+            suffixes.push(p);
+            p = rest;
+        }
+        if items.len() < size {
+            return Nil;
+        }
+        let last = items.len() - size;
+        let mut windows = List::cons(suffixes[last].clone(), Nil);
+        for i in (0..last).rev() {
+            windows = List::cons(List::cons_all(items[i..i + size].to_vec(), Nil), windows);
+        }
+        windows
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Return a copy of this list with `sep` between each pair of neighboring
+    /// items: `list![1, 2, 3].intersperse(0)` is `list![1, 0, 2, 0, 3]`. The
+    /// last item is shared with this list; the others, and `sep`, are cloned.
+    ///
+    pub fn intersperse(&self, sep: V) -> List<V, P> {
+        let mut items = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            if rest.is_empty() {
+                break;
+            }
+            items.push(first);
+            items.push(&sep);
+            p = rest;
+        }
+        List::cons_all(items, p.clone())
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Split this list into the items that satisfy `pred` and the items that
+    /// don't, each in their original order.
+    ///
+    /// The last run of items that all land on the same side is a suffix of
+    /// this list, and is shared with it; the other items are cloned.
+    ///
+    pub fn partition<F: FnMut(&V) -> bool>(&self, mut pred: F) -> (List<V, P>, List<V, P>) {
+        let mut yes = vec![];
+        let mut no = vec![];
+        // Which side the current run of items is going to, where it starts,
+        // and how long it is so far.
+        let mut run = None;
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            let side = pred(first);
+            match run {
+                Some((s, _, ref mut n)) if s == side => *n += 1,
+                _ => run = Some((side, p, 1))
+            }
+            if side { yes.push(first) } else { no.push(first) }
+            p = rest;
+        }
+        match run {
+            None => (Nil, Nil),
+            Some((true, start, n)) => {
+                yes.truncate(yes.len() - n);
+                (List::cons_all(yes, start.clone()), List::cons_all(no, Nil))
+            }
+            Some((false, start, n)) => {
+                no.truncate(no.len() - n);
+                (List::cons_all(yes, Nil), List::cons_all(no, start.clone()))
             }
         }
     }
+
+    /// Split this list into its longest prefix whose items all satisfy
+    /// `pred`, and the rest.
+    ///
+    /// The rest is shared with this list. The prefix is a copy, unless it is
+    /// the whole list.
+    ///
+    pub fn span<F: FnMut(&V) -> bool>(&self, mut pred: F) -> (List<V, P>, List<V, P>) {
+        let mut prefix = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            if !pred(first) {
+                return (List::cons_all(prefix, Nil), p.clone());
+            }
+            prefix.push(first);
+            p = rest;
+        }
+        (self.clone(), Nil)
+    }
 }
 
+impl<V: Clone, P: SharedPointerKind> List<List<V, P>, P> {
+    /// Join these lists into one, with a copy of `sep` between each pair of
+    /// neighbors: `list![list![1, 2], list![3]].intercalate(&list![0])` is
+    /// `list![1, 2, 0, 3]`. The last list is shared, not copied; the items of
+    /// the others, and of each copy of `sep`, are cloned.
+    ///
+    pub fn intercalate(&self, sep: &List<V, P>) -> List<V, P> {
+        let mut items = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            if rest.is_empty() {
+                return List::cons_all(items, first.clone());
+            }
+            items.extend(Iter(first));
+            items.extend(Iter(sep));
+            p = rest;
+        }
+        Nil
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Return a copy of this list with each run of items for which
+    /// `same(previous, next)` is true reduced to its first item. On a sorted
+    /// list, this removes all duplicates.
+    ///
+    /// Everything after the last removed item is shared with this list.
+    ///
+    pub fn dedup_by<F: FnMut(&V, &V) -> bool>(&self, mut same: F) -> List<V, P> {
+        let mut kept: Vec<&V> = vec![];
+        let mut copy_len = 0;
+        let mut shared = self;
+        let mut p = self;
+        while let Some((item, rest)) = p.split() {
+            if kept.last().is_some_and(|&prev| same(prev, item)) {
+                copy_len = kept.len();
+                shared = rest;
+            } else {
+                kept.push(item);
+            }
+            p = rest;
+        }
+        kept.truncate(copy_len);
+        List::cons_all(kept, shared.clone())
+    }
+
+    /// Return a copy of this list with each run of equal items reduced to one
+    /// item. See `dedup_by`.
+    pub fn dedup(&self) -> List<V, P>
+        where V: PartialEq
+    {
+        self.dedup_by(|a, b| a == b)
+    }
+
+    /// Return a list of each run of equal items in this list, with its
+    /// length. For example, `[a, a, b, a]` becomes `[(a, 2), (b, 1), (a, 1)]`.
+    pub fn run_length_encode(&self) -> List<(V, usize), P>
+        where V: PartialEq
+    {
+        let mut runs: Vec<(&V, usize)> = vec![];
+        for item in Iter(self) {
+            match runs.last_mut() {
+                Some(&mut (prev, ref mut n)) if prev == item => *n += 1,
+                _ => runs.push((item, 1))
+            }
+        }
+        runs.into_iter().rev().fold(Nil, |list, (v, n)| List::cons((v.clone(), n), list))
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Return a copy of this list with the items in reverse order.
+    ///
+    /// This copies the entire list and all the items. See `Stack::reverse`.
+    ///
+    pub fn rev(&self) -> List<V, P> {
+        Stack::reverse(self)
+    }
+}
+
+/// Reverse a list. This is `Stack::reverse`, taking the list by value.
+///
+/// This copies the entire list and all the items.
+///
+pub fn reverse<V: Clone, P: SharedPointerKind>(s: List<V, P>) -> List<V, P> {
+    Stack::reverse(&s)
+}
+
+/// Concatenate two stacks. This is `Stack::append`.
 pub fn concat<S: Stack>(a: &S, b: S) -> S
     where S::Item: Clone
 {
-    match a.split() {
-        None => b,
-        Some((first, rest)) => S::cons(first.clone(), concat(rest, b))
+    a.append(b)
+}
+
+pub fn suffixes<S: Stack + Clone>(a: &S) -> List<S> {
+    let mut all = vec![a];
+    while let Some(rest) = all[all.len() - 1].tail() {
+        all.push(rest);
     }
+    all.into_iter().rev().fold(List::empty(), |list, s| List::cons(s.clone(), list))
 }
 
-pub fn suffixes<S: Stack>(a: &S) -> List<S>
-    where S: Clone
-{
-    match a.tail() {
-        None => List::cons(a.clone(), List::empty()),
-        Some(rest) =>
-            List::cons(a.clone(), suffixes(rest))
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Return a copy of this list that shares nodes using pointer kind `Q`.
+    ///
+    /// This copies every node and clones every item once.
+    ///
+    pub fn to_pointer_kind<Q: SharedPointerKind>(&self) -> List<V, Q> {
+        let mut items = vec![];
+        let mut p = self;
+        while let Some((first, rest)) = p.split() {
+            items.push(first);
+            p = rest;
+        }
+        let mut result = Nil;
+        for v in items.into_iter().rev() {
+            result = List::cons(v.clone(), result);
+        }
+        result
+    }
+}
+
+impl<V: Clone> List<V> {
+    /// Return a thread-safe copy of this list.
+    pub fn to_shared(&self) -> List<V, ArcKind> { self.to_pointer_kind() }
+}
+
+impl<V: Clone> List<V, ArcKind> {
+    /// Return a copy of this list that uses `Rc` to share nodes.
+    pub fn to_local(&self) -> List<V> { self.to_pointer_kind() }
+}
+
+impl<V: Clone, P: SharedPointerKind> List<V, P> {
+    /// Encode several versions of a list as a `Snapshot`. Tails shared between
+    /// versions are written only once.
+    pub fn encode(versions: &[List<V, P>]) -> Snapshot<V, ListRecord> {
+        let mut encoder = Encoder::new();
+        for list in versions {
+            // Walk down to the first node that's already encoded, then encode
+            // the new nodes from the back so each tail gets its index first.
+            let mut new_nodes = vec![];
+            let mut tail = None;
+            let mut p = list;
+            while let Cons(ref rc) = *p {
+                let node: &(V, List<V, P>) = rc;
+                if let Some(id) = encoder.lookup(node) {
+                    tail = Some(id);
+                    break;
+                }
+                new_nodes.push(node);
+                p = &node.1;
+            }
+            for node in new_nodes.into_iter().rev() {
+                let value = encoder.value(&node.0);
+                tail = Some(encoder.node(node, ListRecord { value, tail }));
+            }
+            encoder.root(tail);
+        }
+        encoder.finish()
+    }
+
+    /// Rebuild the versions stored in a snapshot, restoring the sharing
+    /// between them.
+    pub fn decode(snapshot: &Snapshot<V, ListRecord>) -> Result<Vec<List<V, P>>, DecodeError> {
+        let mut lists: Vec<List<V, P>> = Vec::with_capacity(snapshot.nodes.len());
+        for (i, r) in snapshot.nodes.iter().enumerate() {
+            snapshot.check_value(i, r.value)?;
+            snapshot.check_child(i, r.tail)?;
+            let list = List::cons(snapshot.values[r.value].clone(), decoded(&lists, r.tail));
+            lists.push(list);
+        }
+        snapshot.roots.iter().map(|&root| {
+            snapshot.check_root(root)?;
+            Ok(decoded(&lists, root))
+        }).collect()
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> fmt::Debug for ListIterator<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut t = f.debug_tuple("ListIterator");
+        match self.both_ends {
+            None => t.field(&self.list),
+            Some(ref items) => t.field(items)
+        };
+        t.finish()
+    }
+}
+
+impl<V: Clone + fmt::Debug, P: SharedPointerKind> List<V, P> {
+    /// Render this list as a Graphviz graph, in DOT format, with one vertex
+    /// per node.
+    pub fn to_dot(&self) -> String {
+        List::versions_to_dot(slice::from_ref(self))
+    }
+
+    /// Render several versions of a list as one Graphviz graph, in DOT
+    /// format. Nodes shared by more than one version appear only once, so the
+    /// graph shows exactly how much structure the versions share.
+    ///
+    pub fn versions_to_dot(versions: &[List<V, P>]) -> String {
+        dot::render("List", &List::encode(versions))
     }
 }
 
+impl<V: fmt::Debug, P: SharedPointerKind> fmt::Debug for List<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("List")?;
+        f.debug_list().entries(Iter(self)).finish()
+    }
+}
+
+/// An iterator over references to the items of a list, first to last. See
+/// `List::iter`.
+pub struct Iter<'a, V: 'a, P: SharedPointerKind + 'a = RcKind>(&'a List<V, P>);
+
+impl<'a, V, P: SharedPointerKind> Clone for Iter<'a, V, P> {
+    fn clone(&self) -> Iter<'a, V, P> {
+        Iter(self.0)
+    }
+}
+
+impl<'a, V: fmt::Debug, P: SharedPointerKind> fmt::Debug for Iter<'a, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Iter").field(self.0).finish()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> IntoIterator for &'a List<V, P> {
+    type Item = &'a V;
+    type IntoIter = Iter<'a, V, P>;
+    fn into_iter(self) -> Iter<'a, V, P> {
+        self.iter()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> Iterator for Iter<'a, V, P> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        let (first, rest) = self.0.split()?;
+        self.0 = rest;
+        Some(first)
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> List<V, P> {
+    /// Return the `k`th smallest item of this list, counting from 0, or None
+    /// if the list has `k` items or fewer. `select_nth(len / 2)` is the
+    /// median.
+    ///
+    /// This is quickselect on references to the items, so it takes O(n) time
+    /// on average and doesn't change, copy, or clone anything in the list.
+    ///
+    pub fn select_nth(&self, k: usize) -> Option<&V> {
+        let mut refs: Vec<&V> = Iter(self).collect();
+        if k >= refs.len() {
+            return None;
+        }
+        Some(*refs.select_nth_unstable(k).1)
+    }
+}
+
+impl<V, P: SharedPointerKind> List<V, P> {
+    /// Return an object that formats this list's elements with `Display`,
+    /// separated by `sep`.
+    pub fn display_with<'a>(&'a self, sep: &'a str) -> DisplayWith<'a, List<V, P>> {
+        DisplayWith { seq: self, sep }
+    }
+}
+
+impl<'a, V: fmt::Display, P: SharedPointerKind> fmt::Display for DisplayWith<'a, List<V, P>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_separated(f, Iter(self.seq), self.sep)
+    }
+}
+
+impl<V: fmt::Display, P: SharedPointerKind> fmt::Display for List<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.display_with(", "), f)
+    }
+}
+
+impl<V, P: SharedPointerKind> Linked for List<V, P> {
+    fn root_addr(&self) -> Option<usize> {
+        match *self {
+            Nil => None,
+            Cons(ref rc) => Some(&**rc as *const (V, List<V, P>) as usize)
+        }
+    }
+
+    fn children(&self) -> (Option<&List<V, P>>, Option<&List<V, P>>) {
+        (self.tail(), None)
+    }
+}
+
+impl<V, P: SharedPointerKind> List<V, P> {
+    /// Return true if `self` and `other` are the same version of the list:
+    /// both empty, or starting with the same node. This takes constant time. If it
+    /// returns true, the two are certainly equal; if not, they may still be
+    /// equal, and `==` will say.
+    pub fn same(&self, other: &List<V, P>) -> bool {
+        sharing::same(self, other)
+    }
+
+    /// Return true if `self` and `other` have any nodes in common, as when
+    /// one was made from the other. This takes time proportional to the
+    /// number of nodes in both.
+    ///
+    pub fn shares_structure_with(&self, other: &List<V, P>) -> bool {
+        sharing::shares_structure(&[self], &[other])
+    }
+
+    /// Return the number of nodes `self` and `other` have in common. This
+    /// takes time proportional to the number of nodes in both.
+    pub fn shared_node_count(&self, other: &List<V, P>) -> usize {
+        sharing::shared_count(&[self], &[other])
+    }
+
+    /// Return the number of nodes in `self` that are not also in `other`:
+    /// how much memory keeping `self` costs, on top of keeping `other`.
+    pub fn unique_node_count(&self, other: &List<V, P>) -> usize {
+        sharing::unique_count(&[self], &[other])
+    }
+}
+
+impl<V: PartialEq, P: SharedPointerKind> PartialEq for List<V, P> {
+    fn eq(&self, other: &List<V, P>) -> bool {
+        let mut a = self;
+        let mut b = other;
+        loop {
+            match (a, b) {
+                (Nil, Nil) => return true,
+                (Cons(x), Cons(y)) => {
+                    // Once the two lists reach a shared tail, the rest is equal.
+                    if P::ptr_eq(x, y) {
+                        return true;
+                    }
+                    if x.0 != y.0 {
+                        return false;
+                    }
+                    a = &x.1;
+                    b = &y.1;
+                }
+                _ => return false
+            }
+        }
+    }
+}
+
+impl<V: Eq, P: SharedPointerKind> Eq for List<V, P> {}
+
+// Hash the elements, then the length, like `Vec` (but with the length last, so
+// that it can be counted along the way).
+impl<V: Hash, P: SharedPointerKind> Hash for List<V, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for v in Iter(self) {
+            v.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
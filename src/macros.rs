@@ -0,0 +1,84 @@
+//! Macros for writing down collections, like `vec!`, and for taking lists
+//! apart.
+//!
+//! Each building macro builds the `Rc` flavor of its structure from an array
+//! of the elements, using the structure's bulk construction where it has one:
+//! `rbset!` sorts the elements and builds the tree in one pass, and `heap!`
+//! merges singleton heaps in pairs, in O(n) time.
+
+/// Build a `List` containing the given elements, in order.
+///
+/// `list![1, 2, 3]` is the list whose head is `1`.
+///
+#[macro_export]
+macro_rules! list {
+    ($($x:expr),* $(,)?) => {{
+        let mut list = $crate::list::List::new();
+        for x in ::std::iter::IntoIterator::into_iter([$($x),*]).rev() {
+            list = <$crate::list::List<_> as $crate::traits::Stack>::cons(x, list);
+        }
+        list
+    }};
+}
+
+/// Build an `RBTree` set containing the given elements.
+///
+/// As with `Set::plus`, if an element appears more than once, the first
+/// occurrence is kept. See `RBTree`'s `FromIterator` impl.
+///
+#[macro_export]
+macro_rules! rbset {
+    ($($x:expr),* $(,)?) => {
+        <$crate::rbtree::RBTree<_> as ::std::iter::FromIterator<_>>::from_iter([$($x),*])
+    };
+}
+
+/// Build a `LeftistHeap` containing the given elements, in O(n) time. See
+/// `LeftistHeap`'s `FromIterator` impl.
+#[macro_export]
+macro_rules! heap {
+    ($($x:expr),* $(,)?) => {
+        <$crate::heap::LeftistHeap<_> as ::std::iter::FromIterator<_>>::from_iter([$($x),*])
+    };
+}
+
+/// Build a `BatchedQueue` containing the given elements, front first.
+#[macro_export]
+macro_rules! queue {
+    ($($x:expr),* $(,)?) => {{
+        let mut queue = $crate::queue::BatchedQueue::new();
+        for x in ::std::iter::IntoIterator::into_iter([$($x),*]) {
+            queue = $crate::traits::Queue::snoc(queue, x);
+        }
+        queue
+    }};
+}
+
+/// Peel the first `n` items off a `List`, by reference, for `n` from 1 to 4.
+///
+/// `as_head_tail!(list, 3)` is `Some((&a, &b, &c, &rest))` if `list` has at
+/// least three items, and None otherwise. It replaces nested `split` matches:
+///
+/// ```ignore
+/// if let Some((x, y, rest)) = as_head_tail!(list, 2) {
+///     ...
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! as_head_tail {
+    ($list:expr, 1) => { $crate::as_head_tail!(@peel ($list); (); x) };
+    ($list:expr, 2) => { $crate::as_head_tail!(@peel ($list); (); x x) };
+    ($list:expr, 3) => { $crate::as_head_tail!(@peel ($list); (); x x x) };
+    ($list:expr, 4) => { $crate::as_head_tail!(@peel ($list); (); x x x x) };
+    (@peel $rest:expr; ($($item:ident)*); ) => {
+        ::std::option::Option::Some(($($item,)* $rest))
+    };
+    (@peel $rest:expr; ($($item:ident)*); $next:ident $($more:ident)*) => {
+        match ($rest).uncons() {
+            ::std::option::Option::None => ::std::option::Option::None,
+            ::std::option::Option::Some((head, tail)) =>
+                $crate::as_head_tail!(@peel tail; ($($item)* head); $($more)*)
+        }
+    };
+}
@@ -0,0 +1,356 @@
+//! 6.4.3 Bottom-up mergesort with sharing
+//!
+//! A `MergeSort` keeps the values added so far as a list of sorted segments
+//! whose sizes are distinct powers of two, like the binary representation of
+//! the number of values. Adding a value merges segments the way adding 1 to a
+//! binary number carries. The second type parameter, a strategy from the
+//! `evaluation` module, says when those merges happen:
+//!
+//! - `Lazy`, the default: the merges are suspended until someone calls
+//!   `sort`, and the suspension is shared by every later version. So `add`
+//!   takes O(log n) amortized time and `sort` O(n), even when old versions
+//!   are reused.
+//!
+//! - `Scheduled` (7.4): the same work is done on a schedule, so the bounds
+//!   are worst-case rather than amortized: every `add` takes O(log n) time
+//!   and every `sort` O(n). Use it when an occasional slow `add` is
+//!   unacceptable. `ScheduledMergeSort` is short for this one.
+//!
+//! - `Eager`: `add` does its merges right away. That's O(log n) amortized
+//!   time, but only as long as old versions aren't reused; adding to the
+//!   same old version over and over can take O(n) time each.
+//!
+//! The sort is stable: values that compare equal come out in the order they
+//! were added.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use evaluation::{Eager, Lazy, Scheduled, Strategy};
+use list::{merge_sorted, List};
+use list::List::Nil;
+use pointer::ArcKind;
+use stream::{self, Stream};
+use traits::{Sortable, Stack};
+
+type Run<T> = List<T, ArcKind>;
+
+/// How a `MergeSort` does its merges. This is implemented for each strategy
+/// in the `evaluation` module; see the module documentation above for how
+/// they differ. `MergeSort` calls these methods, and there's no need to call
+/// them directly.
+pub trait SortStrategy: Strategy {
+    /// The sorted segments, smallest first, plus whatever else the strategy
+    /// keeps track of.
+    type Segments<T>: Clone;
+
+    /// Return the segments of an empty collection.
+    fn empty<T>() -> Self::Segments<T>;
+
+    /// Return the segments with `value` added. `size` is the number of
+    /// values already in them.
+    fn add<T>(segments: &Self::Segments<T>, size: usize, value: T) -> Self::Segments<T>
+        where T: Ord + Clone + Send + Sync + 'static;
+
+    /// Merge all the segments into one sorted list.
+    fn sort<T>(segments: &Self::Segments<T>) -> Run<T>
+        where T: Ord + Clone + Send + Sync + 'static;
+}
+
+/// A collection that sorts its values incrementally, with its merges done
+/// according to strategy `S`. Use the `Sortable` methods.
+pub struct MergeSort<T, S: SortStrategy = Lazy> {
+    size: usize,
+    segments: S::Segments<T>
+}
+
+/// A collection that sorts its values incrementally, with worst-case rather
+/// than amortized bounds.
+pub type ScheduledMergeSort<T> = MergeSort<T, Scheduled>;
+
+impl<T, S: SortStrategy> Clone for MergeSort<T, S> {
+    fn clone(&self) -> MergeSort<T, S> {
+        MergeSort {
+            size: self.size,
+            segments: self.segments.clone()
+        }
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync + 'static> MergeSort<T> {
+    /// Return an empty collection that uses the default strategy, `Lazy`.
+    pub fn new() -> MergeSort<T> {
+        Sortable::empty()
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync + 'static, S: SortStrategy> Default for MergeSort<T, S> {
+    fn default() -> MergeSort<T, S> {
+        Sortable::empty()
+    }
+}
+
+impl<T, S: SortStrategy> MergeSort<T, S> {
+    /// The number of values added.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Return true if no values have been added.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync + 'static, S: SortStrategy> Sortable for MergeSort<T, S> {
+    type Item = T;
+
+    fn empty() -> MergeSort<T, S> {
+        MergeSort { size: 0, segments: S::empty() }
+    }
+
+    fn add(&self, value: T) -> MergeSort<T, S> {
+        MergeSort { size: self.size + 1, segments: S::add(&self.segments, self.size, value) }
+    }
+
+    fn sort(&self) -> Run<T> {
+        S::sort(&self.segments)
+    }
+}
+
+impl<T, S: SortStrategy> fmt::Debug for MergeSort<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MergeSort")
+            .field("strategy", &format_args!("{}", S::NAME))
+            .field("len", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+// Add a segment of `size` values to `segments`, merging with existing
+// segments as a binary increment carries. Older segments are always the first
+// argument to `merge_sorted`, so the sort is stable.
+fn add_segment<T: Ord + Clone>(segment: Run<T>, segments: &List<Run<T>, ArcKind>, size: usize) -> List<Run<T>, ArcKind> {
+    let mut segment = segment;
+    let mut segments = segments;
+    let mut size = size;
+    while size % 2 == 1 {
+        match segments.split() {
+            None => break,
+            Some((first, rest)) => {
+                segment = merge_sorted(first, &segment);
+                segments = rest;
+            }
+        }
+        size /= 2;
+    }
+    List::cons(segment, segments.clone())
+}
+
+// Merge all the segments, smallest first, so the result is stable.
+fn merge_all<T: Ord + Clone>(segments: &List<Run<T>, ArcKind>) -> Run<T> {
+    let mut result = Nil;
+    let mut segments = segments;
+    while let Some((segment, rest)) = segments.split() {
+        result = merge_sorted(segment, &result);
+        segments = rest;
+    }
+    result
+}
+
+impl SortStrategy for Eager {
+    type Segments<T> = List<Run<T>, ArcKind>;
+
+    fn empty<T>() -> Self::Segments<T> {
+        Nil
+    }
+
+    fn add<T>(segments: &Self::Segments<T>, size: usize, value: T) -> Self::Segments<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        add_segment(List::cons(value, Nil), segments, size)
+    }
+
+    fn sort<T>(segments: &Self::Segments<T>) -> Run<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        merge_all(segments)
+    }
+}
+
+/// The segments of a `MergeSort<T, Lazy>`: a shared suspension of the
+/// merges still owed by the `add`s that made them.
+///
+/// Each `add` suspends one step, which needs the segments before it. A long
+/// run of `add`s makes a long chain of suspensions, so both forcing and
+/// dropping the chain are done with loops rather than recursion.
+pub struct LazySegments<T>(Arc<Suspension<T>>);
+
+struct Suspension<T> {
+    segments: OnceLock<List<Run<T>, ArcKind>>,
+    step: Mutex<Option<Step<T>>>
+}
+
+// An `add` that hasn't been done yet.
+struct Step<T> {
+    before: LazySegments<T>,
+    size: usize,
+    value: T
+}
+
+impl<T> Clone for LazySegments<T> {
+    fn clone(&self) -> LazySegments<T> {
+        LazySegments(self.0.clone())
+    }
+}
+
+impl<T> Suspension<T> {
+    fn take_step(&self) -> Option<Step<T>> {
+        self.step.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+}
+
+impl<T: Ord + Clone> LazySegments<T> {
+    fn force(&self) -> &List<Run<T>, ArcKind> {
+        if let Some(segments) = self.0.segments.get() {
+            return segments;
+        }
+
+        // Walk back to the newest step that has been forced, or that another
+        // thread is forcing right now, then force forward from there, oldest
+        // first, so each step finds the segments before it already forced.
+        let mut chain = vec![self.0.clone()];
+        loop {
+            let before = {
+                let last = chain.last().unwrap();
+                if last.segments.get().is_some() {
+                    break;
+                }
+                match *last.step.lock().unwrap_or_else(PoisonError::into_inner) {
+                    Some(ref step) => step.before.0.clone(),
+                    None => break
+                }
+            };
+            chain.push(before);
+        }
+        for suspension in chain.iter().rev() {
+            suspension.segments.get_or_init(|| {
+                let step = suspension.take_step()
+                    .expect("suspension forced again after its computation panicked");
+                add_segment(List::cons(step.value, Nil), step.before.force(), step.size)
+            });
+        }
+        self.0.segments.get().unwrap()
+    }
+}
+
+impl<T> Drop for Suspension<T> {
+    fn drop(&mut self) {
+        // Unlink the steps before this one while nothing else shares them.
+        let mut step = self.take_step();
+        while let Some(Step { before, .. }) = step {
+            step = match Arc::try_unwrap(before.0) {
+                Ok(suspension) => suspension.take_step(),
+                Err(_) => None
+            };
+        }
+    }
+}
+
+impl SortStrategy for Lazy {
+    type Segments<T> = LazySegments<T>;
+
+    fn empty<T>() -> Self::Segments<T> {
+        LazySegments(Arc::new(Suspension {
+            segments: OnceLock::from(Nil),
+            step: Mutex::new(None)
+        }))
+    }
+
+    fn add<T>(segments: &Self::Segments<T>, size: usize, value: T) -> Self::Segments<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        LazySegments(Arc::new(Suspension {
+            segments: OnceLock::new(),
+            step: Mutex::new(Some(Step { before: segments.clone(), size, value }))
+        }))
+    }
+
+    fn sort<T>(segments: &Self::Segments<T>) -> Run<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        merge_all(segments.force())
+    }
+}
+
+
+/* 7.4 Bottom-up mergesort with scheduling */
+
+// Streams whose cells still need to be forced, the next one first.
+type Schedule<T> = List<Stream<T>, ArcKind>;
+
+// Force one more cell of the first unfinished stream in `schedule`.
+fn exec1<T: Send + Sync + 'static>(schedule: &Schedule<T>) -> Schedule<T> {
+    let mut schedule = schedule;
+    while let Some((stream, rest)) = schedule.split() {
+        match stream.split() {
+            None => schedule = rest,
+            Some((_, tail)) => return List::cons(tail.clone(), rest.clone())
+        }
+    }
+    Nil
+}
+
+// Each segment, smallest first, with the schedule of merges still to be
+// forced in it.
+impl SortStrategy for Scheduled {
+    type Segments<T> = List<(Stream<T>, Schedule<T>), ArcKind>;
+
+    fn empty<T>() -> Self::Segments<T> {
+        Nil
+    }
+
+    fn add<T>(segments: &Self::Segments<T>, size: usize, value: T) -> Self::Segments<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        // Carry, as with the other strategies, but remember each merge so
+        // its cells can be forced a few at a time. A segment being merged
+        // here always has an empty schedule: its merges were finished by
+        // earlier `add`s.
+        let mut segment = Stream::cons(value, Stream::empty());
+        let mut segments = segments;
+        let mut size = size;
+        let mut merges = vec![];
+        while size % 2 == 1 {
+            let ((older, _), rest) = segments.split()
+                .expect("one segment for each 1 bit of the size");
+            segment = stream::merge_sorted(older, &segment);
+            merges.push(segment.clone());
+            segments = rest;
+            size /= 2;
+        }
+        let schedule = merges.into_iter().rev().fold(Nil, |list, s| List::cons(s, list));
+
+        // Then force two cells of every segment's schedule.
+        let mut all = vec![(segment, schedule)];
+        while let Some((first, rest)) = segments.split() {
+            all.push(first.clone());
+            segments = rest;
+        }
+        all.into_iter().rev().fold(Nil, |list, (segment, schedule)| {
+            List::cons((segment, exec1(&exec1(&schedule))), list)
+        })
+    }
+
+    fn sort<T>(segments: &Self::Segments<T>) -> Run<T>
+        where T: Ord + Clone + Send + Sync + 'static
+    {
+        let mut merged = Stream::empty();
+        let mut segments = segments;
+        while let Some(((segment, _), rest)) = segments.split() {
+            merged = stream::merge_sorted(segment, &merged);
+            segments = rest;
+        }
+        let values: Vec<&T> = merged.iter().collect();
+        values.into_iter().rev().fold(Nil, |list, v| List::cons(v.clone(), list))
+    }
+}
@@ -0,0 +1,221 @@
+//! Double-ended priority queues
+//!
+//! A `MinMaxHeap` is a pair of leftist heaps holding the same values, one
+//! ordered smallest first and one largest first. Taking the minimum from one
+//! heap leaves a stale copy in the other. Rather than search for it, we add
+//! it to a second heap of deleted entries; whenever a heap and its deleted
+//! heap have the same entry on top, both are popped. Every operation takes
+//! O(log n) time.
+
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
+use std::iter::FromIterator;
+use heap::LeftistHeap;
+use pointer::{SharedPointerKind, RcKind};
+use traits::Heap;
+
+// A value and its insertion number, so that equal values still make distinct
+// entries and the deleted heaps delete exactly the right copy.
+struct Entry<V, P: SharedPointerKind> {
+    value: P::Pointer<V>,
+    serial: u64
+}
+
+impl<V, P: SharedPointerKind> Clone for Entry<V, P> {
+    fn clone(&self) -> Entry<V, P> {
+        Entry { value: self.value.clone(), serial: self.serial }
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> PartialEq for Entry<V, P> {
+    fn eq(&self, other: &Entry<V, P>) -> bool {
+        self.serial == other.serial
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Eq for Entry<V, P> {}
+
+impl<V: Ord, P: SharedPointerKind> PartialOrd for Entry<V, P> {
+    fn partial_cmp(&self, other: &Entry<V, P>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Ord for Entry<V, P> {
+    fn cmp(&self, other: &Entry<V, P>) -> Ordering {
+        (*self.value).cmp(&*other.value).then(self.serial.cmp(&other.serial))
+    }
+}
+
+// A heap with lazy deletion: the live entries are those in `all` but not in
+// `dead`. The top of `all` is always live.
+struct Side<E, P: SharedPointerKind> {
+    all: LeftistHeap<E, P>,
+    dead: LeftistHeap<E, P>
+}
+
+impl<E, P: SharedPointerKind> Clone for Side<E, P> {
+    fn clone(&self) -> Side<E, P> {
+        Side { all: self.all.clone(), dead: self.dead.clone() }
+    }
+}
+
+impl<E: Ord, P: SharedPointerKind> Side<E, P> {
+    fn empty() -> Side<E, P> {
+        Side { all: LeftistHeap::empty(), dead: LeftistHeap::empty() }
+    }
+
+    fn insert(&self, entry: E) -> Side<E, P> {
+        Side { all: self.all.insert(entry), dead: self.dead.clone() }
+    }
+
+    fn without_top(&self) -> Side<E, P> {
+        Side { all: self.all.without_min(), dead: self.dead.clone() }.cleaned()
+    }
+
+    fn kill(&self, entry: E) -> Side<E, P> {
+        Side { all: self.all.clone(), dead: self.dead.insert(entry) }.cleaned()
+    }
+
+    fn cleaned(mut self) -> Side<E, P> {
+        while let (Some(a), Some(d)) = (self.all.min(), self.dead.min()) {
+            if a != d {
+                break;
+            }
+            self.all = self.all.without_min();
+            self.dead = self.dead.without_min();
+        }
+        self
+    }
+}
+
+/// A persistent double-ended priority queue, with fast access to both its
+/// smallest and its largest value. See the module documentation.
+pub struct MinMaxHeap<V, P: SharedPointerKind = RcKind> {
+    low: Side<Entry<V, P>, P>,
+    high: Side<Reverse<Entry<V, P>>, P>,
+    len: usize,
+    next: u64
+}
+
+impl<V, P: SharedPointerKind> Clone for MinMaxHeap<V, P> {
+    fn clone(&self) -> MinMaxHeap<V, P> {
+        MinMaxHeap {
+            low: self.low.clone(),
+            high: self.high.clone(),
+            len: self.len,
+            next: self.next
+        }
+    }
+}
+
+impl<V: Ord> MinMaxHeap<V> {
+    /// Return an empty heap that shares nodes using `Rc`.
+    pub fn new() -> MinMaxHeap<V> {
+        MinMaxHeap::empty()
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Default for MinMaxHeap<V, P> {
+    fn default() -> MinMaxHeap<V, P> {
+        MinMaxHeap::empty()
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> MinMaxHeap<V, P> {
+    /// Return an empty heap.
+    pub fn empty() -> MinMaxHeap<V, P> {
+        MinMaxHeap { low: Side::empty(), high: Side::empty(), len: 0, next: 0 }
+    }
+
+    /// The number of values in the heap.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return a heap with `value` added.
+    pub fn insert(&self, value: V) -> MinMaxHeap<V, P> {
+        let entry = Entry { value: P::new(value), serial: self.next };
+        MinMaxHeap {
+            low: self.low.insert(entry.clone()),
+            high: self.high.insert(Reverse(entry)),
+            len: self.len + 1,
+            next: self.next + 1
+        }
+    }
+
+    /// The smallest value, or None if the heap is empty.
+    pub fn min(&self) -> Option<&V> {
+        self.low.all.min().map(|e| &*e.value)
+    }
+
+    /// The largest value, or None if the heap is empty.
+    pub fn max(&self) -> Option<&V> {
+        self.high.all.min().map(|e| &*e.0.value)
+    }
+
+    /// Return a heap without the smallest value. If there are several equal
+    /// smallest values, the oldest goes first. If the heap is empty, this
+    /// returns an empty heap.
+    pub fn without_min(&self) -> MinMaxHeap<V, P> {
+        match self.low.all.min() {
+            None => self.clone(),
+            Some(e) => MinMaxHeap {
+                low: self.low.without_top(),
+                high: self.high.kill(Reverse(e.clone())),
+                len: self.len - 1,
+                next: self.next
+            }
+        }
+    }
+
+    /// Return a heap without the largest value. If there are several equal
+    /// largest values, the newest goes first. If the heap is empty, this
+    /// returns an empty heap.
+    pub fn without_max(&self) -> MinMaxHeap<V, P> {
+        match self.high.all.min() {
+            None => self.clone(),
+            Some(e) => MinMaxHeap {
+                low: self.low.kill(e.0.clone()),
+                high: self.high.without_top(),
+                len: self.len - 1,
+                next: self.next
+            }
+        }
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> FromIterator<V> for MinMaxHeap<V, P> {
+    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> MinMaxHeap<V, P> {
+        let mut heap = MinMaxHeap::empty();
+        heap.extend(iterator);
+        heap
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Extend<V> for MinMaxHeap<V, P> {
+    fn extend<Iterable: IntoIterator<Item=V>>(&mut self, iterator: Iterable) {
+        for v in iterator {
+            *self = self.insert(v);
+        }
+    }
+}
+
+impl<V: Ord + fmt::Debug, P: SharedPointerKind> fmt::Debug for MinMaxHeap<V, P> {
+    /// Heaps are shown in sorted order. This costs O(n log n) time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MinMaxHeap")?;
+        let mut entries = f.debug_list();
+        let mut h = self.low.clone();
+        while let Some(e) = h.all.min() {
+            entries.entry(&*e.value);
+            h = h.without_top();
+        }
+        entries.finish()
+    }
+}
@@ -0,0 +1,262 @@
+//! Maps from keys to any number of values.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::vec;
+use compare::Compare;
+use pointer::{SharedPointerKind, RcKind};
+use queue::BatchedQueue;
+use rbtree::{RBTree, RBTreeFinger};
+use traits::{Queue, Set};
+
+// A key's values, oldest first, never empty. Buckets are ordered by key
+// alone, so they can be looked up, replaced and removed by key.
+struct Bucket<K, V, P: SharedPointerKind> {
+    key: K,
+    values: BatchedQueue<V, P>
+}
+
+impl<K: Clone, V, P: SharedPointerKind> Clone for Bucket<K, V, P> {
+    fn clone(&self) -> Bucket<K, V, P> {
+        Bucket { key: self.key.clone(), values: self.values.clone() }
+    }
+}
+
+enum ByKey {}
+
+impl<K: Ord, V, P: SharedPointerKind> Compare<Bucket<K, V, P>> for ByKey {
+    fn compare(a: &Bucket<K, V, P>, b: &Bucket<K, V, P>) -> Ordering {
+        a.key.cmp(&b.key)
+    }
+}
+
+/// A persistent ordered multimap: each key maps to a queue of values, in the
+/// order they were inserted. A key with no values is not in the map.
+pub struct MultiMap<K, V, P: SharedPointerKind = RcKind> {
+    buckets: RBTree<Bucket<K, V, P>, P, ByKey>,
+    // The total number of values.
+    len: usize
+}
+
+impl<K, V, P: SharedPointerKind> Clone for MultiMap<K, V, P> {
+    fn clone(&self) -> MultiMap<K, V, P> {
+        MultiMap { buckets: self.buckets.clone(), len: self.len }
+    }
+}
+
+impl<K: Ord + Clone, V> MultiMap<K, V> {
+    /// Return an empty map that shares nodes using `Rc`.
+    pub fn new() -> MultiMap<K, V> {
+        MultiMap::empty()
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> Default for MultiMap<K, V, P> {
+    fn default() -> MultiMap<K, V, P> {
+        MultiMap::empty()
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> MultiMap<K, V, P> {
+    /// Return an empty map.
+    pub fn empty() -> MultiMap<K, V, P> {
+        MultiMap { buckets: RBTree::empty(), len: 0 }
+    }
+
+    /// The total number of values, counting every value of every key.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the map has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of keys with at least one value.
+    pub fn key_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Return true if `key` has at least one value.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.bucket(key).is_some()
+    }
+
+    /// The values for `key`, oldest first. Empty if the key has none.
+    pub fn get_all(&self, key: &K) -> BatchedQueue<V, P> {
+        match self.bucket(key) {
+            Some(b) => b.values.clone(),
+            None => BatchedQueue::empty()
+        }
+    }
+
+    /// Return a map with `value` added after any values `key` already has.
+    pub fn insert(&self, key: K, value: V) -> MultiMap<K, V, P> {
+        let values = Queue::snoc(self.get_all(&key), value);
+        MultiMap { buckets: self.set_values(key, values), len: self.len + 1 }
+    }
+
+    /// Return a map without `key`'s oldest value equal to `value`. If there
+    /// is no such value, this returns a copy of `self`. Takes time
+    /// proportional to the number of values `key` has.
+    pub fn remove_one(&self, key: &K, value: &V) -> MultiMap<K, V, P>
+        where V: PartialEq
+    {
+        let values = match self.bucket(key) {
+            Some(b) => &b.values,
+            None => return self.clone()
+        };
+        let index = match values.item_refs().iter().position(|v| *v == value) {
+            Some(index) => index,
+            None => return self.clone()
+        };
+        MultiMap {
+            buckets: self.set_values(key.clone(), values.without_index(index)),
+            len: self.len - 1
+        }
+    }
+
+    /// Return a map without any of `key`'s values.
+    pub fn remove_all(&self, key: &K) -> MultiMap<K, V, P> {
+        let count = match self.bucket(key) {
+            Some(b) => b.values.item_refs().len(),
+            None => return self.clone()
+        };
+        MultiMap {
+            buckets: self.set_values(key.clone(), BatchedQueue::empty()),
+            len: self.len - count
+        }
+    }
+
+    /// Iterate over every key and value: keys in order, and each key's values
+    /// oldest first.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        Iter { finger: self.buckets.finger(), key: None, values: vec![].into_iter() }
+    }
+
+    /// Consume the map and iterate over its keys, in order. Keys are moved
+    /// out of any part of the map that no other map shares, and cloned from
+    /// the rest.
+    pub fn into_keys(self) -> IntoKeys<K> {
+        let keys = self.buckets.into_iter()
+            .map(|b| b.key)
+            .collect::<Vec<K>>();
+        IntoKeys(keys.into_iter())
+    }
+
+    /// Consume the map and iterate over its values: keys in order, and each
+    /// key's values oldest first. Like `into_keys`, this moves values out
+    /// where it can and clones the rest.
+    pub fn into_values(self) -> IntoValues<V>
+        where V: Clone
+    {
+        let values = self.buckets.into_iter()
+            .flat_map(|b| b.values.into_items())
+            .collect::<Vec<V>>();
+        IntoValues(values.into_iter())
+    }
+
+    fn bucket(&self, key: &K) -> Option<&Bucket<K, V, P>> {
+        self.buckets.find_by(|b| key.cmp(&b.key))
+    }
+
+    // Return the buckets with `key` holding `values`. A key with no values
+    // loses its bucket.
+    fn set_values(&self, key: K, values: BatchedQueue<V, P>) -> RBTree<Bucket<K, V, P>, P, ByKey> {
+        if values.is_empty() {
+            self.buckets.remove_by(|b| key.cmp(&b.key)).expect("key is in the map")
+        } else {
+            self.buckets.replace(Bucket { key, values })
+        }
+    }
+}
+
+/// An iterator over the keys and values of a `MultiMap`.
+pub struct Iter<'a, K: 'a, V: 'a, P: SharedPointerKind + 'a = RcKind> {
+    finger: RBTreeFinger<'a, Bucket<K, V, P>, P, ByKey>,
+    // The key whose values are being visited, and the rest of its values.
+    key: Option<&'a K>,
+    values: vec::IntoIter<&'a V>
+}
+
+impl<'a, K, V, P: SharedPointerKind> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            if let (Some(key), Some(value)) = (self.key, self.values.next()) {
+                return Some((key, value));
+            }
+            let bucket = self.finger.get()?;
+            self.finger.advance();
+            self.key = Some(&bucket.key);
+            self.values = bucket.values.item_refs().into_iter();
+        }
+    }
+}
+
+/// An iterator that moves the keys out of a `MultiMap`. See
+/// `MultiMap::into_keys`.
+pub struct IntoKeys<K>(vec::IntoIter<K>);
+
+impl<K> Iterator for IntoKeys<K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An iterator that moves the values out of a `MultiMap`. See
+/// `MultiMap::into_values`.
+pub struct IntoValues<V>(vec::IntoIter<V>);
+
+impl<V> Iterator for IntoValues<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> FromIterator<(K, V)> for MultiMap<K, V, P> {
+    fn from_iter<Iterable: IntoIterator<Item=(K, V)>>(iterator: Iterable) -> MultiMap<K, V, P> {
+        let mut map = MultiMap::empty();
+        map.extend(iterator);
+        map
+    }
+}
+
+impl<K: Ord + Clone, V, P: SharedPointerKind> Extend<(K, V)> for MultiMap<K, V, P> {
+    fn extend<Iterable: IntoIterator<Item=(K, V)>>(&mut self, iterator: Iterable) {
+        for (k, v) in iterator {
+            *self = self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, P> fmt::Debug for MultiMap<K, V, P>
+    where K: Ord + Clone + fmt::Debug, V: fmt::Debug, P: SharedPointerKind
+{
+    /// Shown as a map from each key to its values.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MultiMap")?;
+        let mut map = f.debug_map();
+        let mut finger = self.buckets.finger();
+        while let Some(bucket) = finger.get() {
+            map.entry(&bucket.key, &bucket.values.item_refs());
+            finger.advance();
+        }
+        map.finish()
+    }
+}
@@ -0,0 +1,529 @@
+//! 9.1 Positional number systems
+//!
+//! Many structures in chapter 9 are built the way a number is written down in
+//! some positional number system. A binary random-access list of n items is a
+//! list of complete trees, one of size 2^i for each 1 bit of n; adding an
+//! item is incrementing n, and the carries are tree links. Change the number
+//! system and you get a different structure with different bounds.
+//!
+//! This module has the number systems on their own, as persistent values:
+//!
+//! * `DenseBinary`: ordinary binary, one digit (0 or 1) per position.
+//!
+//! * `SparseBinary`: binary again, but storing only the weights of the 1
+//!   digits. Increment and decrement still take O(log n) time.
+//!
+//! * `SkewBinary`: digits weighted 2^(i+1) - 1, where only the lowest nonzero
+//!   digit may be 2. Increment and decrement take O(1) time in the worst
+//!   case, which is what makes skew binary random-access lists fast.
+//!
+//! * `ZerolessBinary`: digits 1 and 2 instead of 0 and 1, so that every
+//!   position holds at least one tree and the lowest one is never missing.
+//!
+//! All four implement `Numeral`. A numerical representation can keep one
+//! alongside its trees: `weights` gives the size of each tree, smallest
+//! first, and how `increment` and `decrement` change the weights says which
+//! trees to link or split. Digits are stored least significant first, in a
+//! `List`, so incrementing shares every position past the last carry with the
+//! original.
+
+use std::fmt;
+use list::List;
+use pointer::{RcKind, SharedPointerKind};
+use traits::Stack;
+
+/// A natural number written in some positional number system.
+pub trait Numeral: Sized {
+    /// Return zero.
+    fn zero() -> Self;
+
+    /// Return true if this is zero.
+    fn is_zero(&self) -> bool;
+
+    /// Return this number plus one.
+    fn increment(&self) -> Self;
+
+    /// Return this number minus one, or None if this is zero.
+    fn decrement(&self) -> Option<Self>;
+
+    /// Return the sum of two numbers.
+    fn add(&self, other: &Self) -> Self;
+
+    /// Return the representation of `n`.
+    fn from_u64(n: u64) -> Self;
+
+    /// The weight of each nonzero digit, smallest first, listed once per unit
+    /// of the digit: a 2 in the fours place contributes two 4s. In a
+    /// numerical representation, these are the sizes of the trees.
+    fn weights(&self) -> Vec<u64>;
+
+    /// Return the number's value.
+    fn to_u64(&self) -> u64 {
+        self.weights().iter().sum()
+    }
+}
+
+// Return `prefix` (least significant first) followed by `tail`.
+fn rebuild<T, P: SharedPointerKind>(prefix: Vec<T>, tail: List<T, P>) -> List<T, P> {
+    prefix.into_iter().rev().fold(tail, |list, d| List::cons(d, list))
+}
+
+// Add two lists of digits position by position, where `digit_and_carry`
+// turns the total at one position into that position's digit and the carry.
+// Once one list runs out and there's no carry, the rest of the other list is
+// shared.
+fn add_digits<P, F>(a: &List<u8, P>, b: &List<u8, P>, digit_and_carry: F) -> List<u8, P>
+    where P: SharedPointerKind, F: Fn(u8) -> (u8, u8)
+{
+    let mut a = a.clone();
+    let mut b = b.clone();
+    let mut prefix = vec![];
+    let mut carry = 0;
+    loop {
+        let (da, ra) = match a.split() {
+            Some((&d, rest)) => (d, rest.clone()),
+            None if carry == 0 => return rebuild(prefix, b),
+            None => (0, List::empty())
+        };
+        let (db, rb) = match b.split() {
+            Some((&d, rest)) => (d, rest.clone()),
+            None if carry == 0 => return rebuild(prefix, List::cons(da, ra)),
+            None => (0, List::empty())
+        };
+        let total = da + db + carry;
+        let (digit, next_carry) = digit_and_carry(total);
+        prefix.push(digit);
+        carry = next_carry;
+        a = ra;
+        b = rb;
+    }
+}
+
+// Write `Name[d0, d1, ...]`.
+fn fmt_digits<T: fmt::Debug, P: SharedPointerKind>(f: &mut fmt::Formatter, name: &str, digits: &List<T, P>)
+                                                  -> fmt::Result {
+    f.write_str(name)?;
+    f.debug_list().entries(digits.iter_refs()).finish()
+}
+
+/* Dense binary */
+
+/// A number in binary, with one digit (0 or 1) per position, least
+/// significant first. There are no trailing zeros; zero has no digits.
+pub struct DenseBinary<P: SharedPointerKind = RcKind>(List<u8, P>);
+
+impl DenseBinary {
+    /// Return zero, with digits shared using `Rc`.
+    pub fn new() -> DenseBinary { DenseBinary(List::new()) }
+}
+
+impl<P: SharedPointerKind> DenseBinary<P> {
+    /// The digits, least significant first.
+    pub fn digits(&self) -> &List<u8, P> {
+        &self.0
+    }
+}
+
+impl<P: SharedPointerKind> Clone for DenseBinary<P> {
+    fn clone(&self) -> DenseBinary<P> {
+        DenseBinary(self.0.clone())
+    }
+}
+
+impl<P: SharedPointerKind> Default for DenseBinary<P> {
+    fn default() -> DenseBinary<P> {
+        DenseBinary::zero()
+    }
+}
+
+impl<P: SharedPointerKind> fmt::Debug for DenseBinary<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_digits(f, "DenseBinary", &self.0)
+    }
+}
+
+impl<P: SharedPointerKind> Numeral for DenseBinary<P> {
+    fn zero() -> DenseBinary<P> {
+        DenseBinary(List::empty())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn increment(&self) -> DenseBinary<P> {
+        // Each 1 becomes 0 and carries; the first 0 (or the end) takes the
+        // carry.
+        let mut prefix = vec![];
+        let mut rest = &self.0;
+        while let Some((&1, tail)) = rest.split() {
+            prefix.push(0);
+            rest = tail;
+        }
+        let rest = rest.tail().cloned().unwrap_or_default();
+        prefix.push(1);
+        DenseBinary(rebuild(prefix, rest))
+    }
+
+    fn decrement(&self) -> Option<DenseBinary<P>> {
+        // Each 0 becomes 1 and borrows; the first 1 pays for it, and is
+        // dropped if it was the last digit.
+        let mut prefix = vec![];
+        let mut rest = &self.0;
+        while let Some((&0, tail)) = rest.split() {
+            prefix.push(1);
+            rest = tail;
+        }
+        let (_, tail) = rest.split()?;
+        if !tail.is_empty() {
+            prefix.push(0);
+        }
+        Some(DenseBinary(rebuild(prefix, tail.clone())))
+    }
+
+    fn add(&self, other: &DenseBinary<P>) -> DenseBinary<P> {
+        DenseBinary(add_digits(&self.0, &other.0, |total| (total % 2, total / 2)))
+    }
+
+    fn from_u64(mut n: u64) -> DenseBinary<P> {
+        let mut digits = vec![];
+        while n > 0 {
+            digits.push((n % 2) as u8);
+            n /= 2;
+        }
+        DenseBinary(rebuild(digits, List::empty()))
+    }
+
+    fn weights(&self) -> Vec<u64> {
+        self.0.iter_refs().enumerate()
+            .filter(|&(_, &d)| d == 1)
+            .map(|(i, _)| 1 << i)
+            .collect()
+    }
+}
+
+/* Sparse binary */
+
+/// A number in binary, stored as the weights of its 1 digits: a strictly
+/// increasing list of powers of two.
+pub struct SparseBinary<P: SharedPointerKind = RcKind>(List<u64, P>);
+
+impl SparseBinary {
+    /// Return zero, with weights shared using `Rc`.
+    pub fn new() -> SparseBinary { SparseBinary(List::new()) }
+}
+
+impl<P: SharedPointerKind> SparseBinary<P> {
+    /// The weights of the 1 digits, smallest first.
+    pub fn digits(&self) -> &List<u64, P> {
+        &self.0
+    }
+}
+
+impl<P: SharedPointerKind> Clone for SparseBinary<P> {
+    fn clone(&self) -> SparseBinary<P> {
+        SparseBinary(self.0.clone())
+    }
+}
+
+impl<P: SharedPointerKind> Default for SparseBinary<P> {
+    fn default() -> SparseBinary<P> {
+        SparseBinary::zero()
+    }
+}
+
+impl<P: SharedPointerKind> fmt::Debug for SparseBinary<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_digits(f, "SparseBinary", &self.0)
+    }
+}
+
+impl<P: SharedPointerKind> Numeral for SparseBinary<P> {
+    fn zero() -> SparseBinary<P> {
+        SparseBinary(List::empty())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn increment(&self) -> SparseBinary<P> {
+        // Carry a weight up the list until it finds a gap.
+        let mut carry = 1;
+        let mut rest = &self.0;
+        while let Some((&w, tail)) = rest.split() {
+            if w != carry {
+                break;
+            }
+            carry *= 2;
+            rest = tail;
+        }
+        SparseBinary(List::cons(carry, rest.clone()))
+    }
+
+    fn decrement(&self) -> Option<SparseBinary<P>> {
+        // Borrowing from weight w leaves 1, 2, 4, ..., w/2.
+        let (&w, tail) = self.0.split()?;
+        let prefix = (0..w.trailing_zeros()).map(|i| 1 << i).collect();
+        Some(SparseBinary(rebuild(prefix, tail.clone())))
+    }
+
+    fn add(&self, other: &SparseBinary<P>) -> SparseBinary<P> {
+        let mut a = &self.0;
+        let mut b = &other.0;
+        let mut weights = vec![];
+        let mut carry = None;
+        loop {
+            let wa = a.head().cloned();
+            let wb = b.head().cloned();
+            let least = match [wa, wb, carry].iter().flatten().min() {
+                None => break,
+                Some(&w) => w
+            };
+            if carry.is_none() && (wa.is_none() || wb.is_none()) {
+                // Nothing left to add to the rest of the longer list.
+                let rest = if wa.is_none() { b } else { a };
+                return SparseBinary(rebuild(weights, rest.clone()));
+            }
+            let mut count = 0;
+            if wa == Some(least) {
+                count += 1;
+                a = a.tail().unwrap();
+            }
+            if wb == Some(least) {
+                count += 1;
+                b = b.tail().unwrap();
+            }
+            if carry == Some(least) {
+                count += 1;
+                carry = None;
+            }
+            if count % 2 == 1 {
+                weights.push(least);
+            }
+            if count >= 2 {
+                carry = Some(least * 2);
+            }
+        }
+        SparseBinary(rebuild(weights, List::empty()))
+    }
+
+    fn from_u64(n: u64) -> SparseBinary<P> {
+        let weights = (0..64).map(|i| 1 << i).filter(|w| n & w != 0).collect();
+        SparseBinary(rebuild(weights, List::empty()))
+    }
+
+    fn weights(&self) -> Vec<u64> {
+        self.0.iter_refs().cloned().collect()
+    }
+}
+
+/* Skew binary */
+
+/// A number in skew binary, stored as the weights of its nonzero digits: an
+/// increasing list of numbers of the form 2^k - 1, in which only the first
+/// two may be equal.
+pub struct SkewBinary<P: SharedPointerKind = RcKind>(List<u64, P>);
+
+impl SkewBinary {
+    /// Return zero, with weights shared using `Rc`.
+    pub fn new() -> SkewBinary { SkewBinary(List::new()) }
+}
+
+impl<P: SharedPointerKind> SkewBinary<P> {
+    /// The weights of the nonzero digits, smallest first. A 2 digit is listed
+    /// twice.
+    pub fn digits(&self) -> &List<u64, P> {
+        &self.0
+    }
+}
+
+impl<P: SharedPointerKind> Clone for SkewBinary<P> {
+    fn clone(&self) -> SkewBinary<P> {
+        SkewBinary(self.0.clone())
+    }
+}
+
+impl<P: SharedPointerKind> Default for SkewBinary<P> {
+    fn default() -> SkewBinary<P> {
+        SkewBinary::zero()
+    }
+}
+
+impl<P: SharedPointerKind> fmt::Debug for SkewBinary<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_digits(f, "SkewBinary", &self.0)
+    }
+}
+
+impl<P: SharedPointerKind> Numeral for SkewBinary<P> {
+    fn zero() -> SkewBinary<P> {
+        SkewBinary(List::empty())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn increment(&self) -> SkewBinary<P> {
+        // If the first two weights are equal, w + w + 1 is the next weight
+        // up, and there's no carry. This is the whole point of skew binary.
+        if let Some((&w1, &w2, rest)) = self.0.uncons2() {
+            if w1 == w2 {
+                return SkewBinary(List::cons(w1 + w2 + 1, rest.clone()));
+            }
+        }
+        SkewBinary(List::cons(1, self.0.clone()))
+    }
+
+    fn decrement(&self) -> Option<SkewBinary<P>> {
+        let (&w, tail) = self.0.split()?;
+        if w == 1 {
+            Some(SkewBinary(tail.clone()))
+        } else {
+            let half = w / 2;
+            Some(SkewBinary(List::cons(half, List::cons(half, tail.clone()))))
+        }
+    }
+
+    /// Skew binary has no simple digit-by-digit addition, so this adds the
+    /// values and converts the sum back, in O(log n) time.
+    fn add(&self, other: &SkewBinary<P>) -> SkewBinary<P> {
+        SkewBinary::from_u64(self.to_u64() + other.to_u64())
+    }
+
+    fn from_u64(mut n: u64) -> SkewBinary<P> {
+        // Greedily take the largest weight that fits. This yields the
+        // canonical form, where only the smallest weight can repeat.
+        let mut weights = vec![];
+        let mut w = u64::MAX >> 1;
+        while n > 0 {
+            while w > n {
+                w /= 2;
+            }
+            weights.push(w);
+            n -= w;
+        }
+        weights.reverse();
+        SkewBinary(rebuild(weights, List::empty()))
+    }
+
+    fn weights(&self) -> Vec<u64> {
+        self.0.iter_refs().cloned().collect()
+    }
+}
+
+/* Zeroless binary */
+
+/// A number in zeroless binary, with one digit (1 or 2) per position, least
+/// significant first. The digit in position i is worth that many times 2^i.
+pub struct ZerolessBinary<P: SharedPointerKind = RcKind>(List<u8, P>);
+
+impl ZerolessBinary {
+    /// Return zero, with digits shared using `Rc`.
+    pub fn new() -> ZerolessBinary { ZerolessBinary(List::new()) }
+}
+
+impl<P: SharedPointerKind> ZerolessBinary<P> {
+    /// The digits, least significant first.
+    pub fn digits(&self) -> &List<u8, P> {
+        &self.0
+    }
+}
+
+impl<P: SharedPointerKind> Clone for ZerolessBinary<P> {
+    fn clone(&self) -> ZerolessBinary<P> {
+        ZerolessBinary(self.0.clone())
+    }
+}
+
+impl<P: SharedPointerKind> Default for ZerolessBinary<P> {
+    fn default() -> ZerolessBinary<P> {
+        ZerolessBinary::zero()
+    }
+}
+
+impl<P: SharedPointerKind> fmt::Debug for ZerolessBinary<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_digits(f, "ZerolessBinary", &self.0)
+    }
+}
+
+impl<P: SharedPointerKind> Numeral for ZerolessBinary<P> {
+    fn zero() -> ZerolessBinary<P> {
+        ZerolessBinary(List::empty())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn increment(&self) -> ZerolessBinary<P> {
+        // 2 + 1 is 1 carry 1; the first 1 (or the end) takes the carry.
+        let mut prefix = vec![];
+        let mut rest = &self.0;
+        while let Some((&2, tail)) = rest.split() {
+            prefix.push(1);
+            rest = tail;
+        }
+        match rest.split() {
+            None => prefix.push(1),
+            Some((_, tail)) => {
+                prefix.push(2);
+                rest = tail;
+            }
+        }
+        ZerolessBinary(rebuild(prefix, rest.clone()))
+    }
+
+    fn decrement(&self) -> Option<ZerolessBinary<P>> {
+        // 1 - 1 is 2 borrow 1, except in the last position, which is
+        // dropped; the first 2 pays for the borrow.
+        let mut prefix = vec![];
+        let mut rest = &self.0;
+        loop {
+            match rest.split()? {
+                (&2, tail) => {
+                    prefix.push(1);
+                    return Some(ZerolessBinary(rebuild(prefix, tail.clone())));
+                }
+                (_, tail) if tail.is_empty() => {
+                    return Some(ZerolessBinary(rebuild(prefix, List::empty())));
+                }
+                (_, tail) => {
+                    prefix.push(2);
+                    rest = tail;
+                }
+            }
+        }
+    }
+
+    fn add(&self, other: &ZerolessBinary<P>) -> ZerolessBinary<P> {
+        // Each position must get a 1 or a 2, so an even total leaves 2 and
+        // carries the rest.
+        ZerolessBinary(add_digits(&self.0, &other.0, |total| {
+            let digit = if total % 2 == 1 { 1 } else { 2 };
+            (digit, (total - digit) / 2)
+        }))
+    }
+
+    fn from_u64(mut n: u64) -> ZerolessBinary<P> {
+        let mut digits = vec![];
+        while n > 0 {
+            let digit = if n % 2 == 1 { 1 } else { 2 };
+            digits.push(digit as u8);
+            n = (n - digit) / 2;
+        }
+        ZerolessBinary(rebuild(digits, List::empty()))
+    }
+
+    fn weights(&self) -> Vec<u64> {
+        let mut weights = vec![];
+        for (i, &d) in self.0.iter_refs().enumerate() {
+            for _ in 0..d {
+                weights.push(1 << i);
+            }
+        }
+        weights
+    }
+}
@@ -0,0 +1,141 @@
+//! Lenses: composable paths into nested persistent structures.
+//!
+//! A lens focuses on one part of a larger value, say the third item of a list
+//! stored in a field of a struct. Through the lens you can read that part, or
+//! make a copy of the whole value with that part replaced. Since everything
+//! in this crate is persistent, the copy shares all but the path down to the
+//! part that changed.
+//!
+//! The part a lens focuses on may be missing (there might not be a third
+//! item), so `get` and `set` return `Option`. In the jargon this makes every
+//! lens here a prism as well.
+//!
+//! ```ignore
+//! let third_server = field(|c: &Config| &c.servers,
+//!                          |c, servers| Config { servers, ..c.clone() })
+//!     .then(index(2));
+//! let updated = third_server.set(&config, "db3".to_string());
+//! ```
+//!
+//! When the first lens in a composition works on more than one type, as
+//! `index` does, Rust can't tell which `then` is meant; name the type:
+//! `Lens::<List<List<i32>>>::then(index(1), index(0))`.
+
+use pointer::SharedPointerKind;
+use list::List;
+use traits::Stack;
+
+/// A path from a value of type `S` to a part of it.
+pub trait Lens<S> {
+    /// The type of the part this lens focuses on.
+    type Target;
+
+    /// Return the part of `s` this lens focuses on, or None if it's missing.
+    fn get<'a>(&self, s: &'a S) -> Option<&'a Self::Target>;
+
+    /// Return a copy of `s` with the focused part replaced by `value`, or
+    /// None if that part is missing.
+    fn set(&self, s: &S, value: Self::Target) -> Option<S>;
+
+    /// Return a copy of `s` with `f` applied to the focused part, or None if
+    /// that part is missing.
+    fn modify<F>(&self, s: &S, f: F) -> Option<S>
+        where F: FnOnce(&Self::Target) -> Self::Target
+    {
+        let value = f(self.get(s)?);
+        self.set(s, value)
+    }
+
+    /// Return a lens that focuses on the part `next` focuses on, within the
+    /// part this lens focuses on.
+    fn then<L: Lens<Self::Target>>(self, next: L) -> Then<Self, L>
+        where Self: Sized
+    {
+        Then { first: self, second: next }
+    }
+}
+
+/// Two lenses composed. See `Lens::then`.
+#[derive(Clone, Copy, Debug)]
+pub struct Then<A, B> {
+    first: A,
+    second: B
+}
+
+// The middle type has to be `'static` for `get` to be able to return a
+// reference into it with the lifetime of `s`. Types that contain borrowed
+// references can't be the middle of a composition.
+impl<S, A, B> Lens<S> for Then<A, B>
+    where A: Lens<S>, A::Target: 'static, B: Lens<A::Target>
+{
+    type Target = B::Target;
+
+    fn get<'a>(&self, s: &'a S) -> Option<&'a B::Target> {
+        self.second.get(self.first.get(s)?)
+    }
+
+    fn set(&self, s: &S, value: B::Target) -> Option<S> {
+        let inner = self.second.set(self.first.get(s)?, value)?;
+        self.first.set(s, inner)
+    }
+}
+
+/// A lens built from a pair of functions. See `field`.
+pub struct Field<S, A> {
+    get: fn(&S) -> &A,
+    set: fn(&S, A) -> S
+}
+
+// `derive` would require `S: Clone` and `A: Clone`.
+impl<S, A> Clone for Field<S, A> {
+    fn clone(&self) -> Field<S, A> {
+        *self
+    }
+}
+
+impl<S, A> Copy for Field<S, A> {}
+
+/// Return a lens that reads a part of an `S` with `get` and replaces it with
+/// `set`, typically a field of a struct. The part is never missing.
+pub fn field<S, A>(get: fn(&S) -> &A, set: fn(&S, A) -> S) -> Field<S, A> {
+    Field { get, set }
+}
+
+impl<S, A> Lens<S> for Field<S, A> {
+    type Target = A;
+
+    fn get<'a>(&self, s: &'a S) -> Option<&'a A> {
+        Some((self.get)(s))
+    }
+
+    fn set(&self, s: &S, value: A) -> Option<S> {
+        Some((self.set)(s, value))
+    }
+}
+
+/// A lens that focuses on one item of a list. See `index`.
+#[derive(Clone, Copy, Debug)]
+pub struct Index(usize);
+
+/// Return a lens that focuses on the item at `index` in a `List`, counting
+/// from 0. Setting it takes O(index) time: it copies the items before it and
+/// shares the rest.
+pub fn index(index: usize) -> Index {
+    Index(index)
+}
+
+impl<V: Clone, P: SharedPointerKind> Lens<List<V, P>> for Index {
+    type Target = V;
+
+    fn get<'a>(&self, list: &'a List<V, P>) -> Option<&'a V> {
+        let mut p = list;
+        for _ in 0..self.0 {
+            p = p.tail()?;
+        }
+        p.head()
+    }
+
+    fn set(&self, list: &List<V, P>, value: V) -> Option<List<V, P>> {
+        list.replace_at(self.0, value)
+    }
+}
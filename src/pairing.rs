@@ -0,0 +1,413 @@
+//! 5.5 Pairing heaps
+//!
+//! A pairing heap is a tree whose root holds the minimum, with any number of
+//! subheaps as children. Merging two heaps makes the one with the larger root
+//! a child of the other, so `insert` and `merge` take constant time;
+//! `without_min` merges the root's children in pairs and then merges the
+//! pairs, which takes O(log n) amortized time. As Okasaki warns, those
+//! amortized bounds can fail when old versions of a heap are reused.
+//!
+//! `HandleHeap` builds on it a heap whose entries can be found again after
+//! they're inserted, so that their keys can be decreased or the entries
+//! deleted, as in Dijkstra's and Prim's algorithms.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use compare::Compare;
+use list::List;
+use list::List::Nil;
+use pointer::{SharedPointerKind, RcKind};
+use rbtree::RBTree;
+use traits::{Heap, Set, Stack};
+
+// As in `heap`, values are behind their own pointers so that merging copies
+// nodes without cloning values.
+struct PairingNode<V, P: SharedPointerKind> {
+    value: P::Pointer<V>,
+    children: List<PairingHeap<V, P>, P>
+}
+
+enum PairingImpl<V, P: SharedPointerKind> {
+    Empty,
+    NonEmpty(P::Pointer<PairingNode<V, P>>)
+}
+
+use self::PairingImpl::*;
+
+// Inserting values in decreasing order makes each new root the parent of the
+// old one, so a heap can be as deep as it is large. Rather than let the
+// default drop recurse that deep, a node unlinks its children, and any child
+// that nothing else shares has its own children unlinked in turn, from an
+// explicit stack.
+impl<V, P: SharedPointerKind> Drop for PairingNode<V, P> {
+    fn drop(&mut self) {
+        let mut stack = vec![mem::replace(&mut self.children, Nil)];
+        while let Some(mut children) = stack.pop() {
+            while let List::Cons(ref mut rc) = children {
+                let (child, rest) = match P::get_mut(rc) {
+                    Some(cell) => (mem::replace(&mut cell.0, PairingHeap(Empty)),
+                                   mem::replace(&mut cell.1, Nil)),
+                    None => break
+                };
+                if let PairingHeap(NonEmpty(mut child)) = child {
+                    if let Some(node) = P::get_mut(&mut child) {
+                        stack.push(mem::replace(&mut node.children, Nil));
+                    }
+                }
+                children = rest;
+            }
+        }
+    }
+}
+
+/// A persistent pairing heap. For documentation, see the `Heap` trait.
+pub struct PairingHeap<V, P: SharedPointerKind = RcKind>(PairingImpl<V, P>);
+
+impl<V, P: SharedPointerKind> Clone for PairingHeap<V, P> {
+    fn clone(&self) -> PairingHeap<V, P> {
+        match self.0 {
+            Empty => PairingHeap(Empty),
+            NonEmpty(ref rc) => PairingHeap(NonEmpty(rc.clone()))
+        }
+    }
+}
+
+impl<V> PairingHeap<V> {
+    /// Return an empty heap that shares nodes using `Rc`.
+    pub fn new() -> PairingHeap<V> { PairingHeap(Empty) }
+}
+
+impl<V, P: SharedPointerKind> Default for PairingHeap<V, P> {
+    fn default() -> PairingHeap<V, P> { PairingHeap(Empty) }
+}
+
+// Merge a list of heaps by merging neighbors in pairs, left to right, and
+// then merging the pairs right to left. This is a loop rather than
+// Okasaki's recursion, because after many inserts the root can have
+// thousands of children.
+fn merge_pairs<V: Ord, P: SharedPointerKind>(heaps: &List<PairingHeap<V, P>, P>) -> PairingHeap<V, P> {
+    let mut pairs = vec![];
+    let mut p = heaps;
+    while let Some((a, rest)) = p.split() {
+        match rest.split() {
+            None => {
+                pairs.push(a.clone());
+                break;
+            }
+            Some((b, rest)) => {
+                pairs.push(PairingHeap::merge(a.clone(), b.clone()));
+                p = rest;
+            }
+        }
+    }
+    pairs.into_iter().rev().fold(PairingHeap(Empty), |acc, h| PairingHeap::merge(h, acc))
+}
+
+impl<V: Ord, P: SharedPointerKind> Heap for PairingHeap<V, P> {
+    type Item = V;
+
+    fn empty() -> PairingHeap<V, P> { PairingHeap(Empty) }
+
+    fn is_empty(&self) -> bool {
+        matches!(*self, PairingHeap(Empty))
+    }
+
+    fn merge(h1: PairingHeap<V, P>, h2: PairingHeap<V, P>) -> PairingHeap<V, P> {
+        match (h1, h2) {
+            (PairingHeap(Empty), h) => h,
+            (h, PairingHeap(Empty)) => h,
+            (PairingHeap(NonEmpty(n1)), PairingHeap(NonEmpty(n2))) => {
+                let (top, child) = if n1.value.cmp(&n2.value) == Ordering::Greater {
+                    (n2, n1)
+                } else {
+                    (n1, n2)
+                };
+                PairingHeap(NonEmpty(P::new(PairingNode {
+                    value: top.value.clone(),
+                    children: List::cons(PairingHeap(NonEmpty(child)), top.children.clone())
+                })))
+            }
+        }
+    }
+
+    fn insert(&self, value: V) -> PairingHeap<V, P> {
+        PairingHeap::merge(PairingHeap(NonEmpty(P::new(PairingNode {
+            value: P::new(value),
+            children: Nil
+        }))), self.clone())
+    }
+
+    fn min(&self) -> Option<&V> {
+        match self.0 {
+            Empty => None,
+            NonEmpty(ref n) => Some(&n.value)
+        }
+    }
+
+    fn without_min(&self) -> PairingHeap<V, P> {
+        match self.0 {
+            Empty => PairingHeap(Empty),
+            NonEmpty(ref n) => merge_pairs(&n.children)
+        }
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> FromIterator<V> for PairingHeap<V, P> {
+    /// Build a heap in O(n) time. Inserting into a pairing heap already takes
+    /// constant time, so there's no need for a separate heapify.
+    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> PairingHeap<V, P> {
+        let mut heap = PairingHeap::empty();
+        heap.extend(iterator);
+        heap
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Extend<V> for PairingHeap<V, P> {
+    fn extend<Iterable: IntoIterator<Item=V>>(&mut self, iterator: Iterable) {
+        for v in iterator {
+            *self = self.insert(v);
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind> PairingHeap<V, P> {
+    /// Return all the values, in no particular order. Values are moved out
+    /// of nodes no other heap shares, and cloned from the rest. This visits
+    /// each node once, taking O(n) time; popping the values in order would
+    /// take O(n log n).
+    pub fn into_vec_unordered(self) -> Vec<V>
+        where V: Clone
+    {
+        let mut values = vec![];
+        let mut stack = vec![self];
+        while let Some(PairingHeap(heap)) = stack.pop() {
+            let rc = match heap {
+                Empty => continue,
+                NonEmpty(rc) => rc
+            };
+            match P::try_unwrap(rc) {
+                Ok(mut node) => {
+                    let mut children = mem::replace(&mut node.children, Nil);
+                    // As in `heap`, the node has a `Drop` impl, so copy the
+                    // value's pointer and drop the node before unwrapping it.
+                    let value = node.value.clone();
+                    drop(node);
+                    values.push(P::try_unwrap(value).unwrap_or_else(|v| (*v).clone()));
+                    while let Some(child) = children.pop() {
+                        stack.push(child);
+                    }
+                }
+                Err(rc) => {
+                    values.push((*rc.value).clone());
+                    stack.extend(rc.children.iter().cloned());
+                }
+            }
+        }
+        values
+    }
+}
+
+impl<V: Ord + fmt::Debug, P: SharedPointerKind> fmt::Debug for PairingHeap<V, P> {
+    /// Heaps are shown in sorted order. This costs O(n log n) time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PairingHeap")?;
+        let mut entries = f.debug_list();
+        let mut h = self.clone();
+        while let Some(v) = h.min() {
+            entries.entry(v);
+            h = h.without_min();
+        }
+        entries.finish()
+    }
+}
+
+/// Identifies an entry in a `HandleHeap`. Handles are never reused within a
+/// heap or the heaps made from it. A handle doesn't record which heap it came
+/// from, so in an unrelated heap it may name some other entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(u64);
+
+// An entry in the pairing heap, ordered by key. An entry is stale, and is
+// skipped, if the index no longer gives its handle this key.
+struct Entry<K> {
+    key: K,
+    handle: Handle
+}
+
+impl<K: PartialEq> PartialEq for Entry<K> {
+    fn eq(&self, other: &Entry<K>) -> bool {
+        self.key == other.key && self.handle == other.handle
+    }
+}
+
+impl<K: Eq> Eq for Entry<K> {}
+
+impl<K: Ord> PartialOrd for Entry<K> {
+    fn partial_cmp(&self, other: &Entry<K>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for Entry<K> {
+    fn cmp(&self, other: &Entry<K>) -> Ordering {
+        self.key.cmp(&other.key).then(self.handle.cmp(&other.handle))
+    }
+}
+
+// The current key for each handle that hasn't been deleted. The index is a
+// set of slots ordered by handle alone, so slots can be looked up, replaced
+// and removed by handle.
+struct Slot<K> {
+    handle: Handle,
+    key: K
+}
+
+enum ByHandle {}
+
+impl<K> Compare<Slot<K>> for ByHandle {
+    fn compare(a: &Slot<K>, b: &Slot<K>) -> Ordering {
+        a.handle.cmp(&b.handle)
+    }
+}
+
+/// A persistent heap supporting `decrease_key` and `delete`.
+///
+/// `insert` returns a `Handle` for the new entry, which later calls use to
+/// find it. Decreasing a key inserts a fresh heap entry and marks the old one
+/// stale; stale entries are discarded when they reach the top. So every
+/// operation takes O(log n) amortized time, where n counts stale entries too.
+pub struct HandleHeap<K, P: SharedPointerKind = RcKind> {
+    heap: PairingHeap<Entry<K>, P>,
+    index: RBTree<Slot<K>, P, ByHandle>,
+    next: u64,
+    len: usize
+}
+
+impl<K, P: SharedPointerKind> Clone for HandleHeap<K, P> {
+    fn clone(&self) -> HandleHeap<K, P> {
+        HandleHeap {
+            heap: self.heap.clone(),
+            index: self.index.clone(),
+            next: self.next,
+            len: self.len
+        }
+    }
+}
+
+impl<K: Ord + Clone> HandleHeap<K> {
+    /// Return an empty heap that shares nodes using `Rc`.
+    pub fn new() -> HandleHeap<K> {
+        HandleHeap::empty()
+    }
+}
+
+impl<K: Ord + Clone, P: SharedPointerKind> Default for HandleHeap<K, P> {
+    fn default() -> HandleHeap<K, P> {
+        HandleHeap::empty()
+    }
+}
+
+impl<K: Ord + Clone, P: SharedPointerKind> HandleHeap<K, P> {
+    /// Return an empty heap.
+    pub fn empty() -> HandleHeap<K, P> {
+        HandleHeap { heap: PairingHeap::empty(), index: RBTree::empty(), next: 0, len: 0 }
+    }
+
+    /// The number of entries, not counting deleted ones.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the heap has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return a heap with an entry for `key` added, and a handle for the new
+    /// entry.
+    pub fn insert(&self, key: K) -> (HandleHeap<K, P>, Handle) {
+        let handle = Handle(self.next);
+        let heap = HandleHeap {
+            heap: self.heap.insert(Entry { key: key.clone(), handle }),
+            index: self.index.plus(Slot { handle, key }),
+            next: self.next + 1,
+            len: self.len + 1
+        };
+        (heap, handle)
+    }
+
+    /// The smallest key and its handle, or None if the heap is empty.
+    pub fn min(&self) -> Option<(&K, Handle)> {
+        self.heap.min().map(|e| (&e.key, e.handle))
+    }
+
+    /// Return a heap without the entry that has the smallest key. If the heap
+    /// is empty, this returns an empty heap.
+    pub fn without_min(&self) -> HandleHeap<K, P> {
+        match self.min() {
+            None => self.clone(),
+            Some((_, handle)) => self.delete(handle).unwrap_or_else(|| self.clone())
+        }
+    }
+
+    /// The current key of the entry for `handle`, or None if it has been
+    /// deleted. See `Handle` about handles from other heaps.
+    pub fn get(&self, handle: Handle) -> Option<&K> {
+        self.index.find_by(|slot| handle.cmp(&slot.handle)).map(|slot| &slot.key)
+    }
+
+    /// Return a heap in which the entry for `handle` has the key `key`, which
+    /// must be no greater than its current key. Returns None if there's no
+    /// such entry, or if `key` is greater.
+    pub fn decrease_key(&self, handle: Handle, key: K) -> Option<HandleHeap<K, P>> {
+        match key.cmp(self.get(handle)?) {
+            Ordering::Greater => None,
+            Ordering::Equal => Some(self.clone()),
+            Ordering::Less => Some(HandleHeap {
+                heap: self.heap.insert(Entry { key: key.clone(), handle }),
+                index: self.index.replace(Slot { handle, key }),
+                next: self.next,
+                len: self.len
+            }.discard_stale())
+        }
+    }
+
+    /// Return a heap without the entry for `handle`, or None if there's no
+    /// such entry.
+    pub fn delete(&self, handle: Handle) -> Option<HandleHeap<K, P>> {
+        Some(HandleHeap {
+            heap: self.heap.clone(),
+            index: self.index.remove_by(|slot| handle.cmp(&slot.handle))?,
+            next: self.next,
+            len: self.len - 1
+        }.discard_stale())
+    }
+
+    // Pop stale entries off the top of the heap, so that `min` is always
+    // current.
+    fn discard_stale(mut self) -> HandleHeap<K, P> {
+        while let Some(entry) = self.heap.min() {
+            if self.get(entry.handle) == Some(&entry.key) {
+                break;
+            }
+            self.heap = self.heap.without_min();
+        }
+        self
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, P: SharedPointerKind> fmt::Debug for HandleHeap<K, P> {
+    /// Entries are shown in sorted order, as `(key, handle)` pairs.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("HandleHeap")?;
+        let mut entries = f.debug_list();
+        let mut h = self.clone();
+        while let Some((key, handle)) = h.min() {
+            entries.entry(&(key, handle));
+            h = h.without_min();
+        }
+        entries.finish()
+    }
+}
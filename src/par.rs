@@ -0,0 +1,152 @@
+//! Parallel bulk construction, enabled by the `rayon` feature.
+//!
+//! The `Arc`-based sets implement rayon's `FromParallelIterator` and
+//! `ParallelExtend`. Rather than inserting values one at a time, these sort
+//! all the new values in parallel, then build the tree bottom-up, splitting
+//! the sorted values in half and building the two halves on different
+//! threads. Building a tree of n values this way takes O(n log n) work, like
+//! repeated insertion, but nearly all of it can run in parallel.
+//!
+//! When the values are already sorted, `RBTree::from_sorted_slice_parallel`
+//! skips the sort, and the build takes only O(n) work.
+//!
+//! Only the `Arc` flavor can be built in parallel, since `Rc` nodes can't be
+//! sent between threads. Use `to_local()` on the result if you need `Rc`.
+
+use std::cmp::Ordering::*;
+use std::sync::Arc;
+use rayon;
+use rayon::prelude::*;
+use compare::{Compare, Natural};
+use pointer::ArcKind;
+use rbtree::{self, RBTree};
+use tree::{cons_tree, Tree};
+
+// Ranges smaller than this are built on the current thread.
+const SEQUENTIAL_THRESHOLD: usize = 4096;
+
+// Collect and sort the values, keeping only the first of each run of equal
+// values, as repeated insertion would.
+fn sorted_unique<V, C, I>(iter: I) -> Vec<Arc<V>>
+    where V: Send + Sync, C: Compare<V>, I: IntoParallelIterator<Item=V>
+{
+    let mut values: Vec<V> = iter.into_par_iter().collect();
+    values.par_sort_by(C::compare);  // stable, so the first of equal values stays first
+    values.dedup_by(|later, earlier| C::compare(later, earlier) == Equal);
+    values.into_par_iter().map(Arc::new).collect()
+}
+
+// Merge sorted new values into the sorted values already in a set. Where a
+// value is already present, the old one is kept.
+fn merge_unique<V, C: Compare<V>>(old: Vec<Arc<V>>, new: Vec<Arc<V>>) -> Vec<Arc<V>> {
+    let mut out = Vec::with_capacity(old.len() + new.len());
+    let mut old = old.into_iter().peekable();
+    let mut new = new.into_iter().peekable();
+    loop {
+        let order = match (old.peek(), new.peek()) {
+            (Some(a), Some(b)) => C::compare(a, b),
+            (Some(_), None) => Less,
+            (None, Some(_)) => Greater,
+            (None, None) => return out
+        };
+        match order {
+            Less => out.extend(old.next()),
+            Greater => out.extend(new.next()),
+            Equal => {
+                out.extend(old.next());
+                new.next();
+            }
+        }
+    }
+}
+
+// Run `a` and `b`, in parallel if there's enough work to be worth it.
+fn join<A, B, RA, RB>(len: usize, a: A, b: B) -> (RA, RB)
+    where A: FnOnce() -> RA + Send, B: FnOnce() -> RB + Send, RA: Send, RB: Send
+{
+    if len < SEQUENTIAL_THRESHOLD {
+        (a(), b())
+    } else {
+        rayon::join(a, b)
+    }
+}
+
+fn build_rbtree<V: Send + Sync, C>(values: &[Arc<V>], depth: usize, red_depth: usize) -> RBTree<V, ArcKind, C> {
+    if values.is_empty() {
+        return RBTree::default();
+    }
+    let mid = values.len() / 2;
+    let (left, right) = join(values.len(),
+                             || build_rbtree(&values[..mid], depth + 1, red_depth),
+                             || build_rbtree(&values[mid + 1..], depth + 1, red_depth));
+    rbtree::sorted_node(depth, red_depth, values[mid].clone(), left, right)
+}
+
+fn build_tree<V: Send + Sync>(values: &[Arc<V>]) -> Tree<V, ArcKind> {
+    if values.is_empty() {
+        return Tree::default();
+    }
+    let mid = values.len() / 2;
+    let (left, right) = join(values.len(),
+                             || build_tree(&values[..mid]),
+                             || build_tree(&values[mid + 1..]));
+    cons_tree(values[mid].clone(), left, right)
+}
+
+fn rbtree_from_sorted<V: Send + Sync, C>(values: &[Arc<V>]) -> RBTree<V, ArcKind, C> {
+    build_rbtree(values, 0, rbtree::red_depth(values.len()))
+}
+
+impl<V: Clone + Send + Sync, C: Compare<V>> RBTree<V, ArcKind, C> {
+    /// Build a tree from values already sorted by `C`, cloning each one.
+    /// Where neighbors are equal, the first is kept, as repeated insertion
+    /// would.
+    ///
+    /// This skips the sort that `from_par_iter` does. The tree is built
+    /// from the middle out, with the halves of each range built on
+    /// different threads, so building from n values takes O(n) work and
+    /// scales with the number of cores. Every level is full except possibly
+    /// the last, whose nodes are red, so the black height is the same on
+    /// every path without any rebalancing.
+    ///
+    /// If `values` isn't sorted, the result isn't a valid search tree. With
+    /// the `strict-checks` feature, the result is validated.
+    ///
+    pub fn from_sorted_slice_parallel(values: &[V]) -> RBTree<V, ArcKind, C> {
+        let mut values: Vec<Arc<V>> = values.par_iter().map(|v| Arc::new(v.clone())).collect();
+        values.dedup_by(|later, earlier| C::compare(later, earlier) == Equal);
+        let result = rbtree_from_sorted(&values);
+        strict_check!(result);
+        result
+    }
+}
+
+impl<V: Send + Sync, C: Compare<V>> FromParallelIterator<V> for RBTree<V, ArcKind, C> {
+    fn from_par_iter<I: IntoParallelIterator<Item=V>>(par_iter: I) -> RBTree<V, ArcKind, C> {
+        rbtree_from_sorted(&sorted_unique::<V, C, I>(par_iter))
+    }
+}
+
+impl<V: Send + Sync, C: Compare<V>> ParallelExtend<V> for RBTree<V, ArcKind, C> {
+    fn par_extend<I: IntoParallelIterator<Item=V>>(&mut self, par_iter: I) {
+        let new = sorted_unique::<V, C, I>(par_iter);
+        let mut old = vec![];
+        self.copy_pointers_to_vec(&mut old);
+        *self = rbtree_from_sorted(&merge_unique::<V, C>(old, new));
+    }
+}
+
+impl<V: Ord + Send + Sync> FromParallelIterator<V> for Tree<V, ArcKind> {
+    fn from_par_iter<I: IntoParallelIterator<Item=V>>(par_iter: I) -> Tree<V, ArcKind> {
+        build_tree(&sorted_unique::<V, Natural, I>(par_iter))
+    }
+}
+
+impl<V: Ord + Send + Sync> ParallelExtend<V> for Tree<V, ArcKind> {
+    fn par_extend<I: IntoParallelIterator<Item=V>>(&mut self, par_iter: I) {
+        let new = sorted_unique::<V, Natural, I>(par_iter);
+        let mut old = vec![];
+        self.copy_pointers_to_vec(&mut old);
+        *self = build_tree(&merge_unique::<V, Natural>(old, new));
+    }
+}
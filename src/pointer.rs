@@ -0,0 +1,66 @@
+//! Abstraction over the reference-counted pointer used to share nodes.
+//!
+//! Every data structure in this crate takes a pointer kind as its last type
+//! parameter. It defaults to `RcKind`, which is fast but not thread-safe. Use
+//! `ArcKind` (or the type aliases in the `sync` module) for values that need
+//! to be sent to other threads.
+//!
+//! Rust doesn't use default type parameters when inferring types, so a bare
+//! `RBTree::empty()` is ambiguous. Each type has a `new()` constructor for the
+//! `Rc` flavor, which sidesteps the problem the same way `HashMap::new()` does.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A family of shared pointer types, such as `Rc` or `Arc`.
+pub trait SharedPointerKind {
+    /// The pointer type itself.
+    type Pointer<T>: Clone + Deref<Target = T>;
+
+    /// Allocate a new node and return a pointer to it.
+    fn new<T>(value: T) -> Self::Pointer<T>;
+
+    /// Return true if `a` and `b` point to the same allocation.
+    fn ptr_eq<T>(a: &Self::Pointer<T>, b: &Self::Pointer<T>) -> bool;
+
+    /// Return the value `ptr` points to, if `ptr` is the only pointer to it.
+    /// Otherwise, return `ptr` unchanged.
+    fn try_unwrap<T>(ptr: Self::Pointer<T>) -> Result<T, Self::Pointer<T>>;
+
+    /// Return a mutable reference to the value `ptr` points to, if `ptr` is
+    /// the only pointer to it.
+    fn get_mut<T>(ptr: &mut Self::Pointer<T>) -> Option<&mut T>;
+}
+
+/// Share nodes using `Rc`. This is the default.
+#[derive(Debug)]
+pub enum RcKind {}
+
+/// Share nodes using `Arc`.
+#[derive(Debug)]
+pub enum ArcKind {}
+
+impl SharedPointerKind for RcKind {
+    type Pointer<T> = Rc<T>;
+
+    fn new<T>(value: T) -> Rc<T> { Rc::new(value) }
+
+    fn ptr_eq<T>(a: &Rc<T>, b: &Rc<T>) -> bool { Rc::ptr_eq(a, b) }
+
+    fn try_unwrap<T>(ptr: Rc<T>) -> Result<T, Rc<T>> { Rc::try_unwrap(ptr) }
+
+    fn get_mut<T>(ptr: &mut Rc<T>) -> Option<&mut T> { Rc::get_mut(ptr) }
+}
+
+impl SharedPointerKind for ArcKind {
+    type Pointer<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> { Arc::new(value) }
+
+    fn ptr_eq<T>(a: &Arc<T>, b: &Arc<T>) -> bool { Arc::ptr_eq(a, b) }
+
+    fn try_unwrap<T>(ptr: Arc<T>) -> Result<T, Arc<T>> { Arc::try_unwrap(ptr) }
+
+    fn get_mut<T>(ptr: &mut Arc<T>) -> Option<&mut T> { Arc::get_mut(ptr) }
+}
@@ -0,0 +1,27 @@
+//! The traits and collection types most programs need, in one place.
+//!
+//! ```ignore
+//! use fundata::prelude::*;
+//!
+//! let list = List::cons(1, List::new());
+//! let set = RBTree::new().plus(1).plus(2);
+//! assert!(set.contains(list.head().unwrap()));
+//! ```
+//!
+//! The traits are what make `head`, `plus` and the rest callable as methods,
+//! so a glob import of this module is usually all it takes.
+
+pub use compare::{Compare, Natural};
+pub use pointer::{SharedPointerKind, RcKind, ArcKind};
+pub use traits::{Stack, Queue, Deque, Set, Heap, Sortable, Nested};
+
+pub use hashset::HashSet;
+pub use heap::LeftistHeap;
+pub use intmap::IntMap;
+pub use list::List;
+pub use pairing::PairingHeap;
+pub use queue::BatchedQueue;
+pub use rbmap::RBMap;
+pub use rbtree::RBTree;
+pub use stream::Stream;
+pub use tree::Tree;
@@ -0,0 +1,44 @@
+// Rendering the shape of a binary tree as indented text, for `pretty()`.
+//
+//     4 black
+//     ├─ 2 red
+//     │  ├─ 1 black
+//     │  └─ 3 black
+//     └─ 5 black
+//
+// When a node has only one child, the missing one is shown as `·` so that left
+// and right can be told apart.
+
+pub(crate) trait Shape: Sized {
+    // Return None if this tree is empty; otherwise the label for the root node
+    // and its left and right subtrees.
+    fn node(&self) -> Option<(String, &Self, &Self)>;
+}
+
+// Trees can be as deep as they are large, so this keeps its own stack of
+// lines still to write rather than recursing. Each entry is the text that
+// starts the line, the indent for the node's children, and the node.
+pub(crate) fn pretty<T: Shape>(tree: &T) -> String {
+    let mut out = String::new();
+    let mut stack = vec![(String::new(), String::new(), tree.node())];
+    while let Some((start, indent, node)) = stack.pop() {
+        out.push_str(&start);
+        let (label, left, right) = match node {
+            None => {
+                out.push_str("·\n");
+                continue;
+            }
+            Some(node) => node
+        };
+        out.push_str(&label);
+        out.push('\n');
+        let left = left.node();
+        let right = right.node();
+        if left.is_none() && right.is_none() {
+            continue;
+        }
+        stack.push((format!("{}└─ ", indent), format!("{}   ", indent), right));
+        stack.push((format!("{}├─ ", indent), format!("{}│  ", indent), left));
+    }
+    out
+}
@@ -1,24 +1,37 @@
 // queue.rs - What you sing before "T, U, V"
 
-use std::mem::swap;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem::{self, swap};
+use display::{write_separated, DisplayWith};
+use error::Error;
+use pointer::{SharedPointerKind, RcKind, ArcKind};
+use sharing;
+use stats::QueueStats;
 use traits::{Queue, Stack};
+use validate::{InvariantError, Violation};
 use list::List;
 use list::List::Nil;
 use list::reverse;
 
+// The lists hold pointers to the items, rather than the items themselves, so
+// that reversing `back` copies pointers and never clones an item.
+type Items<T, P> = List<<P as SharedPointerKind>::Pointer<T>, P>;
+
 /// A persistent queue implemented as a pair of linked lists.
-pub struct BatchedQueue<T> {
+pub struct BatchedQueue<T, P: SharedPointerKind = RcKind> {
     // We have an invariant that if front is empty, then back is empty.
     // Or equivalently: it's never true that the front is empty and the back isn't.
-    front: List<T>,
-    back: List<T>
+    front: Items<T, P>,
+    back: Items<T, P>
 }
 
 // `derive(Clone)` is not smart enough to derive this instance, so we have to
 // write it out. (Rust instead derives the more restricted `impl<T: Clone> Clone
 // for BatchedQueue<T>`.)
-impl<T> Clone for BatchedQueue<T> {
-    fn clone(&self) -> BatchedQueue<T> {
+impl<T, P: SharedPointerKind> Clone for BatchedQueue<T, P> {
+    fn clone(&self) -> BatchedQueue<T, P> {
         BatchedQueue {
             front: self.front.clone(),
             back: self.back.clone()
@@ -26,29 +39,86 @@ impl<T> Clone for BatchedQueue<T> {
     }
 }
 
-impl<T: Clone> BatchedQueue<T> {
+impl<T> BatchedQueue<T> {
+    /// Return an empty queue that shares nodes using `Rc`.
+    pub fn new() -> BatchedQueue<T> {
+        BatchedQueue { front: Nil, back: Nil }
+    }
+}
+
+impl<T, P: SharedPointerKind> Default for BatchedQueue<T, P> {
+    fn default() -> BatchedQueue<T, P> {
+        BatchedQueue { front: Nil, back: Nil }
+    }
+}
+
+impl<T, P: SharedPointerKind> BatchedQueue<T, P> {
+    // Build a queue with exactly the given layout: `front` are the first
+    // items and `back` the rest, both in queue order. `front` must not be
+    // empty unless `back` is too.
+    pub(crate) fn from_parts(front: Vec<T>, back: Vec<T>) -> BatchedQueue<T, P> {
+        let mut queue = BatchedQueue { front: Nil, back: Nil };
+        for v in front.into_iter().rev() {
+            queue.front = List::cons(P::new(v), queue.front);
+        }
+        for v in back {
+            queue.back = List::cons(P::new(v), queue.back);
+        }
+        queue
+    }
+
     // Build a queue from components, moving items from back to front if needed
     // to preserve the invariant.
-    fn build(front: List<T>, back: List<T>) -> BatchedQueue<T> {
-        if front.is_empty() {
+    fn build(front: Items<T, P>, back: Items<T, P>) -> BatchedQueue<T, P> {
+        let result = if front.is_empty() {
             BatchedQueue {
                 front: reverse(back),
                 back: Nil
             }
         } else {
             BatchedQueue {
-                front: front,
-                back: back
+                front,
+                back
             }
-        }
+        };
+        strict_check!(result);
+        result
+    }
+
+    // Return the items in queue order. List nodes and items that nothing else
+    // shares are taken apart and moved out; shared ones are cloned.
+    pub(crate) fn into_items(self) -> Vec<T>
+        where T: Clone
+    {
+        let mut items = vec![];
+        take_items(self.front, &mut items);
+        let start = items.len();
+        take_items(self.back, &mut items);
+        items[start..].reverse();
+        items
     }
 }
 
-impl<T: Clone> Queue for BatchedQueue<T> {
+fn take_items<T: Clone, P: SharedPointerKind>(mut list: Items<T, P>, out: &mut Vec<T>) {
+    while let List::Cons(ref rc) = list {
+        // Let go of this reference to the node, so that `try_unwrap` can
+        // succeed if it was the only one.
+        let rc = rc.clone();
+        drop(mem::replace(&mut list, Nil));
+        let (item, rest) = match P::try_unwrap(rc) {
+            Ok(node) => node,
+            Err(rc) => (*rc).clone()
+        };
+        out.push(P::try_unwrap(item).unwrap_or_else(|item| (*item).clone()));
+        list = rest;
+    }
+}
+
+impl<T, P: SharedPointerKind> Queue for BatchedQueue<T, P> {
     type Item = T;
 
     /// Return an empty BatchedQueue.
-    fn empty() -> BatchedQueue<T> {
+    fn empty() -> BatchedQueue<T, P> {
         BatchedQueue { front: Nil, back: Nil }
     }
 
@@ -61,26 +131,28 @@ impl<T: Clone> Queue for BatchedQueue<T> {
 
     /// Add a queue and an item.
     ///
-    /// This returns a new queue with all the items in `queue`, plus a new item
-    /// `value` added at the back.
+    /// This returns a new queue with all the items in this queue, plus a new
+    /// item `value` added at the back.
     ///
     /// This runs in constant time and space. (It does not make a copy of
-    /// the items in `queue`.)
+    /// the items in this queue.)
     ///
-    fn snoc(queue: BatchedQueue<T>, value: T) -> BatchedQueue<T> {
-        if queue.is_empty() {
+    fn snoc(self, value: T) -> BatchedQueue<T, P> {
+        let result = if self.is_empty() {
             // Separate implementation in order to maintain the invariant.
             BatchedQueue {
-                front: List::cons(value, Nil),
+                front: List::cons(P::new(value), Nil),
                 back: Nil
             }
         } else {
-            let BatchedQueue { front, back } = queue;
+            let BatchedQueue { front, back } = self;
             BatchedQueue {
-                front: front,
-                back: List::cons(value, back)
+                front,
+                back: List::cons(P::new(value), back)
             }
-        }
+        };
+        strict_check!(result);
+        result
     }
 
     /// Split this queue into two parts: the item at the front and another
@@ -92,11 +164,11 @@ impl<T: Clone> Queue for BatchedQueue<T> {
     /// but over many `split` calls, the average time and space used is a
     /// low amount that doesn't increase as the size of the queue increases.
     ///
-    fn split(&self) -> Option<(&T, BatchedQueue<T>)> {
+    fn split(&self) -> Option<(&T, BatchedQueue<T, P>)> {
         match self.front.split() {
             None => None,
             Some((first, rest)) =>
-                Some((first, BatchedQueue::build((*rest).clone(), self.back.clone())))
+                Some((&**first, BatchedQueue::build((*rest).clone(), self.back.clone())))
         }
     }
 
@@ -108,11 +180,219 @@ impl<T: Clone> Queue for BatchedQueue<T> {
     /// This runs in constant time and space.
     ///
     fn head(&self) -> Option<&T> {
-        self.front.head()
+        self.front.head().map(|p| &**p)
+    }
+
+    /// Divide this queue into the first `n` items and the rest, without
+    /// cloning any items. See the inherent method `BatchedQueue::split_at`.
+    ///
+    fn split_at(self, n: usize) -> (BatchedQueue<T, P>, BatchedQueue<T, P>)
+        where T: Clone
+    {
+        BatchedQueue::split_at(&self, n)
     }
 }
 
-impl<T: Clone> BatchedQueue<T> {
+impl<T, P: SharedPointerKind> FromIterator<T> for BatchedQueue<T, P> {
+    fn from_iter<Iterable: IntoIterator<Item=T>>(iterator: Iterable) -> BatchedQueue<T, P> {
+        BatchedQueue::from_parts(iterator.into_iter().collect(), vec![])
+    }
+}
+
+impl<T, P: SharedPointerKind> Extend<T> for BatchedQueue<T, P> {
+    /// Add all the items to the back of the queue, in order.
+    ///
+    /// This builds one list. If the queue is empty, that list becomes the
+    /// front; otherwise the items are pushed onto the back list, just as
+    /// `snoc` would, but without rechecking the invariant for each one.
+    ///
+    fn extend<Iterable: IntoIterator<Item=T>>(&mut self, iterator: Iterable) {
+        if self.is_empty() {
+            *self = iterator.into_iter().collect();
+        } else {
+            for v in iterator {
+                self.back = List::cons(P::new(v), mem::replace(&mut self.back, Nil));
+            }
+        }
+        strict_check!(self);
+    }
+}
+
+
+impl<T, P: SharedPointerKind> BatchedQueue<T, P> {
+    /// Return true if `self` and `other` are the same version of the queue:
+    /// both lists are the same (or both empty). This takes constant time. If
+    /// it returns true, the queues are certainly equal; if not, they may still
+    /// be equal, and `==` will say.
+    pub fn same(&self, other: &BatchedQueue<T, P>) -> bool {
+        self.front.same(&other.front) && self.back.same(&other.back)
+    }
+
+    /// Return true if `self` and `other` have any list nodes in common, as
+    /// when one was made from the other. This takes time proportional to the
+    /// number of items in both.
+    ///
+    pub fn shares_structure_with(&self, other: &BatchedQueue<T, P>) -> bool {
+        sharing::shares_structure(&[&self.front, &self.back], &[&other.front, &other.back])
+    }
+
+    /// Return the number of list nodes `self` and `other` have in common.
+    /// This takes time proportional to the number of items in both.
+    pub fn shared_node_count(&self, other: &BatchedQueue<T, P>) -> usize {
+        sharing::shared_count(&[&self.front, &self.back], &[&other.front, &other.back])
+    }
+
+    /// Return the number of list nodes in `self` that are not also in
+    /// `other`: how much memory keeping `self` costs, on top of keeping
+    /// `other`.
+    pub fn unique_node_count(&self, other: &BatchedQueue<T, P>) -> usize {
+        sharing::unique_count(&[&self.front, &self.back], &[&other.front, &other.back])
+    }
+
+    /// Return how many items are in each of this queue's two lists. See the
+    /// `stats` module.
+    pub fn stats(&self) -> QueueStats {
+        QueueStats {
+            front: self.front.length(),
+            back: self.back.length()
+        }
+    }
+
+    /// Check that the front list is nonempty unless the whole queue is empty.
+    /// See the `validate` module.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        if self.front.is_empty() && !self.back.is_empty() {
+            Err(InvariantError::new(Violation::EmptyFront, ""))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Make a list of the given items, in the same order.
+fn items_from_vec<T, P: SharedPointerKind>(items: Vec<P::Pointer<T>>) -> Items<T, P> {
+    let mut list = Nil;
+    for p in items.into_iter().rev() {
+        list = List::cons(p, list);
+    }
+    list
+}
+
+impl<T, P: SharedPointerKind> BatchedQueue<T, P> {
+    /// Return a reference to the item `index` places from the front of this
+    /// queue (so `get(0)` is the head), or None if the queue isn't that long.
+    ///
+    /// This doesn't change the queue. It takes time proportional to `index`
+    /// if the item is in the queue's front list, and otherwise to the length
+    /// of the queue.
+    ///
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let mut p = &self.front;
+        let mut i = index;
+        while let Some((first, rest)) = p.split() {
+            if i == 0 {
+                return Some(&**first);
+            }
+            i -= 1;
+            p = rest;
+        }
+
+        // The item is the `i`th in the back list counting from the oldest,
+        // but the list is newest first.
+        let back_len = self.back.length();
+        if i >= back_len {
+            return None;
+        }
+        let mut p = &self.back;
+        for _ in 0..back_len - 1 - i {
+            p = p.tail()?;
+        }
+        p.head().map(|first| &**first)
+    }
+
+    /// Like `get`, but with an error saying how long the queue is if it
+    /// isn't long enough. Finding the length takes time proportional to it.
+    pub fn try_get(&self, index: usize) -> Result<&T, Error> {
+        self.get(index).ok_or_else(|| {
+            Error::IndexOutOfBounds { index, len: self.front.length() + self.back.length() }
+        })
+    }
+
+    /// Like `Queue::split`, but with an error if the queue is empty.
+    pub fn try_split(&self) -> Result<(&T, BatchedQueue<T, P>), Error> {
+        Queue::split(self).ok_or(Error::Empty)
+    }
+
+    /// Return two queues: the first `index` items of this queue, and the
+    /// rest. If the queue has `index` items or fewer, the second queue is
+    /// empty.
+    ///
+    /// This never clones an item. If the split point is in the front list,
+    /// the nodes before it are copied and the rest are shared; if it's in the
+    /// back list, the older back nodes are shared and the newer ones copied.
+    ///
+    pub fn split_at(&self, index: usize) -> (BatchedQueue<T, P>, BatchedQueue<T, P>) {
+        let mut prefix = vec![];
+        let mut p = &self.front;
+        while prefix.len() < index {
+            match p.split() {
+                None => break,
+                Some((first, rest)) => {
+                    prefix.push(first.clone());
+                    p = rest;
+                }
+            }
+        }
+        if prefix.len() == index {
+            return (BatchedQueue::build(items_from_vec::<T, P>(prefix), Nil),
+                    BatchedQueue::build(p.clone(), self.back.clone()));
+        }
+
+        // The first part is all of `front` plus the `k` oldest items of
+        // `back`, which are the last `k` nodes of the list.
+        let k = index - prefix.len();
+        let back_len = self.back.length();
+        if k >= back_len {
+            return (self.clone(), BatchedQueue::empty());
+        }
+        let mut newer = vec![];
+        let mut p = &self.back;
+        for _ in 0..back_len - k {
+            if let Some((first, rest)) = p.split() {
+                newer.push(first.clone());
+                p = rest;
+            }
+        }
+        newer.reverse();
+        (BatchedQueue::build(self.front.clone(), p.clone()),
+         BatchedQueue::build(items_from_vec::<T, P>(newer), Nil))
+    }
+
+    // Return this queue without the item `index` places from the front,
+    // copying pointers rather than items. Takes time proportional to the
+    // length of the queue.
+    pub(crate) fn without_index(&self, index: usize) -> BatchedQueue<T, P> {
+        let mut items = vec![];
+        let mut p = &self.front;
+        while let Some((first, rest)) = p.split() {
+            items.push(first.clone());
+            p = rest;
+        }
+        let front_len = items.len();
+        p = &self.back;
+        while let Some((first, rest)) = p.split() {
+            items.push(first.clone());
+            p = rest;
+        }
+        items[front_len..].reverse();
+        if index < items.len() {
+            items.remove(index);
+        }
+        BatchedQueue::build(items_from_vec::<T, P>(items), Nil)
+    }
+}
+
+impl<T: Clone, P: SharedPointerKind> BatchedQueue<T, P> {
     /// Break this queue into two parts: the item at the front and another
     /// queue containing everything else. If the queue is empty, this returns
     /// None.
@@ -122,16 +402,19 @@ impl<T: Clone> BatchedQueue<T> {
     /// but over many `split_into` calls, the average time and space used is a
     /// low amount that doesn't increase as the size of the queue increases.
     ///
-    pub fn split_into(self) -> Option<(T, BatchedQueue<T>)> {
+    /// The front item is cloned, since other queues may still share it. Use
+    /// `split` to get a reference to it instead.
+    ///
+    pub fn split_into(self) -> Option<(T, BatchedQueue<T, P>)> {
         match self.front.split_into() {
             None =>
                 match reverse(self.back).split_into() {
                     None => None,
                     Some((first, rest)) =>
-                        Some((first, BatchedQueue { front: rest, back: Nil }))
+                        Some(((*first).clone(), BatchedQueue { front: rest, back: Nil }))
                 },
             Some((first, rest)) =>
-                Some((first, BatchedQueue { front: rest, back: self.back }))
+                Some(((*first).clone(), BatchedQueue::build(rest, self.back)))
         }
     }
 
@@ -156,3 +439,156 @@ impl<T: Clone> BatchedQueue<T> {
         }
     }
 }
+
+// Copy a list of item pointers into a list of pointers of another kind.
+fn convert_items<T: Clone, P, Q>(items: &Items<T, P>) -> Items<T, Q>
+    where P: SharedPointerKind, Q: SharedPointerKind
+{
+    let mut stack = vec![];
+    let mut p = items;
+    while let Some((first, rest)) = p.split() {
+        stack.push(first);
+        p = rest;
+    }
+    let mut result = Nil;
+    for v in stack.into_iter().rev() {
+        result = List::cons(Q::new((**v).clone()), result);
+    }
+    result
+}
+
+impl<T: Clone, P: SharedPointerKind> BatchedQueue<T, P> {
+    /// Return a copy of this queue that shares nodes using pointer kind `Q`.
+    ///
+    /// This copies every node and clones every item once. The front/back
+    /// split is preserved, so no reversal is done.
+    ///
+    pub fn to_pointer_kind<Q: SharedPointerKind>(&self) -> BatchedQueue<T, Q> {
+        BatchedQueue {
+            front: convert_items::<T, P, Q>(&self.front),
+            back: convert_items::<T, P, Q>(&self.back)
+        }
+    }
+}
+
+impl<T: Clone> BatchedQueue<T> {
+    /// Return a thread-safe copy of this queue.
+    pub fn to_shared(&self) -> BatchedQueue<T, ArcKind> { self.to_pointer_kind() }
+}
+
+impl<T: Clone> BatchedQueue<T, ArcKind> {
+    /// Return a copy of this queue that uses `Rc` to share nodes.
+    pub fn to_local(&self) -> BatchedQueue<T> { self.to_pointer_kind() }
+}
+
+impl<T, P: SharedPointerKind> BatchedQueue<T, P> {
+    // Return references to all the items, front first.
+    pub(crate) fn item_refs(&self) -> Vec<&T> {
+        let mut items = vec![];
+        let mut p = &self.front;
+        while let Some((first, rest)) = p.split() {
+            items.push(&**first);
+            p = rest;
+        }
+        let front_len = items.len();
+        p = &self.back;
+        while let Some((first, rest)) = p.split() {
+            items.push(&**first);
+            p = rest;
+        }
+        items[front_len..].reverse();
+        items
+    }
+
+    /// Combine the items, front first, using `f`, stopping at the first error
+    /// and returning it.
+    pub fn try_fold<A, E, F>(&self, init: A, f: F) -> Result<A, E>
+        where F: FnMut(A, &T) -> Result<A, E>
+    {
+        self.item_refs().into_iter().try_fold(init, f)
+    }
+
+    /// Return a queue of the results of calling `f` on each item, front
+    /// first, or the first error `f` returns.
+    pub fn try_map<U, E, F>(&self, f: F) -> Result<BatchedQueue<U, P>, E>
+        where F: FnMut(&T) -> Result<U, E>
+    {
+        let mapped = self.item_refs().into_iter().map(f).collect::<Result<Vec<U>, E>>()?;
+        Ok(BatchedQueue::from_parts(mapped, vec![]))
+    }
+
+    /// Return a queue of the results of calling `f` on each item, in the
+    /// same order. `f` is called on the items front first.
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> BatchedQueue<U, P> {
+        BatchedQueue::from_parts(self.item_refs().into_iter().map(f).collect(), vec![])
+    }
+
+    /// Return a queue of the items that satisfy `pred`, in the same order.
+    /// The items are shared with this queue, not cloned.
+    pub fn filter<F: FnMut(&T) -> bool>(&self, mut pred: F) -> BatchedQueue<T, P> {
+        // Like `item_refs`, but keeping the pointers.
+        let mut kept = vec![];
+        let mut p = &self.front;
+        while let Some((first, rest)) = p.split() {
+            kept.push(first);
+            p = rest;
+        }
+        let front_len = kept.len();
+        p = &self.back;
+        while let Some((first, rest)) = p.split() {
+            kept.push(first);
+            p = rest;
+        }
+        kept[front_len..].reverse();
+        kept.retain(|item| pred(item));
+        let front = kept.into_iter().rev().fold(Nil, |list, item| List::cons(item.clone(), list));
+        BatchedQueue { front, back: Nil }
+    }
+
+    /// Return an object that formats this queue's items with `Display`, front
+    /// first, separated by `sep`.
+    pub fn display_with<'a>(&'a self, sep: &'a str) -> DisplayWith<'a, BatchedQueue<T, P>> {
+        DisplayWith { seq: self, sep }
+    }
+}
+
+impl<T: fmt::Debug, P: SharedPointerKind> fmt::Debug for BatchedQueue<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("BatchedQueue")?;
+        f.debug_list().entries(self.item_refs()).finish()
+    }
+}
+
+impl<'a, T: fmt::Display, P: SharedPointerKind> fmt::Display for DisplayWith<'a, BatchedQueue<T, P>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_separated(f, self.seq.item_refs(), self.sep)
+    }
+}
+
+impl<T: fmt::Display, P: SharedPointerKind> fmt::Display for BatchedQueue<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.display_with(", "), f)
+    }
+}
+
+// Queues are compared item by item, front first, like slices. How the items
+// happen to be divided between `front` and `back` doesn't matter.
+impl<T: PartialEq, P: SharedPointerKind> PartialEq for BatchedQueue<T, P> {
+    fn eq(&self, other: &BatchedQueue<T, P>) -> bool {
+        self.item_refs() == other.item_refs()
+    }
+}
+
+impl<T: Eq, P: SharedPointerKind> Eq for BatchedQueue<T, P> {}
+
+impl<T: PartialOrd, P: SharedPointerKind> PartialOrd for BatchedQueue<T, P> {
+    fn partial_cmp(&self, other: &BatchedQueue<T, P>) -> Option<Ordering> {
+        self.item_refs().partial_cmp(&other.item_refs())
+    }
+}
+
+impl<T: Ord, P: SharedPointerKind> Ord for BatchedQueue<T, P> {
+    fn cmp(&self, other: &BatchedQueue<T, P>) -> Ordering {
+        self.item_refs().cmp(&other.item_refs())
+    }
+}
@@ -0,0 +1,99 @@
+//! `quickcheck::Arbitrary` implementations, enabled by the `quickcheck` feature.
+
+use quickcheck::{Arbitrary, Gen};
+use arbitrary::{heap_from_vec, queue_from_vecs, rbtree_from_vec, tree_from_vec};
+use compare::Compare;
+use heap::LeftistHeap;
+use list::List;
+use pointer::SharedPointerKind;
+use queue::BatchedQueue;
+use rbtree::RBTree;
+use stream::Stream;
+use traits::{Heap, Queue};
+use tree::Tree;
+
+impl<V: Arbitrary, P: SharedPointerKind + 'static> Arbitrary for List<V, P> {
+    fn arbitrary(g: &mut Gen) -> List<V, P> {
+        Vec::<V>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=List<V, P>>> {
+        let items: Vec<V> = self.clone().into_iter().collect();
+        Box::new(items.shrink().map(|v| v.into_iter().collect()))
+    }
+}
+
+impl<V: Arbitrary + Ord, P: SharedPointerKind + 'static> Arbitrary for Tree<V, P> {
+    fn arbitrary(g: &mut Gen) -> Tree<V, P> {
+        // Inserting in random order gives a variety of shapes.
+        tree_from_vec(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=Tree<V, P>>> {
+        let items: Vec<V> = self.iter().cloned().collect();
+        Box::new(items.shrink().map(tree_from_vec))
+    }
+}
+
+impl<V, P, C> Arbitrary for RBTree<V, P, C>
+    where V: Arbitrary, P: SharedPointerKind + 'static, C: Compare<V> + 'static
+{
+    fn arbitrary(g: &mut Gen) -> RBTree<V, P, C> {
+        rbtree_from_vec(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=RBTree<V, P, C>>> {
+        let items: Vec<V> = self.clone().into_iter().collect();
+        Box::new(items.shrink().map(rbtree_from_vec))
+    }
+}
+
+fn heap_items<V: Ord + Clone, P: SharedPointerKind>(h: &LeftistHeap<V, P>) -> Vec<V> {
+    let mut items = vec![];
+    let mut h = h.clone();
+    while let Some(v) = h.pop() {
+        items.push(v);
+    }
+    items
+}
+
+impl<V: Arbitrary + Ord, P: SharedPointerKind + 'static> Arbitrary for LeftistHeap<V, P> {
+    fn arbitrary(g: &mut Gen) -> LeftistHeap<V, P> {
+        heap_from_vec(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=LeftistHeap<V, P>>> {
+        Box::new(heap_items(self).shrink().map(heap_from_vec))
+    }
+}
+
+fn queue_items<T: Clone, P: SharedPointerKind>(q: &BatchedQueue<T, P>) -> Vec<T> {
+    let mut items = vec![];
+    let mut q = q.clone();
+    while let Some((first, rest)) = q.split() {
+        items.push(first.clone());
+        q = rest;
+    }
+    items
+}
+
+impl<T: Arbitrary, P: SharedPointerKind + 'static> Arbitrary for BatchedQueue<T, P> {
+    fn arbitrary(g: &mut Gen) -> BatchedQueue<T, P> {
+        queue_from_vecs(Vec::arbitrary(g), Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=BatchedQueue<T, P>>> {
+        Box::new(queue_items(self).shrink().map(|items| queue_from_vecs(vec![], items)))
+    }
+}
+
+impl<T: Arbitrary + Send + Sync> Arbitrary for Stream<T> {
+    fn arbitrary(g: &mut Gen) -> Stream<T> {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=Stream<T>>> {
+        let items: Vec<T> = self.iter().cloned().collect();
+        Box::new(items.shrink().map(|v| v.into_iter().collect()))
+    }
+}
@@ -0,0 +1,296 @@
+//! Ordered maps, built on red-black trees.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use compare::Compare;
+use diff::MapDiff;
+use pointer::{SharedPointerKind, RcKind};
+use rbtree::{RBTree, RBTreeIter};
+use traits::Nested;
+
+// A key and its value. Entries are ordered by key alone.
+#[derive(Clone)]
+struct Entry<K, V> {
+    key: K,
+    value: V
+}
+
+// Entries are equal if their keys and values are. That's stricter than
+// `ByKey`, which is what lets `RBTree::diff` tell a changed value from an
+// unchanged one.
+impl<K: PartialEq, V: PartialEq> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Entry<K, V>) -> bool {
+        self.key == other.key && self.value == other.value
+    }
+}
+
+enum ByKey {}
+
+impl<K: Ord, V> Compare<Entry<K, V>> for ByKey {
+    fn compare(a: &Entry<K, V>, b: &Entry<K, V>) -> Ordering {
+        a.key.cmp(&b.key)
+    }
+}
+
+/// A persistent ordered map from keys to values.
+///
+/// This is an `RBTree` of key-value entries ordered by key, so it has the
+/// same balancing, the same O(log n) bounds, and the same sharing between
+/// versions: `insert` and `remove` return a new map and leave the old one
+/// as it was.
+pub struct RBMap<K, V, P: SharedPointerKind = RcKind> {
+    tree: RBTree<Entry<K, V>, P, ByKey>
+}
+
+impl<K, V, P: SharedPointerKind> Clone for RBMap<K, V, P> {
+    fn clone(&self) -> RBMap<K, V, P> {
+        RBMap { tree: self.tree.clone() }
+    }
+}
+
+impl<K: Ord, V> RBMap<K, V> {
+    /// Return an empty map that shares nodes using `Rc`.
+    pub fn new() -> RBMap<K, V> {
+        RBMap::empty()
+    }
+}
+
+impl<K: Ord, V, P: SharedPointerKind> Default for RBMap<K, V, P> {
+    fn default() -> RBMap<K, V, P> {
+        RBMap::empty()
+    }
+}
+
+impl<K: Ord, V, P: SharedPointerKind> RBMap<K, V, P> {
+    /// Return an empty map.
+    pub fn empty() -> RBMap<K, V, P> {
+        RBMap { tree: RBTree::default() }
+    }
+
+    /// The value for `key`, or None if the key isn't in the map.
+    ///
+    /// `key` can be any borrowed form of the keys, as with `BTreeMap`, so a
+    /// map with `String` keys can be searched with a `&str`.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where Q: Ord + ?Sized, K: Borrow<Q>
+    {
+        self.entry(key).map(|e| &e.value)
+    }
+
+    /// Return true if `key` is in the map.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where Q: Ord + ?Sized, K: Borrow<Q>
+    {
+        self.entry(key).is_some()
+    }
+
+    /// Return a map with `key` set to `value`, replacing any old value.
+    pub fn insert(&self, key: K, value: V) -> RBMap<K, V, P> {
+        RBMap { tree: self.tree.replace(Entry { key, value }) }
+    }
+
+    /// Return a map without `key`. If the key isn't in the map, this returns
+    /// a copy of `self`.
+    pub fn remove<Q>(&self, key: &Q) -> RBMap<K, V, P>
+        where Q: Ord + ?Sized, K: Borrow<Q>
+    {
+        match self.tree.remove_by(|e| key.cmp(e.key.borrow())) {
+            Some(tree) => RBMap { tree },
+            None => self.clone()
+        }
+    }
+
+    /// Return a map with the value for `key` replaced by `f(old)`, where
+    /// `old` is the current value or None. If `f` returns None, the key is
+    /// removed.
+    pub fn update<F>(&self, key: K, f: F) -> RBMap<K, V, P>
+        where F: FnOnce(Option<&V>) -> Option<V>
+    {
+        match f(self.get(&key)) {
+            Some(value) => self.insert(key, value),
+            None => self.remove(&key)
+        }
+    }
+
+    /// Update a value in maps nested inside this one. `path` is a key in this
+    /// map, then a key in the map that is its value, and so on, and the last
+    /// key is updated as with `update`. Only the maps along the path are
+    /// rebuilt; everything else is shared with `self`. A key along the way
+    /// that's missing, or whose value isn't a map, gets a new map.
+    ///
+    /// # Panics
+    ///
+    /// If `path` is empty.
+    pub fn update_in<F>(&self, path: &[K], f: F) -> RBMap<K, V, P>
+        where K: Clone, V: Nested<RBMap<K, V, P>>, F: FnOnce(Option<&V>) -> Option<V>
+    {
+        let (key, rest) = path.split_first().expect("update_in: empty path");
+        if rest.is_empty() {
+            return self.update(key.clone(), f);
+        }
+        let child = match self.get(key).and_then(V::as_map) {
+            Some(map) => map.update_in(rest, f),
+            None => RBMap::empty().update_in(rest, f)
+        };
+        self.insert(key.clone(), V::from_map(child))
+    }
+
+    fn entry<Q>(&self, key: &Q) -> Option<&Entry<K, V>>
+        where Q: Ord + ?Sized, K: Borrow<Q>
+    {
+        self.tree.find_by(|e| key.cmp(e.key.borrow()))
+    }
+}
+
+impl<K: Ord, V: PartialEq, P: SharedPointerKind> RBMap<K, V, P> {
+    /// Return the entries added, removed and changed between `old` and `new`,
+    /// skipping over any subtrees they share. See the `diff` module.
+    pub fn diff<'a>(old: &'a RBMap<K, V, P>, new: &'a RBMap<K, V, P>) -> MapDiff<'a, K, V> {
+        let d = RBTree::diff(&old.tree, &new.tree);
+        MapDiff {
+            added: d.added.into_iter().map(|e| (&e.key, &e.value)).collect(),
+            removed: d.removed.into_iter().map(|e| (&e.key, &e.value)).collect(),
+            changed: d.changed.into_iter().map(|(a, b)| (&a.key, &a.value, &b.value)).collect()
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone + PartialEq, P: SharedPointerKind> RBMap<K, V, P> {
+    /// Merge two maps `a` and `b` that were both made from `base`. The result
+    /// is `a` with every insertion, removal and change of value `b` made.
+    ///
+    /// Where `a` and `b` both changed the same key, and not in the same way,
+    /// `conflict(key, in_a, in_b)` decides: `in_a` and `in_b` are the values
+    /// the two maps have for `key`, or None where it was removed. The result
+    /// gets the value `conflict` returns, or leaves the key out if it returns
+    /// None. See `RBTree::merge3`.
+    pub fn merge3<F>(base: &RBMap<K, V, P>, a: &RBMap<K, V, P>, b: &RBMap<K, V, P>, mut conflict: F)
+                     -> RBMap<K, V, P>
+        where F: FnMut(&K, Option<&V>, Option<&V>) -> Option<V>
+    {
+        let tree = RBTree::merge3(&base.tree, &a.tree, &b.tree, |e, x, y| {
+            conflict(&e.key, x.map(|x| &x.value), y.map(|y| &y.value))
+                .map(|value| Entry { key: e.key.clone(), value })
+        });
+        RBMap { tree }
+    }
+}
+
+impl<K, V, P: SharedPointerKind> RBMap<K, V, P> {
+    /// The number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Iterate over the keys and values in the map, in order by key.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        Iter(self.tree.iter())
+    }
+
+    /// Iterate over the keys in the map, in order.
+    pub fn keys(&self) -> Keys<'_, K, V, P> {
+        Keys(self.tree.iter())
+    }
+
+    /// Iterate over the values in the map, in order by key.
+    pub fn values(&self) -> Values<'_, K, V, P> {
+        Values(self.tree.iter())
+    }
+}
+
+/// An iterator over the entries of an `RBMap`, in order by key.
+pub struct Iter<'a, K: 'a, V: 'a, P: SharedPointerKind + 'a = RcKind>(RBTreeIter<'a, Entry<K, V>, P, ByKey>);
+
+impl<'a, K, V, P: SharedPointerKind> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|e| (&e.key, &e.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V, P: SharedPointerKind> ExactSizeIterator for Iter<'a, K, V, P> {}
+
+/// An iterator over the keys of an `RBMap`, in order.
+pub struct Keys<'a, K: 'a, V: 'a, P: SharedPointerKind + 'a = RcKind>(RBTreeIter<'a, Entry<K, V>, P, ByKey>);
+
+impl<'a, K, V, P: SharedPointerKind> Iterator for Keys<'a, K, V, P> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.0.next().map(|e| &e.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V, P: SharedPointerKind> ExactSizeIterator for Keys<'a, K, V, P> {}
+
+/// An iterator over the values of an `RBMap`, in order by key.
+pub struct Values<'a, K: 'a, V: 'a, P: SharedPointerKind + 'a = RcKind>(RBTreeIter<'a, Entry<K, V>, P, ByKey>);
+
+impl<'a, K, V, P: SharedPointerKind> Iterator for Values<'a, K, V, P> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.0.next().map(|e| &e.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V, P: SharedPointerKind> ExactSizeIterator for Values<'a, K, V, P> {}
+
+impl<'a, K, V, P: SharedPointerKind> IntoIterator for &'a RBMap<K, V, P> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P>;
+    fn into_iter(self) -> Iter<'a, K, V, P> {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V, P: SharedPointerKind> FromIterator<(K, V)> for RBMap<K, V, P> {
+    fn from_iter<Iterable: IntoIterator<Item=(K, V)>>(iterator: Iterable) -> RBMap<K, V, P> {
+        let mut map = RBMap::empty();
+        map.extend(iterator);
+        map
+    }
+}
+
+impl<K: Ord, V, P: SharedPointerKind> Extend<(K, V)> for RBMap<K, V, P> {
+    fn extend<Iterable: IntoIterator<Item=(K, V)>>(&mut self, iterator: Iterable) {
+        for (k, v) in iterator {
+            *self = self.insert(k, v);
+        }
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, P: SharedPointerKind> PartialEq for RBMap<K, V, P> {
+    fn eq(&self, other: &RBMap<K, V, P>) -> bool {
+        self.tree.same(&other.tree) || (self.len() == other.len() && self.iter().eq(other.iter()))
+    }
+}
+
+impl<K: Eq, V: Eq, P: SharedPointerKind> Eq for RBMap<K, V, P> {}
+
+impl<K: fmt::Debug, V: fmt::Debug, P: SharedPointerKind> fmt::Debug for RBMap<K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RBMap")?;
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -1,17 +1,48 @@
 /* 3.3 Red-Black Trees */
 
-use std::rc::Rc;
+use std::borrow::Borrow;
+use std::cmp::Ordering::{self, *};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::ops::RangeBounds;
+use std::slice;
+use compare::{Compare, Natural};
+use diff::{self, Diff, SortedTree};
+use edit::{self, Edit};
+use dot;
+use pointer::{SharedPointerKind, RcKind, ArcKind};
+use pretty::{pretty, Shape};
+use sharing::{self, Linked};
+use snapshot::{decoded, DecodeError, Encoder, RBTreeRecord, Snapshot};
+use stats::RBTreeStats;
 use traits::Set;
-use std::cmp::Ordering::*;
+use validate::{InvariantError, Violation};
 
 #[derive(PartialEq, Clone, Copy)]
 enum Color { Red, Black }
 
-struct RBTreeNode<V> {
+// As in `tree`, each value is behind its own pointer, so rebalancing and path
+// copying never clone values.
+struct RBTreeNode<V, P: SharedPointerKind, C> {
     color: Color,
-    value: V,
-    left: RBTree<V>,
-    right: RBTree<V>
+    value: P::Pointer<V>,
+    left: RBTree<V, P, C>,
+    right: RBTree<V, P, C>,
+    // The number of values in this subtree.
+    size: usize,
+    order: PhantomData<fn() -> C>
+}
+
+impl<V, P: SharedPointerKind, C> RBTreeNode<V, P, C> {
+    fn new(color: Color, value: P::Pointer<V>, left: RBTree<V, P, C>, right: RBTree<V, P, C>)
+           -> RBTreeNode<V, P, C> {
+        let size = left.len() + 1 + right.len();
+        RBTreeNode { color, value, left, right, size, order: PhantomData }
+    }
 }
 
 // Implementation note: an RBTreeImpl is either empty or a pointer to a tree
@@ -20,47 +51,187 @@ struct RBTreeNode<V> {
 // wouldn't be necessary if we stored the color bit in the RBTreeImpl, and
 // there are plenty of spare bits here. But I don't think Rust is miserly
 // enough to use those spare bits; I think it would bloat.
-#[derive(Clone)]
-enum RBTreeImpl<V> {
+enum RBTreeImpl<V, P: SharedPointerKind, C> {
     RBEmpty,
-    RBNonEmpty(Rc<RBTreeNode<V>>)
+    RBNonEmpty(P::Pointer<RBTreeNode<V, P, C>>)
 }
 
 /// Red-black balanced binary trees. Use the `Set` methods.
-#[derive(Clone)]
-pub struct RBTree<V>(RBTreeImpl<V>);
+///
+/// The values are kept sorted using the comparator `C`, which by default is
+/// their own `Ord` implementation. See the `compare` module.
+///
+pub struct RBTree<V, P: SharedPointerKind = RcKind, C = Natural>(RBTreeImpl<V, P, C>);
+
+impl<V, P: SharedPointerKind, C> Clone for RBTree<V, P, C> {
+    fn clone(&self) -> RBTree<V, P, C> {
+        match self.0 {
+            RBEmpty => RBTree(RBEmpty),
+            RBNonEmpty(ref rc) => RBTree(RBNonEmpty(rc.clone()))
+        }
+    }
+}
+
+impl<V> RBTree<V> {
+    /// Return an empty tree that shares nodes using `Rc`.
+    pub fn new() -> RBTree<V> { RBTree(RBEmpty) }
+}
+
+impl<V, P: SharedPointerKind, C> Default for RBTree<V, P, C> {
+    fn default() -> RBTree<V, P, C> { RBTree(RBEmpty) }
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// The number of values in the tree. Each node records the size of its
+    /// subtree, so this takes constant time.
+    pub fn len(&self) -> usize {
+        match self.0 {
+            RBEmpty => 0,
+            RBNonEmpty(ref rc) => rc.size
+        }
+    }
+
+    /// Return true if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.0, RBEmpty)
+    }
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return the `k`th smallest value in this tree, counting from 0, or None
+    /// if the tree has `k` values or fewer. `select_nth(len / 2)` is the
+    /// median.
+    ///
+    /// This steers down the tree using the sizes recorded in the nodes, so it
+    /// takes O(log n) time.
+    ///
+    pub fn select_nth(&self, mut k: usize) -> Option<&V> {
+        let mut tree = self;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            let left = rc.left.len();
+            if k < left {
+                tree = &rc.left;
+            } else if k == left {
+                return Some(&rc.value);
+            } else {
+                k -= left + 1;
+                tree = &rc.right;
+            }
+        }
+        None
+    }
+
+    /// Return the number of values, in order, for which `pred` is true,
+    /// assuming it is true of some prefix of the values and false of the
+    /// rest, as with `slice::partition_point`.
+    ///
+    /// Like `select_nth`, this counts using the sizes recorded in the nodes,
+    /// so it takes O(log n) time.
+    ///
+    pub fn partition_point<F: FnMut(&V) -> bool>(&self, mut pred: F) -> usize {
+        let mut tree = self;
+        let mut index = 0;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            if pred(&rc.value) {
+                index += rc.left.len() + 1;
+                tree = &rc.right;
+            } else {
+                tree = &rc.left;
+            }
+        }
+        index
+    }
+
+    /// Search for a value using `f`, which says how a value compares to the
+    /// one being sought, as with `slice::binary_search_by`. Returns the index
+    /// of the matching value, or if there is none, `Err` of the index where
+    /// it would go. This takes O(log n) time.
+    ///
+    pub fn binary_search_by<F: FnMut(&V) -> Ordering>(&self, mut f: F) -> Result<usize, usize> {
+        let mut tree = self;
+        let mut index = 0;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            match f(&rc.value) {
+                Less => {
+                    index += rc.left.len() + 1;
+                    tree = &rc.right;
+                }
+                Greater => tree = &rc.left,
+                Equal => return Ok(index + rc.left.len())
+            }
+        }
+        Err(index)
+    }
+
+    /// Return the smallest value in this tree, or None if it's empty. This
+    /// takes O(log n) time.
+    pub fn min(&self) -> Option<&V> {
+        let mut tree = self;
+        let mut least = None;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            least = Some(&*rc.value);
+            tree = &rc.left;
+        }
+        least
+    }
+
+    /// Return the largest value in this tree, or None if it's empty. This
+    /// takes O(log n) time.
+    pub fn max(&self) -> Option<&V> {
+        let mut tree = self;
+        let mut greatest = None;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            greatest = Some(&*rc.value);
+            tree = &rc.right;
+        }
+        greatest
+    }
+}
 
 use self::Color::*;
 use self::RBTreeImpl::*;
 
-fn black<V: Clone>(left: &RBTree<V>, value: &V, right: &RBTree<V>) -> RBTree<V> {
-    RBTree(RBNonEmpty(Rc::new(RBTreeNode {
-        color: Black,
-        value: value.clone(),
-        left: left.clone(),
-        right: right.clone()
-    })))
-}
-
-fn build_rotated_nodes<V: Clone>(a: &RBTree<V>,
-                                 x: &V,
-                                 b: &RBTree<V>,
-                                 y: &V,
-                                 c: &RBTree<V>,
-                                 z: &V,
-                                 d: &RBTree<V>)
-                                 -> Rc<RBTreeNode<V>>
+fn black<V, P: SharedPointerKind, C>(left: &RBTree<V, P, C>,
+                                     value: &P::Pointer<V>,
+                                     right: &RBTree<V, P, C>)
+                                     -> RBTree<V, P, C>
+{
+    RBTree(RBNonEmpty(P::new(RBTreeNode::new(Black, value.clone(), left.clone(), right.clone()))))
+}
+
+fn build_rotated_nodes<V, P: SharedPointerKind, C>(a: &RBTree<V, P, C>,
+                                                   x: &P::Pointer<V>,
+                                                   b: &RBTree<V, P, C>,
+                                                   y: &P::Pointer<V>,
+                                                   c: &RBTree<V, P, C>,
+                                                   z: &P::Pointer<V>,
+                                                   d: &RBTree<V, P, C>)
+                                                   -> P::Pointer<RBTreeNode<V, P, C>>
 {
-    Rc::new(RBTreeNode {
-        color: Red,
-        value: y.clone(),
-        left: black(a, x, b),
-        right: black(c, z, d)
-    })
+    P::new(RBTreeNode::new(Red, y.clone(), black(a, x, b), black(c, z, d)))
+}
+
+// Build a node, fixing a red node with a red child just below it if there is
+// one. Balancing a black node always produces a valid subtree; a red node may
+// still have a red child, to be fixed by balancing its parent.
+fn balance<V, P: SharedPointerKind, C: Compare<V>>(color: Color,
+                                                   left_tree: RBTree<V, P, C>,
+                                                   value: P::Pointer<V>,
+                                                   right_tree: RBTree<V, P, C>)
+                                                   -> P::Pointer<RBTreeNode<V, P, C>>
+{
+    let node = rebalance(color, left_tree, value, right_tree);
+    if color == Black {
+        strict_check!(RBTree::<V, P, C>(RBNonEmpty(node.clone())));
+    }
+    node
 }
 
-fn balance<V: Clone>(color: Color, left_tree: RBTree<V>, value: V, right_tree: RBTree<V>)
-                     -> Rc<RBTreeNode<V>>
+fn rebalance<V, P: SharedPointerKind, C>(color: Color,
+                                         left_tree: RBTree<V, P, C>,
+                                         value: P::Pointer<V>,
+                                         right_tree: RBTree<V, P, C>)
+                                         -> P::Pointer<RBTreeNode<V, P, C>>
 {
     if color == Black {
         match left_tree.0 {
@@ -112,48 +283,104 @@ fn balance<V: Clone>(color: Color, left_tree: RBTree<V>, value: V, right_tree: R
             }
         }
     }
-    return Rc::new(RBTreeNode {
-        color: color,
-        value: value,
-        left: left_tree,
-        right: right_tree
-    });
+    P::new(RBTreeNode::new(color, value, left_tree, right_tree))
 }
 
-impl<V: Clone> RBTree<V> {
+impl<V: Clone, P: SharedPointerKind, C> RBTree<V, P, C> {
     fn copy_to_vec(&self, out: &mut Vec<V>) {
         match self.0 {
             RBEmpty => (),
             RBNonEmpty(ref rc) => {
                 let r = &**rc;
                 r.left.copy_to_vec(out);
-                out.push(r.value.clone());
+                out.push((*r.value).clone());
                 r.right.copy_to_vec(out);
             }
         }
     }
+
+    // Like `copy_to_vec`, but nodes and values that nothing else shares are
+    // taken apart and moved into `out` rather than cloned.
+    pub(crate) fn move_to_vec(self, out: &mut Vec<V>) {
+        if let RBNonEmpty(rc) = self.0 {
+            match P::try_unwrap(rc) {
+                Ok(node) => {
+                    node.left.move_to_vec(out);
+                    out.push(P::try_unwrap(node.value).unwrap_or_else(|v| (*v).clone()));
+                    node.right.move_to_vec(out);
+                }
+                Err(rc) => RBTree::<V, P, C>(RBNonEmpty(rc)).copy_to_vec(out)
+            }
+        }
+    }
 }
 
-impl<V: Clone> IntoIterator for RBTree<V> {
+// Helpers for building a tree directly from sorted, distinct values (see
+// `par`, `edit` and `from_sorted_iter`). The median of each range becomes the
+// root of its subtree, so every level is full except possibly the last. Nodes
+// on that last level are red and all others black, which gives every path the
+// same number of black nodes.
+//
+// `red_depth` is the depth of that last level: floor(log2(len + 1)).
+pub(crate) fn red_depth(len: usize) -> usize {
+    (len + 1).ilog2() as usize
+}
+
+pub(crate) fn sorted_node<V, P: SharedPointerKind, C>(depth: usize,
+                                                        red_depth: usize,
+                                                        value: P::Pointer<V>,
+                                                        left: RBTree<V, P, C>,
+                                                        right: RBTree<V, P, C>) -> RBTree<V, P, C> {
+    let color = if depth == red_depth { Red } else { Black };
+    RBTree(RBNonEmpty(P::new(RBTreeNode::new(color, value, left, right))))
+}
+
+fn build_sorted<V, P: SharedPointerKind, C>(values: &[P::Pointer<V>], depth: usize, red_depth: usize) -> RBTree<V, P, C> {
+    if values.is_empty() {
+        return RBTree(RBEmpty);
+    }
+    let mid = values.len() / 2;
+    sorted_node(depth, red_depth, values[mid].clone(),
+                build_sorted(&values[..mid], depth + 1, red_depth),
+                build_sorted(&values[mid + 1..], depth + 1, red_depth))
+}
+
+// Build a node with the given color. It's up to the caller to make sure the
+// result is a valid tree.
+pub(crate) fn make_node<V, P: SharedPointerKind, C>(red: bool,
+                                                    value: V,
+                                                    left: RBTree<V, P, C>,
+                                                    right: RBTree<V, P, C>) -> RBTree<V, P, C> {
+    let color = if red { Red } else { Black };
+    RBTree(RBNonEmpty(P::new(RBTreeNode::new(color, P::new(value), left, right))))
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    // Append pointers to all the values, in order, to `out`.
+    pub(crate) fn copy_pointers_to_vec(&self, out: &mut Vec<P::Pointer<V>>) {
+        if let RBNonEmpty(ref rc) = self.0 {
+            rc.left.copy_pointers_to_vec(out);
+            out.push(rc.value.clone());
+            rc.right.copy_pointers_to_vec(out);
+        }
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind, C> IntoIterator for RBTree<V, P, C> {
     type Item = V;
     type IntoIter = <Vec<V> as IntoIterator>::IntoIter;
     fn into_iter(self) -> Self::IntoIter {
         let mut v = vec![];
-        self.copy_to_vec(&mut v);
+        self.move_to_vec(&mut v);
         v.into_iter()
     }
 }
 
-fn ins<V: Clone + Ord>(tree: &RBTree<V>, value: V) -> Rc<RBTreeNode<V>> {
+fn ins<V, P: SharedPointerKind, C: Compare<V>>(tree: &RBTree<V, P, C>, value: V) -> P::Pointer<RBTreeNode<V, P, C>> {
     match tree.0 {
-        RBEmpty => Rc::new(RBTreeNode {
-            color: Red,
-            value: value,
-            left: RBTree(RBEmpty),
-            right: RBTree(RBEmpty)
-        }),
+        RBEmpty => P::new(RBTreeNode::new(Red, P::new(value), RBTree(RBEmpty), RBTree(RBEmpty))),
         RBNonEmpty(ref rc) => {
-            match value.cmp(&rc.value) {
+            match C::compare(&value, &rc.value) {
                 Less => balance(
                     rc.color,
                     RBTree(RBNonEmpty(ins(&rc.left, value))),
@@ -170,30 +397,1314 @@ fn ins<V: Clone + Ord>(tree: &RBTree<V>, value: V) -> Rc<RBTreeNode<V>> {
     }
 }
 
-impl<V: Clone + Ord> Set for RBTree<V> {
-    fn empty() -> RBTree<V> { RBTree(RBEmpty) }
+// Copy the path to the value equal to `key`, replacing that value with
+// `f(value)`. Colors don't change, so the result is balanced if `tree` is.
+fn modify_node<V, P, C, F>(tree: &RBTree<V, P, C>, key: &V, f: F) -> Option<RBTree<V, P, C>>
+    where P: SharedPointerKind, C: Compare<V>, F: FnOnce(&V) -> V
+{
+    let rc = match tree.0 {
+        RBEmpty => return None,
+        RBNonEmpty(ref rc) => rc
+    };
+    let (value, left, right) = match C::compare(key, &rc.value) {
+        Less => (rc.value.clone(), modify_node(&rc.left, key, f)?, rc.right.clone()),
+        Greater => (rc.value.clone(), rc.left.clone(), modify_node(&rc.right, key, f)?),
+        Equal => {
+            let value = f(&rc.value);
+            if C::compare(&value, &rc.value) != Equal {
+                return None;
+            }
+            (P::new(value), rc.left.clone(), rc.right.clone())
+        }
+    };
+    Some(RBTree(RBNonEmpty(P::new(RBTreeNode::new(rc.color, value, left, right)))))
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> Set for RBTree<V, P, C> {
+    type Item = V;
+
+    fn empty() -> RBTree<V, P, C> { RBTree(RBEmpty) }
 
-    fn plus(&self, value: V) -> RBTree<V> {
+    fn len(&self) -> usize {
+        RBTree::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        RBTree::is_empty(self)
+    }
+
+    fn plus(&self, value: V) -> RBTree<V, P, C> {
         let rc = ins(self, value);
-        if rc.color == Red {
+        let result = if rc.color == Red {
             black(&rc.left, &rc.value, &rc.right)
         } else {
             RBTree(RBNonEmpty(rc))
-        }
+        };
+        strict_check!(result);
+        result
     }
     
     fn contains(&self, value: &V) -> bool {
+        RBTree::contains(self, value)
+    }
+
+    fn modify<F: FnOnce(&V) -> V>(&self, key: &V, f: F) -> Option<RBTree<V, P, C>> {
+        RBTree::modify(self, key, f)
+    }
+
+    /// Remove the values as one `edit` batch: a few are removed one at a
+    /// time, each copying O(log n) nodes, and many by rebuilding the tree.
+    fn minus_all<I: IntoIterator<Item=V>>(&self, values: I) -> RBTree<V, P, C> {
+        self.edit(|e| {
+            for v in values {
+                e.remove(v);
+            }
+        })
+    }
+
+    /// Return the union of two trees. Where both have equal values, the one
+    /// from `self` is kept.
+    ///
+    /// This splits `self` around the root of `other`, takes the unions of
+    /// the pieces on each side, and joins the results. A piece with nothing
+    /// to combine with, such as a whole subtree of `other` whose range holds
+    /// no values of `self`, is reused as it is, not copied. For trees of
+    /// sizes m <= n, it takes O(m log(n/m + 1)) time, and never clones a
+    /// value.
+    fn union(&self, other: &RBTree<V, P, C>) -> RBTree<V, P, C> {
+        let result = union(self, other).blacken();
+        strict_check!(result);
+        result
+    }
+
+    /// Return the values of `self` that are also in `other`.
+    ///
+    /// Like `union`, this works by splitting `self` around the root of
+    /// `other` and joining the results, in O(m log(n/m + 1)) time. Trees
+    /// made from a common version share whatever subtrees they still have in
+    /// common with the result.
+    fn intersection(&self, other: &RBTree<V, P, C>) -> RBTree<V, P, C> {
+        let result = intersection(self, other).blacken();
+        strict_check!(result);
+        result
+    }
+
+    /// Return the values of `self` that are not in `other`, in
+    /// O(m log(n/m + 1)) time, like `union`.
+    fn difference(&self, other: &RBTree<V, P, C>) -> RBTree<V, P, C> {
+        let result = difference(self, other).blacken();
+        strict_check!(result);
+        result
+    }
+}
+
+/* Join-based set operations
+ *
+ * `join(left, value, right)` builds a tree of everything in `left`, then
+ * `value`, then everything in `right`, in time proportional to the difference
+ * in their black heights. `split` is the inverse: it cuts a tree around a
+ * key. Union and the other whole-set operations are built from the two
+ * (Blelloch, Ferizovic and Sun, "Just Join for Parallel Ordered Sets", 2016).
+ *
+ * The results may have a red root, which is fine inside a larger tree but not
+ * for a finished one; the public functions call `blacken` on what they
+ * return.
+ */
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    // The number of black nodes on each path from the root to a leaf, not
+    // counting the empty trees at the bottom.
+    fn black_height(&self) -> usize {
+        let mut height = 0;
+        let mut tree = self;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            if rc.color == Black {
+                height += 1;
+            }
+            tree = &rc.left;
+        }
+        height
+    }
+
+    // This tree, with its root colored black.
+    fn blacken(self) -> RBTree<V, P, C> {
+        match self.0 {
+            RBNonEmpty(ref rc) if rc.color == Red => black(&rc.left, &rc.value, &rc.right),
+            _ => self
+        }
+    }
+}
+
+// Join two trees with black roots, where `left` is at least as tall, by
+// walking down the right spine of `left` to a black node of the same black
+// height as `right` and hanging a red node there. Any red node with a red
+// child this makes is fixed by `balance` on the way back up, as in `ins`.
+fn join_right<V, P, C>(left: &RBTree<V, P, C>, left_height: usize, value: P::Pointer<V>,
+                       right: RBTree<V, P, C>, right_height: usize)
+                       -> P::Pointer<RBTreeNode<V, P, C>>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    match left.0 {
+        RBNonEmpty(ref rc) if left_height > right_height || rc.color == Red => {
+            let child_height = if rc.color == Black { left_height - 1 } else { left_height };
+            let joined = join_right(&rc.right, child_height, value, right, right_height);
+            balance(rc.color, rc.left.clone(), rc.value.clone(), RBTree(RBNonEmpty(joined)))
+        }
+        _ => P::new(RBTreeNode::new(Red, value, left.clone(), right))
+    }
+}
+
+// The mirror image of `join_right`.
+fn join_left<V, P, C>(left: RBTree<V, P, C>, left_height: usize, value: P::Pointer<V>,
+                      right: &RBTree<V, P, C>, right_height: usize)
+                      -> P::Pointer<RBTreeNode<V, P, C>>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    match right.0 {
+        RBNonEmpty(ref rc) if right_height > left_height || rc.color == Red => {
+            let child_height = if rc.color == Black { right_height - 1 } else { right_height };
+            let joined = join_left(left, left_height, value, &rc.left, child_height);
+            balance(rc.color, RBTree(RBNonEmpty(joined)), rc.value.clone(), rc.right.clone())
+        }
+        _ => P::new(RBTreeNode::new(Red, value, left, right.clone()))
+    }
+}
+
+// A tree of the values in `left`, then `value`, then the values in `right`.
+// Every value in `left` must be less than `value`, and every value in `right`
+// greater.
+fn join<V, P, C>(left: RBTree<V, P, C>, value: P::Pointer<V>, right: RBTree<V, P, C>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    let left = left.blacken();
+    let right = right.blacken();
+    let (left_height, right_height) = (left.black_height(), right.black_height());
+    let node = if left_height >= right_height {
+        join_right(&left, left_height, value, right, right_height)
+    } else {
+        join_left(left, left_height, value, &right, right_height)
+    };
+    RBTree(RBNonEmpty(node))
+}
+
+// Like `join`, but with no value in the middle.
+fn join2<V, P, C>(left: RBTree<V, P, C>, right: RBTree<V, P, C>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    match right.split_first() {
+        None => left,
+        Some((first, rest)) => join(left, first, rest)
+    }
+}
+
+// What `split_at_key` returns: the values less than the key, the value equal
+// to it if any, and the values greater.
+type Pieces<V, P, C> = (RBTree<V, P, C>, Option<<P as SharedPointerKind>::Pointer<V>>, RBTree<V, P, C>);
+
+/// What `RBTree::split` returns: the values less than the pivot, the value
+/// equal to it if any, and the values greater.
+pub type Split<V, P = RcKind, C = Natural> = (RBTree<V, P, C>, Option<V>, RBTree<V, P, C>);
+
+// What `split_first` and `split_last` return: the least (or greatest) value,
+// and a tree of the rest.
+type Detached<V, P, C> = (<P as SharedPointerKind>::Pointer<V>, RBTree<V, P, C>);
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    // Cut this tree into the values less than `key`, the value equal to it if
+    // any, and the values greater.
+    fn split_at_key(&self, key: &V) -> Pieces<V, P, C> {
+        self.split_by(&|value| C::compare(key, value))
+    }
+
+    // Like `split_at_key`, but with the key given as a function that compares
+    // it to a value in the tree. See `find_by`.
+    fn split_by<F: Fn(&V) -> Ordering>(&self, probe: &F) -> Pieces<V, P, C> {
         match self.0 {
-            RBEmpty => false,
+            RBEmpty => (RBTree(RBEmpty), None, RBTree(RBEmpty)),
+            RBNonEmpty(ref rc) => match probe(&rc.value) {
+                Less => {
+                    let (less, found, greater) = rc.left.split_by(probe);
+                    (less, found, join(greater, rc.value.clone(), rc.right.clone()))
+                }
+                Greater => {
+                    let (less, found, greater) = rc.right.split_by(probe);
+                    (join(rc.left.clone(), rc.value.clone(), less), found, greater)
+                }
+                Equal => (rc.left.clone(), Some(rc.value.clone()), rc.right.clone())
+            }
+        }
+    }
+
+    // The least value, and a tree of the rest.
+    fn split_first(&self) -> Option<Detached<V, P, C>> {
+        let rc = match self.0 {
+            RBEmpty => return None,
+            RBNonEmpty(ref rc) => rc
+        };
+        Some(match rc.left.split_first() {
+            None => (rc.value.clone(), rc.right.clone()),
+            Some((first, rest)) => (first, join(rest, rc.value.clone(), rc.right.clone()))
+        })
+    }
+
+    // The mirror image of `split_first`.
+    fn split_last(&self) -> Option<Detached<V, P, C>> {
+        let rc = match self.0 {
+            RBEmpty => return None,
+            RBNonEmpty(ref rc) => rc
+        };
+        Some(match rc.right.split_last() {
+            None => (rc.value.clone(), rc.left.clone()),
+            Some((last, rest)) => (last, join(rc.left.clone(), rc.value.clone(), rest))
+        })
+    }
+}
+
+
+fn union<V, P, C>(a: &RBTree<V, P, C>, b: &RBTree<V, P, C>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    let rb = match (&a.0, &b.0) {
+        (_, RBEmpty) => return a.clone(),
+        (RBEmpty, _) => return b.clone(),
+        (RBNonEmpty(ra), RBNonEmpty(rb)) if P::ptr_eq(ra, rb) => return a.clone(),
+        (_, RBNonEmpty(rb)) => rb
+    };
+    let (less, found, greater) = a.split_at_key(&rb.value);
+    let left = union(&less, &rb.left);
+    let right = union(&greater, &rb.right);
+    join(left, found.unwrap_or_else(|| rb.value.clone()), right)
+}
+
+fn intersection<V, P, C>(a: &RBTree<V, P, C>, b: &RBTree<V, P, C>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    let rb = match (&a.0, &b.0) {
+        (RBEmpty, _) | (_, RBEmpty) => return RBTree(RBEmpty),
+        (RBNonEmpty(ra), RBNonEmpty(rb)) if P::ptr_eq(ra, rb) => return a.clone(),
+        (_, RBNonEmpty(rb)) => rb
+    };
+    let (less, found, greater) = a.split_at_key(&rb.value);
+    let left = intersection(&less, &rb.left);
+    let right = intersection(&greater, &rb.right);
+    match found {
+        Some(value) => join(left, value, right),
+        None => join2(left, right)
+    }
+}
+
+fn difference<V, P, C>(a: &RBTree<V, P, C>, b: &RBTree<V, P, C>) -> RBTree<V, P, C>
+    where P: SharedPointerKind, C: Compare<V>
+{
+    let rb = match (&a.0, &b.0) {
+        (RBEmpty, _) => return RBTree(RBEmpty),
+        (_, RBEmpty) => return a.clone(),
+        (RBNonEmpty(ra), RBNonEmpty(rb)) if P::ptr_eq(ra, rb) => return RBTree(RBEmpty),
+        (_, RBNonEmpty(rb)) => rb
+    };
+    let (less, _, greater) = a.split_at_key(&rb.value);
+    join2(difference(&less, &rb.left), difference(&greater, &rb.right))
+}
+
+// Lookups and removal where the key is given as a function rather than a
+// value, for keys of a different type than the values: borrowed forms, and
+// the keys of `RBMap`, whose entries can't be built from a key alone. The
+// function compares the key to a value in the tree, the way
+// `C::compare(key, value)` would.
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    pub(crate) fn find_by<F: Fn(&V) -> Ordering>(&self, probe: F) -> Option<&V> {
+        let mut tree = self;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            match probe(&rc.value) {
+                Less => tree = &rc.left,
+                Greater => tree = &rc.right,
+                Equal => return Some(&rc.value)
+            }
+        }
+        None
+    }
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    // Return a tree with `value` in place of any equal value. (`plus` keeps
+    // the old one.)
+    pub(crate) fn replace(&self, value: V) -> RBTree<V, P, C> {
+        let (less, _, greater) = self.split_at_key(&value);
+        let result = join(less, P::new(value), greater).blacken();
+        strict_check!(result);
+        result
+    }
+
+    // Return a tree without the value matching `probe`, or None if nothing
+    // matches.
+    pub(crate) fn remove_by<F: Fn(&V) -> Ordering>(&self, probe: F) -> Option<RBTree<V, P, C>> {
+        self.find_by(&probe)?;
+        let (less, _, greater) = self.split_by(&probe);
+        let result = join2(less, greater).blacken();
+        strict_check!(result);
+        Some(result)
+    }
+}
+
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Check that the values in this tree are in order, that no red node has a
+    /// red child, that every path from the root to a leaf passes through the
+    /// same number of black nodes, and that each node's recorded size is
+    /// right. See the `validate` module.
+    ///
+    /// This is always available, not just with `strict-checks`, so property
+    /// tests of code that builds trees can call it directly. The error names
+    /// the broken invariant and the path to the node where it was found.
+    #[doc(alias = "check_invariants")]
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.check(None, None, false, &mut String::new()).map(|_| ())
+    }
+
+    // Check this subtree and return its black height, counting empty trees as
+    // black.
+    fn check(&self, lo: Option<&V>, hi: Option<&V>, parent_red: bool, path: &mut String)
+             -> Result<usize, InvariantError> {
+        match self.0 {
+            RBEmpty => Ok(1),
             RBNonEmpty(ref rc) => {
-                let r = &**rc;
-                match value.cmp(&r.value) {
-                    Less => r.left.contains(value),
-                    Greater => r.right.contains(value),
-                    Equal => true
+                let v = &*rc.value;
+                if lo.is_some_and(|lo| C::compare(lo, v) != Less) ||
+                   hi.is_some_and(|hi| C::compare(v, hi) != Less) {
+                    return Err(InvariantError::new(Violation::OutOfOrder, path));
+                }
+                let red = rc.color == Red;
+                if red && parent_red {
+                    return Err(InvariantError::new(Violation::RedRed, path));
+                }
+                path.push('L');
+                let left_height = rc.left.check(lo, Some(v), red, path)?;
+                path.pop();
+                path.push('R');
+                let right_height = rc.right.check(Some(v), hi, red, path)?;
+                path.pop();
+                if left_height != right_height {
+                    return Err(InvariantError::new(Violation::BlackHeight, path));
+                }
+                if rc.size != rc.left.len() + 1 + rc.right.len() {
+                    return Err(InvariantError::new(Violation::Size, path));
+                }
+                Ok(left_height + if red { 0 } else { 1 })
+            }
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// The number of values in `range`. This uses the sizes recorded in the
+    /// nodes along the two search paths, so it takes O(log n) time.
+    ///
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>, R: RangeBounds<Q>
+    {
+        self.count_between(range.start_bound(), range.end_bound())
+    }
+
+    fn count_between<Q>(&self, lo: Bound<&Q>, hi: Bound<&Q>) -> usize
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        match self.0 {
+            RBEmpty => 0,
+            RBNonEmpty(ref rc) if matches!((lo, hi), (Unbounded, Unbounded)) => rc.size,
+            RBNonEmpty(ref rc) => {
+                let v = &*rc.value;
+                let above_lo = match lo {
+                    Unbounded => true,
+                    Included(lo) => compare_key::<Q, V, C>(lo, v) != Greater,
+                    Excluded(lo) => compare_key::<Q, V, C>(lo, v) == Less
+                };
+                let below_hi = match hi {
+                    Unbounded => true,
+                    Included(hi) => compare_key::<Q, V, C>(hi, v) != Less,
+                    Excluded(hi) => compare_key::<Q, V, C>(hi, v) == Greater
+                };
+                if !above_lo {
+                    rc.right.count_between(lo, hi)
+                } else if !below_hi {
+                    rc.left.count_between(lo, hi)
+                } else {
+                    rc.left.count_between(lo, Unbounded) + 1 + rc.right.count_between(Unbounded, hi)
+                }
+            }
+        }
+    }
+}
+
+// Lookups take any borrowed form of the values, as `BTreeSet`'s do, so an
+// `RBTree<String>` can be searched with a `&str`. The comparator has to
+// agree on the two forms: for `Natural`, that's the same requirement `Borrow`
+// already makes of `Ord`.
+fn compare_key<Q: ?Sized, V: Borrow<Q>, C: Compare<Q>>(key: &Q, value: &V) -> Ordering {
+    C::compare(key, value.borrow())
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Return true if the tree contains a value equal to `value`.
+    ///
+    /// `value` can be any borrowed form of the values, so an
+    /// `RBTree<String>` can be searched with a `&str`.
+    ///
+    pub fn contains<Q>(&self, value: &Q) -> bool
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.get(value).is_some()
+    }
+
+    /// Return a copy of this tree with the value equal to `key` replaced by
+    /// `f(old_value)`, copying only the search path. See `Set::modify`.
+    ///
+    /// Unlike the trait method, this doesn't need `V: Clone`.
+    pub fn modify<F: FnOnce(&V) -> V>(&self, key: &V, f: F) -> Option<RBTree<V, P, C>> {
+        let result = modify_node(self, key, f)?;
+        strict_check!(result);
+        Some(result)
+    }
+
+    /// Return the value in the tree equal to `value`, or None. Like
+    /// `contains`, this accepts any borrowed form of the values.
+    ///
+    pub fn get<Q>(&self, value: &Q) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.find_by(|v| compare_key::<Q, V, C>(value, v))
+    }
+
+    /// Divide the tree into the values less than `pivot`, the value equal to
+    /// it if there is one, and the values greater. Like `contains`, this
+    /// accepts any borrowed form of the values.
+    ///
+    /// This takes O(log n) time, and the two trees share most of their nodes
+    /// with this one. It's the primitive `union`, `intersection` and
+    /// `difference` are built on, and it's also a cheap way to cut a set into
+    /// parts to hand to different workers.
+    ///
+    pub fn split<Q>(&self, pivot: &Q) -> Split<V, P, C>
+        where Q: ?Sized, V: Borrow<Q> + Clone, C: Compare<Q>
+    {
+        let (less, found, greater) = self.split_by(&|v| compare_key::<Q, V, C>(pivot, v));
+        let (less, greater) = (less.blacken(), greater.blacken());
+        strict_check!(less);
+        strict_check!(greater);
+        (less, found.map(|v| (*v).clone()), greater)
+    }
+
+    /// Return the index of `value` in the tree, counting from 0 in order, or
+    /// if it isn't there, `Err` of the index where it would go. This is
+    /// `binary_search_by` using the tree's own ordering, and like `contains`
+    /// it accepts any borrowed form of the values.
+    ///
+    pub fn binary_search<Q>(&self, value: &Q) -> Result<usize, usize>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.binary_search_by(|v| compare_key::<Q, V, C>(value, v).reverse())
+    }
+
+    /// Return the greatest value less than or equal to `value`, or None if
+    /// every value in the tree is greater. This takes O(log n) time.
+    ///
+    pub fn floor<Q>(&self, value: &Q) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.nearest_below(value, true)
+    }
+
+    /// Return the least value greater than or equal to `value`, or None if
+    /// every value in the tree is less. This takes O(log n) time.
+    ///
+    pub fn ceiling<Q>(&self, value: &Q) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.nearest_above(value, true)
+    }
+
+    /// Return the greatest value strictly less than `value`, or None. Unlike
+    /// `floor`, this never returns a value equal to `value`, so it can be
+    /// used to step backward through the tree.
+    ///
+    pub fn predecessor<Q>(&self, value: &Q) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.nearest_below(value, false)
+    }
+
+    /// Return the least value strictly greater than `value`, or None. Unlike
+    /// `ceiling`, this never returns a value equal to `value`, so it can be
+    /// used to step forward through the tree.
+    ///
+    pub fn successor<Q>(&self, value: &Q) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        self.nearest_above(value, false)
+    }
+
+    // Walk down the search path for `value`, remembering the last node where
+    // we turned right: that's the closest value below it seen so far.
+    fn nearest_below<Q>(&self, value: &Q, inclusive: bool) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        let mut best = None;
+        let mut tree = self;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            match compare_key::<Q, V, C>(value, &rc.value) {
+                Greater => {
+                    best = Some(&*rc.value);
+                    tree = &rc.right;
+                }
+                Equal if inclusive => return Some(&rc.value),
+                _ => tree = &rc.left
+            }
+        }
+        best
+    }
+
+    fn nearest_above<Q>(&self, value: &Q, inclusive: bool) -> Option<&V>
+        where Q: ?Sized, V: Borrow<Q>, C: Compare<Q>
+    {
+        let mut best = None;
+        let mut tree = self;
+        while let RBNonEmpty(ref rc) = tree.0 {
+            match compare_key::<Q, V, C>(value, &rc.value) {
+                Less => {
+                    best = Some(&*rc.value);
+                    tree = &rc.left;
                 }
+                Equal if inclusive => return Some(&rc.value),
+                _ => tree = &rc.right
             }
         }
+        best
+    }
+
+    /// Return a tree without the smallest value. If this tree is empty, this
+    /// returns an empty tree. Together with `min`, this drains a tree in
+    /// order, with each step taking O(log n) time and sharing most of its
+    /// nodes with the tree before.
+    ///
+    pub fn without_min(&self) -> RBTree<V, P, C> {
+        let result = match self.split_first() {
+            None => RBTree(RBEmpty),
+            Some((_, rest)) => rest.blacken()
+        };
+        strict_check!(result);
+        result
+    }
+
+    /// Return a tree without the largest value. If this tree is empty, this
+    /// returns an empty tree.
+    ///
+    pub fn without_max(&self) -> RBTree<V, P, C> {
+        let result = match self.split_last() {
+            None => RBTree(RBEmpty),
+            Some((_, rest)) => rest.blacken()
+        };
+        strict_check!(result);
+        result
     }
 }
 
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return a tree of the same shape and colors holding `f` of each value.
+    ///
+    /// This copies every node but does no comparisons and no rebalancing, so
+    /// `f` must be strictly increasing: `a < b` must imply `f(a) < f(b)`.
+    /// Otherwise the result is not a valid search tree. With the
+    /// `strict-checks` feature, the result is validated.
+    ///
+    pub fn map_monotonic<U, F>(&self, mut f: F) -> RBTree<U, P, C>
+        where C: Compare<U>, F: FnMut(&V) -> U
+    {
+        let result = self.map_monotonic_by(&mut f);
+        strict_check!(result);
+        result
+    }
+
+    fn map_monotonic_by<U, F: FnMut(&V) -> U>(&self, f: &mut F) -> RBTree<U, P, C> {
+        match self.0 {
+            RBEmpty => RBTree(RBEmpty),
+            RBNonEmpty(ref rc) => {
+                let left = rc.left.map_monotonic_by(f);
+                let value = f(&rc.value);
+                let right = rc.right.map_monotonic_by(f);
+                make_node(rc.color == Red, value, left, right)
+            }
+        }
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return a copy of this tree, with the same shape and colors, that shares
+    /// nodes using pointer kind `Q`.
+    ///
+    /// This copies every node and clones every value once. No rebalancing is
+    /// done.
+    ///
+    pub fn to_pointer_kind<Q: SharedPointerKind>(&self) -> RBTree<V, Q, C> {
+        match self.0 {
+            RBEmpty => RBTree(RBEmpty),
+            RBNonEmpty(ref rc) => RBTree(RBNonEmpty(Q::new(RBTreeNode::new(
+                rc.color,
+                Q::new((*rc.value).clone()),
+                rc.left.to_pointer_kind(),
+                rc.right.to_pointer_kind()
+            ))))
+        }
+    }
+}
+
+impl<V: Clone, C> RBTree<V, RcKind, C> {
+    /// Return a thread-safe copy of this tree.
+    pub fn to_shared(&self) -> RBTree<V, ArcKind, C> { self.to_pointer_kind() }
+}
+
+impl<V: Clone, C> RBTree<V, ArcKind, C> {
+    /// Return a copy of this tree that uses `Rc` to share nodes.
+    pub fn to_local(&self) -> RBTree<V, RcKind, C> { self.to_pointer_kind() }
+}
+
+impl<V: Clone, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Encode several versions of a tree as a `Snapshot`. Nodes and values
+    /// shared between versions are written only once.
+    pub fn encode(versions: &[RBTree<V, P, C>]) -> Snapshot<V, RBTreeRecord> {
+        let mut encoder = Encoder::new();
+        for t in versions {
+            let id = t.encode_node(&mut encoder);
+            encoder.root(id);
+        }
+        encoder.finish()
+    }
+
+    fn encode_node(&self, encoder: &mut Encoder<V, RBTreeRecord>) -> Option<usize> {
+        match self.0 {
+            RBEmpty => None,
+            RBNonEmpty(ref rc) => {
+                let node: &RBTreeNode<V, P, C> = rc;
+                if let Some(id) = encoder.lookup(node) {
+                    return Some(id);
+                }
+                let left = node.left.encode_node(encoder);
+                let right = node.right.encode_node(encoder);
+                let value = encoder.value(&node.value);
+                Some(encoder.node(node, RBTreeRecord {
+                    red: node.color == Red,
+                    value,
+                    left,
+                    right
+                }))
+            }
+        }
+    }
+
+    /// Rebuild the versions stored in a snapshot, restoring the sharing
+    /// between them.
+    pub fn decode(snapshot: &Snapshot<V, RBTreeRecord>) -> Result<Vec<RBTree<V, P, C>>, DecodeError> {
+        let values: Vec<P::Pointer<V>> = snapshot.values.iter().map(|v| P::new(v.clone())).collect();
+        let mut trees: Vec<RBTree<V, P, C>> = Vec::with_capacity(snapshot.nodes.len());
+        for (i, r) in snapshot.nodes.iter().enumerate() {
+            snapshot.check_value(i, r.value)?;
+            snapshot.check_child(i, r.left)?;
+            snapshot.check_child(i, r.right)?;
+            let color = if r.red { Red } else { Black };
+            let node = RBTreeNode::new(color, values[r.value].clone(),
+                                       decoded(&trees, r.left), decoded(&trees, r.right));
+            trees.push(RBTree(RBNonEmpty(P::new(node))));
+        }
+        snapshot.roots.iter().map(|&root| {
+            snapshot.check_root(root)?;
+            Ok(decoded(&trees, root))
+        }).collect()
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind, C> Shape for RBTree<V, P, C> {
+    fn node(&self) -> Option<(String, &RBTree<V, P, C>, &RBTree<V, P, C>)> {
+        match self.0 {
+            RBEmpty => None,
+            RBNonEmpty(ref rc) => {
+                let color = if rc.color == Red { "red" } else { "black" };
+                Some((format!("{:?} {}", *rc.value, color), &rc.left, &rc.right))
+            }
+        }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Draw the shape of this tree as indented text, one node per line, with
+    /// each node's color, for debugging. An empty subtree whose sibling isn't
+    /// empty is shown as `·`.
+    pub fn pretty(&self) -> String {
+        pretty(self)
+    }
+}
+
+impl<V: Clone + fmt::Debug, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Render this tree as a Graphviz graph, in DOT format, with one vertex
+    /// per node.
+    pub fn to_dot(&self) -> String {
+        RBTree::versions_to_dot(slice::from_ref(self))
+    }
+
+    /// Render several versions of a tree as one Graphviz graph, in DOT
+    /// format. Nodes shared by more than one version appear only once, so the
+    /// graph shows exactly how much structure the versions share.
+    ///
+    pub fn versions_to_dot(versions: &[RBTree<V, P, C>]) -> String {
+        dot::render("RBTree", &RBTree::encode(versions))
+    }
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return the distribution of node depths in this tree, and how many
+    /// nodes are red and black. See the `stats` module.
+    pub fn stats(&self) -> RBTreeStats {
+        let mut stats = RBTreeStats::default();
+        self.add_stats(0, &mut stats);
+        stats
+    }
+
+    fn add_stats(&self, depth: usize, stats: &mut RBTreeStats) {
+        if let RBNonEmpty(ref rc) = self.0 {
+            stats.shape.add(depth);
+            if rc.color == Red {
+                stats.red += 1;
+            } else {
+                stats.black += 1;
+            }
+            rc.left.add_stats(depth + 1, stats);
+            rc.right.add_stats(depth + 1, stats);
+        }
+    }
+}
+
+// Push `tree` and the nodes down its left spine onto `stack`.
+fn push_left_spine<'a, V, P: SharedPointerKind, C>(stack: &mut Vec<&'a RBTreeNode<V, P, C>>,
+                                                     mut tree: &'a RBTree<V, P, C>) {
+    while let RBNonEmpty(ref rc) = tree.0 {
+        stack.push(rc);
+        tree = &rc.left;
+    }
+}
+
+/// An in-order iterator over references to the values in an `RBTree`.
+pub struct RBTreeIter<'a, V: 'a, P: SharedPointerKind + 'a = RcKind, C: 'a = Natural> {
+    // The nodes whose values haven't been visited yet, deepest last. The right
+    // subtree of each node is pushed only after the node itself is visited.
+    stack: Vec<&'a RBTreeNode<V, P, C>>,
+    // The number of values left, from the sizes recorded in the nodes.
+    remaining: usize
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return an iterator over references to the values in this tree, in
+    /// order. Unlike `into_iter`, this doesn't clone anything or require
+    /// `V: Clone`. It keeps a stack of O(log n) nodes.
+    pub fn iter(&self) -> RBTreeIter<'_, V, P, C> {
+        let mut iter = RBTreeIter { stack: vec![], remaining: self.len() };
+        push_left_spine(&mut iter.stack, self);
+        iter
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C> Iterator for RBTreeIter<'a, V, P, C> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        let node = self.stack.pop()?;
+        push_left_spine(&mut self.stack, &node.right);
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C> ExactSizeIterator for RBTreeIter<'a, V, P, C> {}
+
+impl<'a, V, P: SharedPointerKind, C> Clone for RBTreeIter<'a, V, P, C> {
+    fn clone(&self) -> RBTreeIter<'a, V, P, C> {
+        RBTreeIter { stack: self.stack.clone(), remaining: self.remaining }
+    }
+}
+
+impl<'a, V: fmt::Debug, P: SharedPointerKind, C> fmt::Debug for RBTreeIter<'a, V, P, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RBTreeIter")?;
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C> IntoIterator for &'a RBTree<V, P, C> {
+    type Item = &'a V;
+    type IntoIter = RBTreeIter<'a, V, P, C>;
+    fn into_iter(self) -> RBTreeIter<'a, V, P, C> {
+        self.iter()
+    }
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Combine the values, in order, using `f`. This walks the tree by
+    /// reference, without cloning or collecting the values.
+    pub fn fold<A, F: FnMut(A, &V) -> A>(&self, init: A, f: F) -> A {
+        self.iter().fold(init, f)
+    }
+
+    /// Call `f` on each value, in order.
+    pub fn for_each<F: FnMut(&V)>(&self, f: F) {
+        self.iter().for_each(f)
+    }
+
+    /// The `k` least values, least first, or all of them if there are fewer
+    /// than `k`. This takes O(log n + k) time.
+    pub fn take_smallest(&self, k: usize) -> Vec<&V> {
+        self.iter().take(k).collect()
+    }
+
+    /// The `k` greatest values, greatest first, or all of them if there are
+    /// fewer than `k`. This takes O(log n + k) time.
+    pub fn take_largest(&self, k: usize) -> Vec<&V> {
+        let mut out = Vec::with_capacity(k.min(self.len()));
+        self.push_largest(k, &mut out);
+        out
+    }
+
+    // Push values onto `out`, greatest first, until it holds `k`.
+    fn push_largest<'a>(&'a self, k: usize, out: &mut Vec<&'a V>) {
+        if let RBNonEmpty(ref rc) = self.0 {
+            rc.right.push_largest(k, out);
+            if out.len() < k {
+                out.push(&rc.value);
+                rc.left.push_largest(k, out);
+            }
+        }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind, C> fmt::Debug for RBTree<V, P, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RBTree")?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<V, P: SharedPointerKind, C> Linked for RBTree<V, P, C> {
+    fn root_addr(&self) -> Option<usize> {
+        match self.0 {
+            RBEmpty => None,
+            RBNonEmpty(ref rc) => Some(&**rc as *const RBTreeNode<V, P, C> as usize)
+        }
+    }
+
+    fn children(&self) -> (Option<&RBTree<V, P, C>>, Option<&RBTree<V, P, C>>) {
+        match self.0 {
+            RBEmpty => (None, None),
+            RBNonEmpty(ref rc) => (Some(&rc.left), Some(&rc.right))
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return true if `self` and `other` are the same version of the tree:
+    /// both empty, or with the same root node. This takes constant time. If it
+    /// returns true, the two are certainly equal; if not, they may still be
+    /// equal, and `==` will say.
+    pub fn same(&self, other: &RBTree<V, P, C>) -> bool {
+        sharing::same(self, other)
+    }
+
+    /// Return true if `self` and `other` have any nodes in common, as when
+    /// one was made from the other. This takes time proportional to the
+    /// number of nodes in both.
+    ///
+    pub fn shares_structure_with(&self, other: &RBTree<V, P, C>) -> bool {
+        sharing::shares_structure(&[self], &[other])
+    }
+
+    /// Return the number of nodes `self` and `other` have in common. This
+    /// takes time proportional to the number of nodes in both.
+    pub fn shared_node_count(&self, other: &RBTree<V, P, C>) -> usize {
+        sharing::shared_count(&[self], &[other])
+    }
+
+    /// Return the number of nodes in `self` that are not also in `other`:
+    /// how much memory keeping `self` costs, on top of keeping `other`.
+    pub fn unique_node_count(&self, other: &RBTree<V, P, C>) -> usize {
+        sharing::unique_count(&[self], &[other])
+    }
+}
+
+impl<V, P: SharedPointerKind, C> SortedTree for RBTree<V, P, C> {
+    type Value = V;
+
+    fn root_value(&self) -> &V {
+        match self.0 {
+            RBEmpty => panic!("root_value called on an empty tree"),
+            RBNonEmpty(ref rc) => &rc.value
+        }
+    }
+}
+
+impl<V: PartialEq, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Return the values added, removed and changed between `old` and `new`,
+    /// skipping over any subtrees they share. See the `diff` module.
+    pub fn diff<'a>(old: &'a RBTree<V, P, C>, new: &'a RBTree<V, P, C>) -> Diff<'a, V> {
+        diff::diff(old, new, C::compare, V::eq)
+    }
+}
+
+impl<V: Clone + PartialEq, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Merge two versions `a` and `b` that were both made from `base`. The
+    /// result starts from `a` and makes every change `b` made: values added,
+    /// values removed, and values replaced by ones that compare equal.
+    ///
+    /// Where `a` and `b` both changed the same value, and not in the same
+    /// way, `conflict(key, in_a, in_b)` decides. `key` is the value from
+    /// `base`, or from `b` if `base` didn't have one; `in_a` and `in_b` are
+    /// what the two versions have in its place, or None where it was removed.
+    /// The result gets what `conflict` returns, or nothing if it returns None.
+    /// With the `Natural` order on a type whose equal values are identical,
+    /// two versions never conflict.
+    ///
+    /// Both diffs skip the subtrees their versions share with `base`, so this
+    /// takes time proportional to the number of changes, times log² n.
+    pub fn merge3<F>(base: &RBTree<V, P, C>, a: &RBTree<V, P, C>, b: &RBTree<V, P, C>, mut conflict: F)
+                     -> RBTree<V, P, C>
+        where F: FnMut(&V, Option<&V>, Option<&V>) -> Option<V>
+    {
+        let ours = diff::changes(&RBTree::diff(base, a), C::compare);
+        let theirs = diff::changes(&RBTree::diff(base, b), C::compare);
+        let mut result = a.clone();
+        for (key, in_b) in theirs {
+            let value = match ours.binary_search_by(|&(k, _)| C::compare(k, key)) {
+                Err(_) => in_b.cloned(),
+                Ok(i) if ours[i].1 == in_b => continue,
+                Ok(i) => conflict(key, ours[i].1, in_b)
+            };
+            result = result.remove_by(|x| C::compare(key, x)).unwrap_or(result);
+            if let Some(value) = value {
+                result = result.replace(value);
+            }
+        }
+        result
+    }
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Return a copy of this tree with a batch of changes applied. `f` is
+    /// called once to record the changes. See the `edit` module.
+    pub fn edit<F: FnOnce(&mut Edit<V>)>(&self, f: F) -> RBTree<V, P, C> {
+        let mut batch = Edit::new();
+        f(&mut batch);
+        let changes = edit::sorted(batch, C::compare);
+        if !edit::should_rebuild(self, &changes, true) {
+            return changes.into_iter().fold(self.clone(), |t, (v, insert)| {
+                if insert {
+                    t.plus(v)
+                } else {
+                    t.remove_by(|x| C::compare(&v, x)).unwrap_or(t)
+                }
+            });
+        }
+        let mut old = vec![];
+        self.copy_pointers_to_vec(&mut old);
+        let values = edit::merge(old, changes, C::compare, P::new);
+        let result = build_sorted(&values, 0, red_depth(values.len()));
+        strict_check!(result);
+        result
+    }
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// Build a tree from values already sorted by `C` (Okasaki, exercise
+    /// 3.9). Where neighbors are equal, the first is kept, as repeated
+    /// insertion would.
+    ///
+    /// This takes O(n) time, where inserting the values one at a time would
+    /// take O(n log n). The values are gathered into a vector first, then
+    /// the tree is built from the middle out, as `edit` does, with every
+    /// level full except possibly the last, whose nodes are red.
+    ///
+    /// If the values aren't sorted, the result isn't a valid search tree.
+    /// With the `strict-checks` feature, the result is validated.
+    ///
+    pub fn from_sorted_iter<I: IntoIterator<Item=V>>(values: I) -> RBTree<V, P, C> {
+        let mut values: Vec<P::Pointer<V>> = values.into_iter().map(P::new).collect();
+        values.dedup_by(|later, earlier| C::compare(later, earlier) == Equal);
+        let result = build_sorted(&values, 0, red_depth(values.len()));
+        strict_check!(result);
+        result
+    }
+}
+
+impl<V, P: SharedPointerKind, C: Compare<V>> FromIterator<V> for RBTree<V, P, C> {
+    /// Sort the values, then build the tree with `from_sorted_iter`. The sort
+    /// is stable, so where values are equal the first is kept, as with
+    /// `Set::plus`. This takes O(n log n) time for the sort, and O(n) for the
+    /// tree.
+    fn from_iter<Iterable: IntoIterator<Item=V>>(iterator: Iterable) -> RBTree<V, P, C> {
+        let mut values: Vec<V> = iterator.into_iter().collect();
+        values.sort_by(C::compare);
+        RBTree::from_sorted_iter(values)
+    }
+}
+
+// Equality compares the values in order, not the shape of the tree, which
+// depends on the order of insertion.
+impl<V: PartialEq, P: SharedPointerKind, C> PartialEq for RBTree<V, P, C> {
+    fn eq(&self, other: &RBTree<V, P, C>) -> bool {
+        match (&self.0, &other.0) {
+            (RBNonEmpty(a), RBNonEmpty(b)) if P::ptr_eq(a, b) => true,
+            _ => self.iter().eq(other.iter())
+        }
+    }
+}
+
+impl<V: Eq, P: SharedPointerKind, C> Eq for RBTree<V, P, C> {}
+
+// As with `Tree`, the values in order, then the count.
+impl<V: Hash, P: SharedPointerKind, C> Hash for RBTree<V, P, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for v in self.iter() {
+            v.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
+
+/// A position in a red-black tree, for walking around it and changing values
+/// in place.
+///
+/// This works like `tree::TreeZipper`, except that only values can be
+/// replaced, never whole subtrees, so the tree stays balanced. Each
+/// replacement value should compare equal to the one it replaces, or at least
+/// fall between the values on either side of it, to keep the tree sorted.
+pub struct RBTreeZipper<V, P: SharedPointerKind = RcKind, C = Natural> {
+    focus: RBTree<V, P, C>,
+    // The nodes above the focus, root first, and which way we went from each.
+    path: Vec<Step<V, P, C>>
+}
+
+type Step<V, P, C> = (<P as SharedPointerKind>::Pointer<RBTreeNode<V, P, C>>, Side);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side { Left, Right }
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return a zipper focused on the root of this tree.
+    pub fn zipper(&self) -> RBTreeZipper<V, P, C> {
+        RBTreeZipper { focus: self.clone(), path: vec![] }
+    }
+}
+
+impl<V, P: SharedPointerKind, C> RBTreeZipper<V, P, C> {
+    /// The subtree in focus.
+    pub fn focus(&self) -> &RBTree<V, P, C> {
+        &self.focus
+    }
+
+    /// The value at the root of the focus, or None if the focus is empty.
+    pub fn value(&self) -> Option<&V> {
+        match self.focus.0 {
+            RBEmpty => None,
+            RBNonEmpty(ref rc) => Some(&rc.value)
+        }
+    }
+
+    /// How many steps the focus is below the root.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Move the focus to its left subtree. If the focus is empty, this does
+    /// nothing and returns false.
+    pub fn down_left(&mut self) -> bool {
+        self.down(Side::Left)
+    }
+
+    /// Move the focus to its right subtree. If the focus is empty, this does
+    /// nothing and returns false.
+    pub fn down_right(&mut self) -> bool {
+        self.down(Side::Right)
+    }
+
+    fn down(&mut self, side: Side) -> bool {
+        let node = match self.focus.0 {
+            RBEmpty => return false,
+            RBNonEmpty(ref rc) => rc.clone()
+        };
+        self.focus = match side {
+            Side::Left => node.left.clone(),
+            Side::Right => node.right.clone()
+        };
+        self.path.push((node, side));
+        true
+    }
+
+    /// Move the focus to its parent. At the root, this does nothing and
+    /// returns false.
+    pub fn up(&mut self) -> bool {
+        let (parent, side) = match self.path.pop() {
+            None => return false,
+            Some(step) => step
+        };
+        let old = if side == Side::Left { &parent.left } else { &parent.right };
+        if sharing::same(old, &self.focus) {
+            self.focus = RBTree(RBNonEmpty(parent));
+        } else {
+            let focus = mem::replace(&mut self.focus, RBTree(RBEmpty));
+            let (left, right) = match side {
+                Side::Left => (focus, parent.right.clone()),
+                Side::Right => (parent.left.clone(), focus)
+            };
+            let node = RBTreeNode::new(parent.color, parent.value.clone(), left, right);
+            self.focus = RBTree(RBNonEmpty(P::new(node)));
+        }
+        true
+    }
+
+    /// Replace the value at the root of the focus, keeping its color and
+    /// subtrees. If the focus is empty, this does nothing and returns false.
+    pub fn set_value(&mut self, value: V) -> bool {
+        let node = match self.focus.0 {
+            RBEmpty => return false,
+            RBNonEmpty(ref rc) => RBTreeNode::new(rc.color, P::new(value), rc.left.clone(), rc.right.clone())
+        };
+        self.focus = RBTree(RBNonEmpty(P::new(node)));
+        true
+    }
+
+    /// Move back up to the root and return the whole edited tree.
+    pub fn rebuild(mut self) -> RBTree<V, P, C> {
+        while self.up() {}
+        self.focus
+    }
+}
+
+impl<V, P: SharedPointerKind, C> Clone for RBTreeZipper<V, P, C> {
+    fn clone(&self) -> RBTreeZipper<V, P, C> {
+        RBTreeZipper { focus: self.focus.clone(), path: self.path.clone() }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind, C> fmt::Debug for RBTreeZipper<V, P, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RBTreeZipper")
+            .field("focus", &self.focus)
+            .field("depth", &self.path.len())
+            .finish()
+    }
+}
+
+/// A position in a red-black tree that makes it cheap to look up values in
+/// increasing order.
+///
+/// A fresh `contains` starts at the root every time. A finger remembers the
+/// path to its current value, so moving forward by `seek` only climbs as far
+/// as the nearest common ancestor of the old and new positions and searches
+/// down from there: O(log d) steps to move d values ahead, and O(1) amortized
+/// for `advance`. Seeking backward starts over from the root.
+pub struct RBTreeFinger<'a, V: 'a, P: SharedPointerKind + 'a = RcKind, C: 'a = Natural> {
+    tree: &'a RBTree<V, P, C>,
+    // As in `RBTreeIter`: the current node on top, then the ancestors whose values
+    // come after it, in order.
+    stack: Vec<&'a RBTreeNode<V, P, C>>
+}
+
+impl<V, P: SharedPointerKind, C> RBTree<V, P, C> {
+    /// Return a finger pointing at the smallest value in this tree.
+    pub fn finger(&self) -> RBTreeFinger<'_, V, P, C> {
+        let mut stack = vec![];
+        push_left_spine(&mut stack, self);
+        RBTreeFinger { tree: self, stack }
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C> RBTreeFinger<'a, V, P, C> {
+    /// The value the finger points at, or None if it has moved past the
+    /// largest value.
+    pub fn get(&self) -> Option<&'a V> {
+        self.stack.last().map(|node| &*node.value)
+    }
+
+    /// Move to the next value in order, and return it.
+    pub fn advance(&mut self) -> Option<&'a V> {
+        let node = self.stack.pop()?;
+        push_left_spine(&mut self.stack, &node.right);
+        self.get()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C: Compare<V>> RBTreeFinger<'a, V, P, C> {
+    /// Move to the smallest value that is not less than `value`, and return
+    /// it. Returns None, leaving the finger past the end, if every value in
+    /// the tree is less than `value`.
+    pub fn seek(&mut self, value: &V) -> Option<&'a V> {
+        // Past the end, or past the target, start over from the root.
+        let restart = match self.get() {
+            None => true,
+            Some(current) => C::compare(value, current) == Less
+        };
+        if restart {
+            self.stack.clear();
+            self.descend(self.tree, value);
+            return self.get();
+        }
+
+        // Every value between a node on the stack and the one below it is in
+        // the first node's right subtree. Pop the nodes that are too small;
+        // the target is in the right subtree of the last one popped, or else
+        // it's the new top of the stack.
+        let mut last = None;
+        while let Some(&node) = self.stack.last() {
+            if C::compare(&node.value, value) != Less {
+                break;
+            }
+            last = self.stack.pop();
+        }
+        if let Some(node) = last {
+            self.descend(&node.right, value);
+        }
+        self.get()
+    }
+
+    /// Return true if the tree contains `value`, moving the finger to it (or
+    /// to where it would be) as `seek` does.
+    pub fn contains(&mut self, value: &V) -> bool {
+        match self.seek(value) {
+            None => false,
+            Some(found) => C::compare(value, found) == Equal
+        }
+    }
+
+    // Search `tree` for the smallest value not less than `value`, pushing the
+    // nodes we pass on the left.
+    fn descend(&mut self, mut tree: &'a RBTree<V, P, C>, value: &V) {
+        while let RBNonEmpty(ref rc) = tree.0 {
+            if C::compare(&rc.value, value) == Less {
+                tree = &rc.right;
+            } else {
+                self.stack.push(rc);
+                tree = &rc.left;
+            }
+        }
+    }
+}
+
+impl<'a, V, P: SharedPointerKind, C> Clone for RBTreeFinger<'a, V, P, C> {
+    fn clone(&self) -> RBTreeFinger<'a, V, P, C> {
+        RBTreeFinger { tree: self.tree, stack: self.stack.clone() }
+    }
+}
+
+impl<'a, V: fmt::Debug, P: SharedPointerKind, C> fmt::Debug for RBTreeFinger<'a, V, P, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RBTreeFinger").field(&self.get()).finish()
+    }
+}
@@ -0,0 +1,155 @@
+//! Fair scheduling among several queues.
+//!
+//! A `RoundRobin` holds a queue of items for each of any number of streams,
+//! identified by keys. `next` takes one item from each nonempty stream in
+//! turn, so a busy stream can't starve the others.
+
+use std::cmp::Ordering;
+use std::fmt;
+use compare::Compare;
+use pointer::{SharedPointerKind, RcKind};
+use queue::BatchedQueue;
+use rbtree::RBTree;
+use traits::{Queue, Set};
+
+// One stream's queue. Lanes are kept in a set ordered by id alone, so that a
+// lane can be looked up and replaced by its id. Only nonempty streams have
+// lanes.
+struct Lane<K, T, P: SharedPointerKind> {
+    id: K,
+    items: BatchedQueue<T, P>
+}
+
+enum ById {}
+
+impl<K: Ord, T, P: SharedPointerKind> Compare<Lane<K, T, P>> for ById {
+    fn compare(a: &Lane<K, T, P>, b: &Lane<K, T, P>) -> Ordering {
+        a.id.cmp(&b.id)
+    }
+}
+
+/// A persistent round-robin scheduler: a queue of queues, keyed by stream id.
+///
+/// Items from the same stream come out in the order they were enqueued.
+/// Streams take turns in the order they became nonempty; a stream that
+/// empties out loses its turn, and rejoins at the back when an item next
+/// arrives for it.
+pub struct RoundRobin<K, T, P: SharedPointerKind = RcKind> {
+    lanes: RBTree<Lane<K, T, P>, P, ById>,
+    // The ids of the nonempty streams, in turn order.
+    turns: BatchedQueue<K, P>,
+    len: usize
+}
+
+impl<K, T, P: SharedPointerKind> Clone for RoundRobin<K, T, P> {
+    fn clone(&self) -> RoundRobin<K, T, P> {
+        RoundRobin {
+            lanes: self.lanes.clone(),
+            turns: self.turns.clone(),
+            len: self.len
+        }
+    }
+}
+
+impl<K: Ord + Clone, T> RoundRobin<K, T> {
+    /// Return an empty scheduler that shares nodes using `Rc`.
+    pub fn new() -> RoundRobin<K, T> {
+        RoundRobin::empty()
+    }
+}
+
+impl<K: Ord + Clone, T, P: SharedPointerKind> Default for RoundRobin<K, T, P> {
+    fn default() -> RoundRobin<K, T, P> {
+        RoundRobin::empty()
+    }
+}
+
+impl<K: Ord + Clone, T, P: SharedPointerKind> RoundRobin<K, T, P> {
+    /// Return an empty scheduler.
+    pub fn empty() -> RoundRobin<K, T, P> {
+        RoundRobin { lanes: RBTree::empty(), turns: BatchedQueue::empty(), len: 0 }
+    }
+
+    /// The total number of items waiting, in all streams.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if no items are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The queue of items waiting in the given stream.
+    pub fn stream(&self, id: &K) -> BatchedQueue<T, P> {
+        match self.lane(id) {
+            Some(lane) => lane.items.clone(),
+            None => BatchedQueue::empty()
+        }
+    }
+
+    /// Return a scheduler with `item` added to the back of stream `id`.
+    pub fn enqueue(&self, id: K, item: T) -> RoundRobin<K, T, P> {
+        let items = self.stream(&id);
+        let turns = if items.is_empty() {
+            Queue::snoc(self.turns.clone(), id.clone())
+        } else {
+            self.turns.clone()
+        };
+        let lanes = self.set_lane(id, Queue::snoc(items, item));
+        RoundRobin { lanes, turns, len: self.len + 1 }
+    }
+
+    /// The stream whose turn it is, and the item `next` would take from it.
+    pub fn peek(&self) -> Option<(&K, &T)> {
+        let id = self.turns.head()?;
+        let item = self.lane(id)?.items.head()?;
+        Some((id, item))
+    }
+
+    /// Take the next item: the oldest one in the stream whose turn it is.
+    /// Returns the stream id, the item, and the scheduler without that item,
+    /// in which the stream has moved to the back of the turn order. Returns
+    /// None if no items are waiting.
+    pub fn next(&self) -> Option<(&K, &T, RoundRobin<K, T, P>)> {
+        let (id, turns) = self.turns.split()?;
+        let (item, rest) = self.lane(id)?.items.split()?;
+        let turns = if rest.is_empty() { turns } else { Queue::snoc(turns, id.clone()) };
+        let next = RoundRobin {
+            lanes: self.set_lane(id.clone(), rest),
+            turns,
+            len: self.len - 1
+        };
+        Some((id, item, next))
+    }
+
+    fn lane(&self, id: &K) -> Option<&Lane<K, T, P>> {
+        self.lanes.find_by(|lane| id.cmp(&lane.id))
+    }
+
+    // Return the lanes with stream `id` holding `items`. An empty stream's
+    // lane is removed.
+    fn set_lane(&self, id: K, items: BatchedQueue<T, P>) -> RBTree<Lane<K, T, P>, P, ById> {
+        if items.is_empty() {
+            self.lanes.remove_by(|lane| id.cmp(&lane.id)).expect("lane is in the index")
+        } else {
+            self.lanes.replace(Lane { id, items })
+        }
+    }
+}
+
+impl<K, T, P> fmt::Debug for RoundRobin<K, T, P>
+    where K: Ord + Clone + fmt::Debug, T: fmt::Debug, P: SharedPointerKind
+{
+    /// Shown as the nonempty streams, in turn order, each with its items.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RoundRobin")?;
+        let mut map = f.debug_map();
+        let mut turns = self.turns.clone();
+        while let Some((id, rest)) = turns.split() {
+            map.entry(id, &self.stream(id));
+            turns = rest;
+        }
+        map.finish()
+    }
+}
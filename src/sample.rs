@@ -0,0 +1,34 @@
+//! Random sampling from sets, enabled by the `rand` feature.
+//!
+//! Each `RBTree` node records the size of its subtree, so both methods pick
+//! positions at random and jump straight to the values there, in O(log n)
+//! time per value drawn.
+
+use rand::{Rng, RngExt};
+use rand::seq::index;
+use compare::Compare;
+use pointer::SharedPointerKind;
+use rbtree::RBTree;
+
+impl<V, P: SharedPointerKind, C: Compare<V>> RBTree<V, P, C> {
+    /// A value chosen uniformly at random, or None if the tree is empty.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&V> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        self.select_nth(rng.random_range(0..len))
+    }
+
+    /// `k` distinct values chosen uniformly at random, in order, or all the
+    /// values if there are no more than `k`.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R, k: usize) -> Vec<&V> {
+        let len = self.len();
+        if k >= len {
+            return self.take_smallest(len);
+        }
+        let mut chosen = index::sample(rng, len, k).into_vec();
+        chosen.sort_unstable();
+        chosen.into_iter().filter_map(|i| self.select_nth(i)).collect()
+    }
+}
@@ -0,0 +1,64 @@
+// Detecting structural sharing, for `same`, `shares_structure_with`, and the
+// node counts.
+
+use std::collections::HashSet;
+
+pub(crate) trait Linked: Sized {
+    // The address of the root node, or None if this structure is empty.
+    fn root_addr(&self) -> Option<usize>;
+
+    // The substructures the root node points to.
+    fn children(&self) -> (Option<&Self>, Option<&Self>);
+}
+
+pub(crate) fn same<T: Linked>(a: &T, b: &T) -> bool {
+    a.root_addr() == b.root_addr()
+}
+
+// Add the addresses of all nodes in `t` to `seen`. Returns true as soon as
+// `stop` is true of a node. Nodes already in `seen` aren't visited again, so
+// this takes time proportional to the number of distinct nodes.
+fn walk<T: Linked, F: Fn(usize) -> bool>(t: &T, seen: &mut HashSet<usize>, stop: F) -> bool {
+    let mut stack = vec![t];
+    while let Some(t) = stack.pop() {
+        if let Some(addr) = t.root_addr() {
+            if stop(addr) {
+                return true;
+            }
+            if seen.insert(addr) {
+                let (a, b) = t.children();
+                stack.extend(a);
+                stack.extend(b);
+            }
+        }
+    }
+    false
+}
+
+pub(crate) fn shares_structure<T: Linked>(a: &[&T], b: &[&T]) -> bool {
+    let mut b_nodes = HashSet::new();
+    for t in b {
+        walk(*t, &mut b_nodes, |_| false);
+    }
+    let mut a_nodes = HashSet::new();
+    a.iter().any(|t| walk(*t, &mut a_nodes, |addr| b_nodes.contains(&addr)))
+}
+
+// The addresses of all the nodes reachable from any of `roots`.
+fn nodes<T: Linked>(roots: &[&T]) -> HashSet<usize> {
+    let mut seen = HashSet::new();
+    for t in roots {
+        walk(*t, &mut seen, |_| false);
+    }
+    seen
+}
+
+pub(crate) fn shared_count<T: Linked>(a: &[&T], b: &[&T]) -> usize {
+    let b_nodes = nodes(b);
+    nodes(a).intersection(&b_nodes).count()
+}
+
+pub(crate) fn unique_count<T: Linked>(a: &[&T], b: &[&T]) -> usize {
+    let b_nodes = nodes(b);
+    nodes(a).difference(&b_nodes).count()
+}
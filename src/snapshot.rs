@@ -0,0 +1,174 @@
+//! Serializing many versions of a structure without losing sharing.
+//!
+//! Persistent structures are cheap to keep many versions of, because the
+//! versions share most of their nodes. A naive serializer writes out each
+//! version separately, so 1000 nearly-identical versions take 1000 times the
+//! space on disk.
+//!
+//! Instead, each structure can `encode` a list of versions as a `Snapshot`: a
+//! table of values plus a table of nodes, where each node refers to its value
+//! and children by index. A node (or value) shared by several versions appears
+//! in the tables only once. `decode` turns a snapshot back into the list of
+//! versions, and the nodes that were shared before are shared again.
+//!
+//! A `Snapshot` is plain data. With the `serde` feature enabled, it implements
+//! `Serialize` and `Deserialize`, so it can be written in any format serde
+//! supports.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A flattened encoding of one or more versions of a structure.
+///
+/// `N` is the node record type, which depends on the kind of structure. Each
+/// node refers to children by index into `nodes`, and always to nodes that
+/// come before it. `roots` has one entry per version; `None` means that
+/// version was empty.
+///
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot<V, N> {
+    pub values: Vec<V>,
+    pub nodes: Vec<N>,
+    pub roots: Vec<Option<usize>>
+}
+
+/// A node of a `List`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ListRecord {
+    pub value: usize,
+    pub tail: Option<usize>
+}
+
+/// A node of a `Tree`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TreeRecord {
+    pub value: usize,
+    pub left: Option<usize>,
+    pub right: Option<usize>
+}
+
+/// A node of an `RBTree`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RBTreeRecord {
+    pub red: bool,
+    pub value: usize,
+    pub left: Option<usize>,
+    pub right: Option<usize>
+}
+
+/// A node of a `LeftistHeap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HeapRecord {
+    pub rank: usize,
+    pub value: usize,
+    pub left: Option<usize>,
+    pub right: Option<usize>
+}
+
+/// The error returned when a snapshot refers to a value or node that doesn't
+/// exist, or to a node that doesn't come before the referring node.
+///
+/// Decoding doesn't check the structure's other invariants (ordering, balance,
+/// and so on). A snapshot produced by `encode` is always valid.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The index of the bad node in `nodes`, or `None` if a root is bad.
+    pub node: Option<usize>
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.node {
+            Some(i) => write!(f, "snapshot node {} has an invalid index", i),
+            None => write!(f, "snapshot root has an invalid index")
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+// Assigns an index to each distinct node and value, keyed by address. The
+// versions being encoded are borrowed for as long as the encoder lives, so an
+// address can't be reused by a different node in the meantime.
+pub(crate) struct Encoder<V, N> {
+    snapshot: Snapshot<V, N>,
+    value_ids: HashMap<usize, usize>,
+    node_ids: HashMap<usize, usize>
+}
+
+impl<V: Clone, N> Encoder<V, N> {
+    pub(crate) fn new() -> Encoder<V, N> {
+        Encoder {
+            snapshot: Snapshot { values: vec![], nodes: vec![], roots: vec![] },
+            value_ids: HashMap::new(),
+            node_ids: HashMap::new()
+        }
+    }
+
+    pub(crate) fn value(&mut self, value: &V) -> usize {
+        let values = &mut self.snapshot.values;
+        *self.value_ids.entry(value as *const V as usize).or_insert_with(|| {
+            values.push(value.clone());
+            values.len() - 1
+        })
+    }
+
+    // Return the index of the node at `addr`, if it has already been encoded.
+    pub(crate) fn lookup<T>(&self, addr: &T) -> Option<usize> {
+        self.node_ids.get(&(addr as *const T as usize)).cloned()
+    }
+
+    pub(crate) fn node<T>(&mut self, addr: &T, record: N) -> usize {
+        self.snapshot.nodes.push(record);
+        let id = self.snapshot.nodes.len() - 1;
+        self.node_ids.insert(addr as *const T as usize, id);
+        id
+    }
+
+    pub(crate) fn root(&mut self, id: Option<usize>) {
+        self.snapshot.roots.push(id);
+    }
+
+    pub(crate) fn finish(self) -> Snapshot<V, N> {
+        self.snapshot
+    }
+}
+
+impl<V, N> Snapshot<V, N> {
+    // Check that a child index refers to an earlier node.
+    pub(crate) fn check_child(&self, node: usize, child: Option<usize>) -> Result<(), DecodeError> {
+        match child {
+            Some(c) if c >= node => Err(DecodeError { node: Some(node) }),
+            _ => Ok(())
+        }
+    }
+
+    pub(crate) fn check_value(&self, node: usize, value: usize) -> Result<(), DecodeError> {
+        if value < self.values.len() {
+            Ok(())
+        } else {
+            Err(DecodeError { node: Some(node) })
+        }
+    }
+
+    pub(crate) fn check_root(&self, root: Option<usize>) -> Result<(), DecodeError> {
+        match root {
+            Some(r) if r >= self.nodes.len() => Err(DecodeError { node: None }),
+            _ => Ok(())
+        }
+    }
+}
+
+// Look up an already-decoded node, or return an empty structure for `None`.
+pub(crate) fn decoded<T: Clone + Default>(decoded: &[T], id: Option<usize>) -> T {
+    id.map_or_else(T::default, |i| decoded[i].clone())
+}
@@ -0,0 +1,55 @@
+//! Sorting any iterator into a persistent list.
+//!
+//! These are shortcuts for the common case of wanting the sorted result as a
+//! `List` rather than a `Vec`, plus `select_nth` for when only one position
+//! of the sorted order is needed. For adding values a few at a time and
+//! sorting on demand, see the `mergesort` module.
+
+use heap::LeftistHeap;
+use list::List;
+use rbtree::RBTree;
+use traits::{Heap, Set};
+
+/// Return a list of all the values from `iter`, in ascending order,
+/// including duplicates. Equal values may come out in any order.
+///
+/// This puts the values in a leftist heap and takes them out again, in
+/// O(n log n) time.
+///
+pub fn sort_via_heap<T, I>(iter: I) -> List<T>
+    where T: Ord + Clone, I: IntoIterator<Item=T>
+{
+    let mut heap = iter.into_iter().fold(LeftistHeap::new(), |h, v| h.insert(v));
+    let mut sorted = vec![];
+    while let Some(v) = heap.pop() {
+        sorted.push(v);
+    }
+    sorted.into_iter().collect()
+}
+
+/// Return the `k`th smallest value from `iter`, counting from 0, or None if
+/// there are `k` values or fewer. Duplicates count separately, so this is the
+/// value that would be at index `k` after sorting.
+///
+/// This uses quickselect, in O(n) time on average. For a list, the method
+/// `List::select_nth` does the same without taking ownership of the items.
+///
+pub fn select_nth<T: Ord, I: IntoIterator<Item=T>>(iter: I, k: usize) -> Option<T> {
+    let mut values: Vec<T> = iter.into_iter().collect();
+    if k >= values.len() {
+        return None;
+    }
+    values.select_nth_unstable(k);
+    Some(values.swap_remove(k))
+}
+
+/// Return a list of the distinct values from `iter`, in ascending order.
+/// Where several values are equal, the first one is kept.
+///
+/// This puts the values in a red-black tree, in O(n log n) time.
+///
+pub fn sort_unique<T, I>(iter: I) -> List<T>
+    where T: Ord + Clone, I: IntoIterator<Item=T>
+{
+    iter.into_iter().fold(RBTree::new(), |t, v| t.plus(v)).into_iter().collect()
+}
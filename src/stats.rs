@@ -0,0 +1,75 @@
+//! Statistics about the internal shape of a structure.
+//!
+//! The performance of a persistent structure depends on its shape: how deep
+//! its trees are, how its heaps are ranked, how a queue's items are divided
+//! between its two lists. Each structure's `stats` method reports these, for
+//! investigating performance without poking at private fields.
+
+/// The shape of a binary tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShapeStats {
+    /// `depths[d]` is the number of nodes at depth `d`. The root is at depth
+    /// 0. Empty for an empty tree.
+    pub depths: Vec<usize>
+}
+
+impl ShapeStats {
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.depths.iter().sum()
+    }
+
+    /// Return true if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.depths.is_empty()
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf.
+    pub fn height(&self) -> usize {
+        self.depths.len()
+    }
+
+    /// The average depth of a node, or 0 for an empty tree. Searching for a
+    /// value that's present takes `mean_depth() + 1` steps on average.
+    pub fn mean_depth(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let total: usize = self.depths.iter().enumerate().map(|(d, &n)| d * n).sum();
+        total as f64 / self.len() as f64
+    }
+
+    pub(crate) fn add(&mut self, depth: usize) {
+        if self.depths.len() <= depth {
+            self.depths.resize(depth + 1, 0);
+        }
+        self.depths[depth] += 1;
+    }
+}
+
+/// The shape and coloring of a red-black tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RBTreeStats {
+    pub shape: ShapeStats,
+    pub red: usize,
+    pub black: usize
+}
+
+/// The shape and ranks of a leftist heap.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub shape: ShapeStats,
+    /// `ranks[r]` is the number of nodes with rank `r`. Every node has rank
+    /// at least 1, so `ranks[0]` is always 0.
+    pub ranks: Vec<usize>
+}
+
+/// How a batched queue's items are divided between its lists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// The number of items in the front list, which are popped in order.
+    pub front: usize,
+    /// The number of items in the back list, which will be reversed (one
+    /// step per item) when the front list runs out.
+    pub back: usize
+}
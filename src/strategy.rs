@@ -0,0 +1,73 @@
+//! `proptest` strategies for every structure, enabled by the `proptest` feature.
+//!
+//! Each function takes a strategy for the elements and a size range, like
+//! `proptest::collection::vec`. The structures are built with their ordinary
+//! operations, so the values they generate, and the values they shrink to,
+//! always satisfy the structure's invariants.
+
+use proptest::collection::{vec, SizeRange};
+use proptest::strategy::Strategy;
+use arbitrary::{heap_from_vec, queue_from_vecs, rbtree_from_vec, tree_from_vec};
+use compare::Compare;
+use heap::LeftistHeap;
+use list::List;
+use pointer::SharedPointerKind;
+use queue::BatchedQueue;
+use rbtree::RBTree;
+use stream::Stream;
+use tree::Tree;
+
+/// Generate lists with lengths in `size`.
+pub fn list<S, P>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=List<S::Value, P>>
+    where S: Strategy, P: SharedPointerKind
+{
+    vec(element, size).prop_map(|v| v.into_iter().collect())
+}
+
+/// Generate unbalanced trees by inserting elements in random order.
+///
+/// The number of insertions is in `size`. Duplicates are discarded, so the
+/// resulting tree can be smaller.
+///
+pub fn tree<S, P>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=Tree<S::Value, P>>
+    where S: Strategy, S::Value: Ord, P: SharedPointerKind
+{
+    vec(element, size).prop_map(tree_from_vec)
+}
+
+/// Generate red-black trees by inserting elements in random order.
+///
+/// The number of insertions is in `size`. Duplicates are discarded, so the
+/// resulting tree can be smaller.
+///
+pub fn rbtree<S, P, C>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=RBTree<S::Value, P, C>>
+    where S: Strategy, P: SharedPointerKind, C: Compare<S::Value>
+{
+    vec(element, size).prop_map(rbtree_from_vec)
+}
+
+/// Generate heaps with sizes in `size`.
+pub fn heap<S, P>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=LeftistHeap<S::Value, P>>
+    where S: Strategy, S::Value: Ord, P: SharedPointerKind
+{
+    vec(element, size).prop_map(heap_from_vec)
+}
+
+/// Generate queues with lengths in `size`.
+///
+/// The generated queues have a variety of internal layouts, not just the one
+/// you get by pushing all the elements onto an empty queue.
+///
+pub fn queue<S, P>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=BatchedQueue<S::Value, P>>
+    where S: Strategy + Clone, P: SharedPointerKind
+{
+    (vec(element.clone(), 0..8), vec(element, size))
+        .prop_map(|(discarded, items)| queue_from_vecs(discarded, items))
+}
+
+/// Generate fully evaluated streams with lengths in `size`.
+pub fn stream<S>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value=Stream<S::Value>>
+    where S: Strategy, S::Value: Send + Sync + 'static
+{
+    vec(element, size).prop_map(|v| v.into_iter().collect())
+}
@@ -0,0 +1,435 @@
+//! 4.2 Streams
+//!
+//! A stream is a linked list in which every cell is a suspension, so the
+//! elements are computed only as they are demanded. Like everything built on
+//! `Lazy`, streams are thread-safe: the cells are shared using `Arc`.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::Arc;
+use lazy::Lazy;
+
+enum StreamCell<T> {
+    Nil,
+    Cons(T, Stream<T>)
+}
+
+use self::StreamCell::*;
+
+/// A lazy persistent list.
+pub struct Stream<T>(Arc<Lazy<StreamCell<T>>>);
+
+impl<T> Clone for Stream<T> {
+    fn clone(&self) -> Stream<T> {
+        Stream(self.0.clone())
+    }
+}
+
+// Dropping a long forced stream would otherwise recurse once per cell.
+// Instead, move each cell's tail out while nothing else shares it.
+impl<T> Drop for Stream<T> {
+    fn drop(&mut self) {
+        let mut cell = Arc::get_mut(&mut self.0).and_then(Lazy::take);
+        while let Some(Cons(_, mut tail)) = cell {
+            cell = Arc::get_mut(&mut tail.0).and_then(Lazy::take);
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Stream<T> {
+    /// Return an empty stream.
+    pub fn empty() -> Stream<T> {
+        Stream(Arc::new(Lazy::from_value(Nil)))
+    }
+
+    /// Return a stream with `head` as its first element, followed by all the
+    /// elements of `tail`.
+    pub fn cons(head: T, tail: Stream<T>) -> Stream<T> {
+        Stream(Arc::new(Lazy::from_value(Cons(head, tail))))
+    }
+
+    /// Return a stream whose first cell is computed by `f` the first time it
+    /// is needed. `f` returns `None` to indicate an empty stream.
+    pub fn delay<F>(f: F) -> Stream<T>
+        where F: FnOnce() -> Option<(T, Stream<T>)> + Send + 'static
+    {
+        Stream(Arc::new(Lazy::new(move || match f() {
+            None => Nil,
+            Some((head, tail)) => Cons(head, tail)
+        })))
+    }
+
+    /// Return true if this stream is empty. This forces the first cell.
+    pub fn is_empty(&self) -> bool {
+        self.split().is_none()
+    }
+
+    /// Return references to the first element and the rest of the stream, or
+    /// `None` if the stream is empty. This forces the first cell.
+    pub fn split(&self) -> Option<(&T, &Stream<T>)> {
+        match *self.0.force() {
+            Nil => None,
+            Cons(ref head, ref tail) => Some((head, tail))
+        }
+    }
+
+    /// Return the first element of this stream, if any.
+    pub fn head(&self) -> Option<&T> {
+        self.split().map(|(h, _)| h)
+    }
+
+    /// Return everything but the first element of this stream, if any.
+    pub fn tail(&self) -> Option<&Stream<T>> {
+        self.split().map(|(_, t)| t)
+    }
+
+    /// Return true if the first cell of this stream has already been computed.
+    ///
+    /// This never forces anything, so it's handy for checking how much of a
+    /// stream has been evaluated.
+    ///
+    pub fn is_forced(&self) -> bool {
+        self.0.is_forced()
+    }
+
+    /// Return an iterator over references to the elements of this stream,
+    /// forcing each cell as it goes.
+    pub fn iter(&self) -> StreamIter<'_, T> {
+        StreamIter(self)
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Stream<T> {
+    /// Lazily concatenate two streams.
+    ///
+    /// This returns in constant time. Each cell of the result takes constant
+    /// time to force.
+    ///
+    pub fn append(&self, other: Stream<T>) -> Stream<T> {
+        let s = self.clone();
+        Stream::delay(move || match s.split() {
+            None => other.split().map(|(h, t)| (h.clone(), t.clone())),
+            Some((h, t)) => Some((h.clone(), t.append(other)))
+        })
+    }
+
+    /// Lazily return a stream of the first `n` elements of this stream.
+    pub fn take(&self, n: usize) -> Stream<T> {
+        let s = self.clone();
+        Stream::delay(move || {
+            if n == 0 {
+                return None;
+            }
+            s.split().map(|(h, t)| (h.clone(), t.take(n - 1)))
+        })
+    }
+
+    /// Return a stream of everything after the first `n` elements of this
+    /// stream.
+    ///
+    /// This is monolithic: forcing the first cell of the result does all the
+    /// work of skipping `n` elements at once.
+    ///
+    pub fn drop(&self, n: usize) -> Stream<T> {
+        let s = self.clone();
+        Stream::delay(move || {
+            let mut rest = &s;
+            for _ in 0..n {
+                match rest.tail() {
+                    None => return None,
+                    Some(t) => rest = t
+                }
+            }
+            rest.split().map(|(h, t)| (h.clone(), t.clone()))
+        })
+    }
+
+    /// Return this stream reversed.
+    ///
+    /// This is monolithic: forcing the first cell of the result forces the
+    /// whole input stream and builds the entire result.
+    ///
+    pub fn reverse(&self) -> Stream<T> {
+        let s = self.clone();
+        Stream::delay(move || {
+            let mut result = Stream::empty();
+            for v in s.iter() {
+                result = Stream::cons(v.clone(), result);
+            }
+            result.split().map(|(h, t)| (h.clone(), t.clone()))
+        })
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Stream<T> {
+    /// Lazily split this stream into groups of neighboring elements, starting
+    /// a new group wherever `same(previous, next)` is false.
+    ///
+    /// Forcing a cell of the result forces the whole group it holds, plus the
+    /// first element of the next group, to see where the group ends.
+    ///
+    pub fn group_by<F>(&self, same: F) -> Stream<Stream<T>>
+        where F: Fn(&T, &T) -> bool + Send + Sync + 'static
+    {
+        group_by_arc(self.clone(), Arc::new(same))
+    }
+
+    /// Lazily split this stream into groups of `size` elements. The last group
+    /// has fewer if the stream runs out first. Each group is itself lazy.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    pub fn chunks(&self, size: usize) -> Stream<Stream<T>> {
+        assert!(size != 0, "chunk size must be nonzero");
+        let s = self.clone();
+        Stream::delay(move || {
+            if s.is_empty() {
+                return None;
+            }
+            Some((s.take(size), s.drop(size).chunks(size)))
+        })
+    }
+
+    /// Lazily pair each element with the one after it.
+    pub fn pairs(&self) -> Stream<(T, T)> {
+        let s = self.clone();
+        Stream::delay(move || {
+            let (a, rest) = s.split()?;
+            let b = rest.head()?;
+            Some(((a.clone(), b.clone()), rest.pairs()))
+        })
+    }
+
+    /// Lazily produce every run of `size` neighboring elements, in order.
+    /// Each window is a lazy prefix of a suffix of this stream. Forcing a
+    /// cell of the result forces `size` elements, to see whether there are
+    /// enough for a window.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    pub fn windows(&self, size: usize) -> Stream<Stream<T>> {
+        assert!(size != 0, "window size must be nonzero");
+        let s = self.clone();
+        Stream::delay(move || {
+            if s.drop(size - 1).is_empty() {
+                return None;
+            }
+            Some((s.take(size), s.tail()?.windows(size)))
+        })
+    }
+
+    /// Lazily return the same elements as this stream, but force `n` elements
+    /// ahead of the ones demanded: forcing the first cell of the result
+    /// forces the first `n + 1` cells of this stream, and each cell after
+    /// that forces one more.
+    ///
+    /// This is for pipelines where each element is expensive to compute, so
+    /// that the work is done in a batch up front instead of a little at a
+    /// time, each time the consumer asks for an element.
+    ///
+    pub fn buffered(&self, n: usize) -> Stream<T> {
+        buffered_ahead(self.clone(), self.drop(n))
+    }
+}
+
+// The elements of `s`, where forcing each cell also forces the matching cell
+// of `ahead`, a suffix of `s`.
+fn buffered_ahead<T: Clone + Send + Sync + 'static>(s: Stream<T>, ahead: Stream<T>) -> Stream<T> {
+    Stream::delay(move || {
+        let ahead = ahead.tail().cloned().unwrap_or_else(Stream::empty);
+        let (h, t) = s.split()?;
+        Some((h.clone(), buffered_ahead(t.clone(), ahead)))
+    })
+}
+
+impl<T: Clone + Send + Sync + 'static> Stream<T> {
+    /// Lazily return the longest prefix of this stream whose elements all
+    /// satisfy `pred`. Forcing a cell of the result forces one cell of this
+    /// stream.
+    pub fn take_while<F>(&self, pred: F) -> Stream<T>
+        where F: Fn(&T) -> bool + Send + Sync + 'static
+    {
+        take_while_arc(self.clone(), Arc::new(pred))
+    }
+
+    /// Return what's left of this stream after the longest prefix whose
+    /// elements all satisfy `pred`.
+    ///
+    /// This returns in constant time, but forcing the first cell of the result
+    /// forces the whole prefix at once. After that, the result's cells are
+    /// this stream's.
+    ///
+    pub fn drop_while<F>(&self, pred: F) -> Stream<T>
+        where F: Fn(&T) -> bool + Send + 'static
+    {
+        let s = self.clone();
+        Stream::delay(move || {
+            let mut rest = &s;
+            while let Some((h, t)) = rest.split() {
+                if !pred(h) {
+                    return Some((h.clone(), t.clone()));
+                }
+                rest = t;
+            }
+            None
+        })
+    }
+
+    /// Lazily return the elements of this stream that satisfy `pred`.
+    ///
+    /// Forcing a cell of the result forces this stream up to the next element
+    /// that satisfies `pred`, and no further.
+    ///
+    pub fn filter<F>(&self, pred: F) -> Stream<T>
+        where F: Fn(&T) -> bool + Send + Sync + 'static
+    {
+        filter_arc(self.clone(), Arc::new(pred))
+    }
+
+    /// Lazily concatenate the streams `f` returns for each element of this
+    /// stream.
+    ///
+    /// Forcing a cell of the result calls `f` only as often as it takes to
+    /// get a nonempty stream, and forces only the first cell of that stream.
+    ///
+    pub fn flat_map<U, F>(&self, f: F) -> Stream<U>
+        where U: Clone + Send + Sync + 'static, F: Fn(&T) -> Stream<U> + Send + Sync + 'static
+    {
+        flat_map_arc(self.clone(), Arc::new(f))
+    }
+}
+
+fn take_while_arc<T, F>(s: Stream<T>, pred: Arc<F>) -> Stream<T>
+    where T: Clone + Send + Sync + 'static, F: Fn(&T) -> bool + Send + Sync + 'static
+{
+    Stream::delay(move || {
+        let (h, t) = s.split()?;
+        if !pred(h) {
+            return None;
+        }
+        Some((h.clone(), take_while_arc(t.clone(), pred.clone())))
+    })
+}
+
+fn filter_arc<T, F>(s: Stream<T>, pred: Arc<F>) -> Stream<T>
+    where T: Clone + Send + Sync + 'static, F: Fn(&T) -> bool + Send + Sync + 'static
+{
+    Stream::delay(move || {
+        let mut rest = &s;
+        loop {
+            let (h, t) = rest.split()?;
+            if pred(h) {
+                return Some((h.clone(), filter_arc(t.clone(), pred)));
+            }
+            rest = t;
+        }
+    })
+}
+
+fn flat_map_arc<T, U, F>(s: Stream<T>, f: Arc<F>) -> Stream<U>
+    where T: Clone + Send + Sync + 'static, U: Clone + Send + Sync + 'static,
+          F: Fn(&T) -> Stream<U> + Send + Sync + 'static
+{
+    Stream::delay(move || {
+        let mut rest = &s;
+        loop {
+            let (h, t) = rest.split()?;
+            let inner = f(h);
+            if let Some((first, more)) = inner.split() {
+                return Some((first.clone(), more.append(flat_map_arc(t.clone(), f))));
+            }
+            rest = t;
+        }
+    })
+}
+
+fn group_by_arc<T, F>(s: Stream<T>, same: Arc<F>) -> Stream<Stream<T>>
+    where T: Clone + Send + Sync + 'static, F: Fn(&T, &T) -> bool + Send + Sync + 'static
+{
+    Stream::delay(move || {
+        let (first, mut rest) = s.split()?;
+        let mut group = vec![first.clone()];
+        while let Some((next, after)) = rest.split() {
+            if !same(&group[group.len() - 1], next) {
+                break;
+            }
+            group.push(next.clone());
+            rest = after;
+        }
+        Some((group.into_iter().collect(), group_by_arc(rest.clone(), same)))
+    })
+}
+
+/// Lazily merge two sorted streams into one sorted stream. Where elements are
+/// equal, those from `a` come first.
+///
+/// This returns in constant time, and each cell of the result takes constant
+/// time to force. Once one stream runs out, the result's cells point at the
+/// rest of the other.
+///
+pub fn merge_sorted<T: Ord + Clone + Send + Sync + 'static>(a: &Stream<T>, b: &Stream<T>) -> Stream<T> {
+    let (a, b) = (a.clone(), b.clone());
+    Stream::delay(move || match (a.split(), b.split()) {
+        (None, _) => b.split().map(|(y, yt)| (y.clone(), yt.clone())),
+        (_, None) => a.split().map(|(x, xt)| (x.clone(), xt.clone())),
+        (Some((x, xt)), Some((y, yt))) => {
+            if y < x {
+                Some((y.clone(), merge_sorted(&a, yt)))
+            } else {
+                Some((x.clone(), merge_sorted(xt, &b)))
+            }
+        }
+    })
+}
+
+/// An iterator over references to the elements of a stream.
+pub struct StreamIter<'a, T: 'a>(&'a Stream<T>);
+
+impl<'a, T: Send + Sync + 'static> Iterator for StreamIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.split().map(|(h, t)| {
+            self.0 = t;
+            h
+        })
+    }
+}
+
+impl<'a, T: fmt::Debug + Send + Sync + 'static> fmt::Debug for StreamIter<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("StreamIter").field(self.0).finish()
+    }
+}
+
+impl<T: Send + Sync + 'static> FromIterator<T> for Stream<T> {
+    fn from_iter<Iterable: IntoIterator<Item=T>>(iterator: Iterable) -> Stream<T> {
+        let v: Vec<_> = iterator.into_iter().collect();
+        let mut result = Stream::empty();
+        for v in v.into_iter().rev() {
+            result = Stream::cons(v, result);
+        }
+        result
+    }
+}
+
+impl<T: fmt::Debug + Send + Sync + 'static> fmt::Debug for Stream<T> {
+    /// Only the part of the stream that has already been evaluated is shown.
+    /// Formatting a stream never forces anything.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Stream")?;
+        let mut entries = f.debug_list();
+        let mut p = self;
+        while p.is_forced() {
+            match p.split() {
+                None => return entries.finish(),
+                Some((first, rest)) => {
+                    entries.entry(first);
+                    p = rest;
+                }
+            }
+        }
+        entries.entry(&format_args!("..."));
+        entries.finish()
+    }
+}
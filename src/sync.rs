@@ -0,0 +1,40 @@
+//! Thread-safe versions of the data structures.
+//!
+//! The types in this module are exactly the same as the ones elsewhere in the
+//! crate, except that nodes are shared using `Arc` rather than `Rc`. That
+//! makes them `Send` and `Sync` (when the item type is), so persistent values
+//! can be handed off to other threads. The cost is atomic reference counting
+//! on every node copy.
+//!
+//! The lazy structures, `Stream` and the `MergeSort` types, are always
+//! thread-safe, so they are simply re-exported here.
+
+use compare::Natural;
+use pointer::ArcKind;
+
+pub type List<T> = ::list::List<T, ArcKind>;
+pub type Tree<V> = ::tree::Tree<V, ArcKind>;
+pub type TreeZipper<V> = ::tree::TreeZipper<V, ArcKind>;
+pub type RBTree<V, C = Natural> = ::rbtree::RBTree<V, ArcKind, C>;
+pub type RBTreeZipper<V, C = Natural> = ::rbtree::RBTreeZipper<V, ArcKind, C>;
+pub type RBMap<K, V> = ::rbmap::RBMap<K, V, ArcKind>;
+pub type HashSet<T> = ::hashset::HashSet<T, ArcKind>;
+pub type IntMap<V> = ::intmap::IntMap<V, ArcKind>;
+pub type MultiMap<K, V> = ::multimap::MultiMap<K, V, ArcKind>;
+pub type Counter<T> = ::counter::Counter<T, ArcKind>;
+pub type Graph<N, E> = ::graph::Graph<N, E, ArcKind>;
+pub type LeftistHeap<V> = ::heap::LeftistHeap<V, ArcKind>;
+pub type PairingHeap<V> = ::pairing::PairingHeap<V, ArcKind>;
+pub type HandleHeap<K> = ::pairing::HandleHeap<K, ArcKind>;
+pub type MinMaxHeap<V> = ::minmax::MinMaxHeap<V, ArcKind>;
+pub type TopK<T> = ::topk::TopK<T, ArcKind>;
+pub type BatchedQueue<T> = ::queue::BatchedQueue<T, ArcKind>;
+pub type IndexedQueue<K, V> = ::indexedqueue::IndexedQueue<K, V, ArcKind>;
+pub type RoundRobin<K, T> = ::roundrobin::RoundRobin<K, T, ArcKind>;
+pub type DenseBinary = ::numeric::DenseBinary<ArcKind>;
+pub type SparseBinary = ::numeric::SparseBinary<ArcKind>;
+pub type SkewBinary = ::numeric::SkewBinary<ArcKind>;
+pub type ZerolessBinary = ::numeric::ZerolessBinary<ArcKind>;
+
+pub use mergesort::{MergeSort, ScheduledMergeSort};
+pub use stream::Stream;
@@ -0,0 +1,451 @@
+//! Differential testing against the standard collections.
+//!
+//! A `Spec` pairs a kind of persistent structure with a reference model from
+//! `std` that is known to be right: a `Set` is checked against a `BTreeSet`, a
+//! `Queue` against a `VecDeque`, a `Heap` against a `BinaryHeap`. `run` feeds
+//! a sequence of commands to both and compares everything that can be
+//! observed along the way.
+//!
+//! Since the structures are persistent, a command can also go back to any
+//! earlier version and carry on from there. At the end, every version is
+//! compared against its model once more, which catches operations that damage
+//! versions they share nodes with.
+//!
+//! The specs are generic, so they work on any implementation of the traits,
+//! not just the ones in this crate. For example, using `random_commands` to
+//! make up the commands:
+//!
+//! ```ignore
+//! let commands = random_commands(seed, 1000, SetOp::random);
+//! run::<SetSpec<RBTree<u64>>>(&commands).unwrap();
+//! ```
+//!
+//! For small sizes, it's also possible to test every case: `all_trees`,
+//! `all_rbtrees`, and `all_queues` list every tree shape, every valid
+//! red-black tree, and every queue layout up to a given size.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+use pointer::SharedPointerKind;
+use queue::BatchedQueue;
+use rbtree::{self, RBTree};
+use traits::{Heap, Queue, Set};
+use tree::{cons_tree, Tree};
+
+/// A structure under test, paired with the reference model it should agree
+/// with.
+pub trait Spec {
+    /// The structure under test.
+    type Subject: Clone;
+    /// The reference model.
+    type Model: Clone;
+    /// An operation that can be applied to both.
+    type Op: fmt::Debug;
+
+    /// Return an empty structure and an empty model.
+    fn empty() -> (Self::Subject, Self::Model);
+
+    /// Apply `op` to both, returning the new versions, or a description of
+    /// how the subject's behavior differed from the model's.
+    fn step(subject: &Self::Subject, model: &Self::Model, op: &Self::Op)
+            -> Result<(Self::Subject, Self::Model), String>;
+
+    /// Check that the subject has the same contents as the model.
+    fn check(subject: &Self::Subject, model: &Self::Model) -> Result<(), String>;
+}
+
+/// One step of a test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command<Op> {
+    /// Apply an operation to the current version, producing a new version.
+    Apply(Op),
+    /// Make an earlier version current again. Versions are numbered from 0
+    /// (the empty structure) in the order they were made, and the index is
+    /// taken modulo the number of versions so far, so any value is valid.
+    Revisit(usize)
+}
+
+/// The error returned by `run` when the structure and the model disagree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// The index of the command that failed, or `None` if the failure was
+    /// found while rechecking all versions at the end.
+    pub step: Option<usize>,
+    pub message: String
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.step {
+            Some(i) => write!(f, "command {}: {}", i, self.message),
+            None => write!(f, "final check: {}", self.message)
+        }
+    }
+}
+
+impl Error for Failure {}
+
+/// Run `commands` against both the structure and the model of spec `S`.
+pub fn run<S: Spec>(commands: &[Command<S::Op>]) -> Result<(), Failure> {
+    let mut versions = vec![S::empty()];
+    let mut current = 0;
+    for (i, command) in commands.iter().enumerate() {
+        match *command {
+            Command::Revisit(k) => current = k % versions.len(),
+            Command::Apply(ref op) => {
+                let next = {
+                    let (ref subject, ref model) = versions[current];
+                    S::step(subject, model, op).map_err(|message| Failure {
+                        step: Some(i),
+                        message: format!("{:?}: {}", op, message)
+                    })?
+                };
+                versions.push(next);
+                current = versions.len() - 1;
+            }
+        }
+    }
+    for (i, (subject, model)) in versions.iter().enumerate() {
+        S::check(subject, model).map_err(|message| Failure {
+            step: None,
+            message: format!("version {}: {}", i, message)
+        })?;
+    }
+    Ok(())
+}
+
+// SplitMix64, which is plenty random for picking test operations.
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Make `len` pseudo-random commands from `seed`. About one in eight is a
+/// `Revisit`; the rest are made by passing random bits to `make_op`.
+pub fn random_commands<Op, F>(seed: u64, len: usize, mut make_op: F) -> Vec<Command<Op>>
+    where F: FnMut(u64) -> Op
+{
+    let mut state = seed;
+    (0..len).map(|_| {
+        let bits = next_random(&mut state);
+        if bits.is_multiple_of(8) {
+            Command::Revisit((bits >> 3) as usize)
+        } else {
+            Command::Apply(make_op(next_random(&mut state)))
+        }
+    }).collect()
+}
+
+// Random values are kept small so that duplicates are common.
+fn small_value(bits: u64) -> u64 {
+    (bits >> 8) % 64
+}
+
+fn compare_sequences<T: PartialEq + fmt::Debug>(actual: &[T], expected: &[T]) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("contents are {:?}, expected {:?}", actual, expected))
+    }
+}
+
+fn compare_results<T: PartialEq + fmt::Debug>(what: &str, actual: T, expected: T) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{} returned {:?}, expected {:?}", what, actual, expected))
+    }
+}
+
+/// Operations on sets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetOp<T> {
+    Plus(T),
+    Contains(T)
+}
+
+impl SetOp<u64> {
+    /// Make an operation from random bits, for `random_commands`.
+    pub fn random(bits: u64) -> SetOp<u64> {
+        if bits & 1 == 0 {
+            SetOp::Plus(small_value(bits))
+        } else {
+            SetOp::Contains(small_value(bits))
+        }
+    }
+}
+
+/// Checks a `Set` against a `BTreeSet`.
+pub struct SetSpec<S>(PhantomData<S>);
+
+impl<S, T> Spec for SetSpec<S>
+    where S: Set<Item=T> + Clone + IntoIterator<Item=T>, T: Ord + Clone + fmt::Debug
+{
+    type Subject = S;
+    type Model = BTreeSet<T>;
+    type Op = SetOp<T>;
+
+    fn empty() -> (S, BTreeSet<T>) {
+        (S::empty(), BTreeSet::new())
+    }
+
+    fn step(subject: &S, model: &BTreeSet<T>, op: &SetOp<T>) -> Result<(S, BTreeSet<T>), String> {
+        match *op {
+            SetOp::Plus(ref v) => {
+                let mut model = model.clone();
+                model.insert(v.clone());
+                Ok((subject.plus(v.clone()), model))
+            }
+            SetOp::Contains(ref v) => {
+                compare_results("contains", subject.contains(v), model.contains(v))?;
+                Ok((subject.clone(), model.clone()))
+            }
+        }
+    }
+
+    // Not every set iterates in order (a `HashSet` doesn't), so the values
+    // are sorted before comparing.
+    fn check(subject: &S, model: &BTreeSet<T>) -> Result<(), String> {
+        let mut actual: Vec<T> = subject.clone().into_iter().collect();
+        actual.sort();
+        let expected: Vec<T> = model.iter().cloned().collect();
+        compare_sequences(&actual, &expected)
+    }
+}
+
+/// Operations on queues.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueueOp<T> {
+    Snoc(T),
+    Head,
+    Tail
+}
+
+impl QueueOp<u64> {
+    /// Make an operation from random bits, for `random_commands`.
+    pub fn random(bits: u64) -> QueueOp<u64> {
+        match bits % 4 {
+            0 | 1 => QueueOp::Snoc(small_value(bits)),
+            2 => QueueOp::Head,
+            _ => QueueOp::Tail
+        }
+    }
+}
+
+/// Checks a `Queue` against a `VecDeque`.
+pub struct QueueSpec<Q>(PhantomData<Q>);
+
+impl<Q, T> Spec for QueueSpec<Q>
+    where Q: Queue<Item=T> + Clone, T: PartialEq + Clone + fmt::Debug
+{
+    type Subject = Q;
+    type Model = VecDeque<T>;
+    type Op = QueueOp<T>;
+
+    fn empty() -> (Q, VecDeque<T>) {
+        (Q::empty(), VecDeque::new())
+    }
+
+    fn step(subject: &Q, model: &VecDeque<T>, op: &QueueOp<T>) -> Result<(Q, VecDeque<T>), String> {
+        match *op {
+            QueueOp::Snoc(ref v) => {
+                let mut model = model.clone();
+                model.push_back(v.clone());
+                Ok((Q::snoc(subject.clone(), v.clone()), model))
+            }
+            QueueOp::Head => {
+                compare_results("head", subject.head(), model.front())?;
+                compare_results("is_empty", subject.is_empty(), model.is_empty())?;
+                Ok((subject.clone(), model.clone()))
+            }
+            QueueOp::Tail => {
+                let mut model = model.clone();
+                let expected = model.pop_front();
+                match subject.split() {
+                    None => {
+                        compare_results("split", None, expected.as_ref())?;
+                        Ok((subject.clone(), model))
+                    }
+                    Some((first, rest)) => {
+                        compare_results("split", Some(first), expected.as_ref())?;
+                        Ok((rest, model))
+                    }
+                }
+            }
+        }
+    }
+
+    fn check(subject: &Q, model: &VecDeque<T>) -> Result<(), String> {
+        let mut actual = vec![];
+        let mut q = subject.clone();
+        while let Some((first, rest)) = q.split().map(|(first, rest)| (first.clone(), rest)) {
+            actual.push(first);
+            q = rest;
+        }
+        let expected: Vec<T> = model.iter().cloned().collect();
+        compare_sequences(&actual, &expected)
+    }
+}
+
+/// Operations on heaps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeapOp<T> {
+    Insert(T),
+    Min,
+    WithoutMin,
+    /// Merge with a heap containing these values.
+    Merge(Vec<T>)
+}
+
+impl HeapOp<u64> {
+    /// Make an operation from random bits, for `random_commands`.
+    pub fn random(bits: u64) -> HeapOp<u64> {
+        match bits % 8 {
+            0..=3 => HeapOp::Insert(small_value(bits)),
+            4 => HeapOp::Min,
+            5 | 6 => HeapOp::WithoutMin,
+            _ => HeapOp::Merge(vec![small_value(bits), small_value(bits >> 6), small_value(bits >> 12)])
+        }
+    }
+}
+
+/// Checks a `Heap` against a `BinaryHeap`.
+pub struct HeapSpec<H>(PhantomData<H>);
+
+impl<H, T> Spec for HeapSpec<H>
+    where H: Heap<Item=T> + Clone, T: Ord + Clone + fmt::Debug
+{
+    type Subject = H;
+    type Model = BinaryHeap<Reverse<T>>;
+    type Op = HeapOp<T>;
+
+    fn empty() -> (H, BinaryHeap<Reverse<T>>) {
+        (H::empty(), BinaryHeap::new())
+    }
+
+    fn step(subject: &H, model: &BinaryHeap<Reverse<T>>, op: &HeapOp<T>)
+            -> Result<(H, BinaryHeap<Reverse<T>>), String> {
+        let mut model = model.clone();
+        match *op {
+            HeapOp::Insert(ref v) => {
+                model.push(Reverse(v.clone()));
+                Ok((subject.insert(v.clone()), model))
+            }
+            HeapOp::Min => {
+                compare_results("min", subject.min(), model.peek().map(|r| &r.0))?;
+                compare_results("is_empty", subject.is_empty(), model.is_empty())?;
+                Ok((subject.clone(), model))
+            }
+            HeapOp::WithoutMin => {
+                model.pop();
+                Ok((subject.without_min(), model))
+            }
+            HeapOp::Merge(ref values) => {
+                let mut other = H::empty();
+                for v in values {
+                    other = other.insert(v.clone());
+                    model.push(Reverse(v.clone()));
+                }
+                Ok((H::merge(subject.clone(), other), model))
+            }
+        }
+    }
+
+    fn check(subject: &H, model: &BinaryHeap<Reverse<T>>) -> Result<(), String> {
+        let mut actual = vec![];
+        let mut h = subject.clone();
+        while let Some(v) = h.min().cloned() {
+            actual.push(v);
+            h = h.without_min();
+        }
+        let expected: Vec<T> = model.clone().into_sorted_vec().into_iter().rev().map(|r| r.0).collect();
+        compare_sequences(&actual, &expected)
+    }
+}
+
+/// Return every possible shape of `Tree` with up to `n` values. A tree with
+/// k values holds the numbers `0..k`.
+///
+/// There are Catalan(k) trees of each size k, so this is only practical for
+/// small `n`; with `n = 10` there are about 24,000.
+///
+pub fn all_trees<P: SharedPointerKind>(n: u32) -> Vec<Tree<u32, P>> {
+    (0..=n).flat_map(|k| trees_between(0, k)).collect()
+}
+
+fn trees_between<P: SharedPointerKind>(lo: u32, hi: u32) -> Vec<Tree<u32, P>> {
+    if lo == hi {
+        return vec![Tree::default()];
+    }
+    let mut trees = vec![];
+    for root in lo..hi {
+        let rights = trees_between(root + 1, hi);
+        for left in trees_between::<P>(lo, root) {
+            for right in &rights {
+                trees.push(cons_tree(P::new(root), left.clone(), right.clone()));
+            }
+        }
+    }
+    trees
+}
+
+/// Return every valid `RBTree` with up to `n` values: every shape and every
+/// coloring that passes `validate`, including those with a red root. A tree
+/// with k values holds the numbers `0..k`.
+pub fn all_rbtrees<P: SharedPointerKind>(n: u32) -> Vec<RBTree<u32, P>> {
+    let mut trees = vec![];
+    for k in 0..=n {
+        // Empty trees count as black, so black heights run from 1 to k + 1.
+        for height in 1..=(k as usize + 1) {
+            trees.extend(rbtrees_between(0, k, height, true));
+        }
+    }
+    trees
+}
+
+// All valid trees holding `lo..hi`, with black height `height`, with a red
+// root only if `red_ok`.
+fn rbtrees_between<P: SharedPointerKind>(lo: u32, hi: u32, height: usize, red_ok: bool)
+                                         -> Vec<RBTree<u32, P>> {
+    if lo == hi {
+        return if height == 1 { vec![RBTree::default()] } else { vec![] };
+    }
+    let mut trees = vec![];
+    for root in lo..hi {
+        for &red in &[false, true] {
+            if red && !red_ok {
+                continue;
+            }
+            let child_height = if red { height } else { height - 1 };
+            if child_height == 0 {
+                continue;
+            }
+            let rights = rbtrees_between(root + 1, hi, child_height, !red);
+            for left in rbtrees_between::<P>(lo, root, child_height, !red) {
+                for right in &rights {
+                    trees.push(rbtree::make_node(red, root, left.clone(), right.clone()));
+                }
+            }
+        }
+    }
+    trees
+}
+
+/// Return every internal layout of `BatchedQueue` with up to `n` items. A
+/// queue with k items holds the numbers `0..k`, front first; the layouts
+/// differ in how many of them are in the front list and how many in the back.
+pub fn all_queues<P: SharedPointerKind>(n: u32) -> Vec<BatchedQueue<u32, P>> {
+    let mut queues = vec![BatchedQueue::default()];
+    for k in 1..=n {
+        for split in 1..=k {
+            queues.push(BatchedQueue::from_parts((0..split).collect(), (split..k).collect()));
+        }
+    }
+    queues
+}
@@ -0,0 +1,114 @@
+//! Keeping the k largest values seen.
+//!
+//! A `TopK` is a leftist heap that never holds more than k values. Its
+//! minimum is the smallest value kept, so deciding whether a new value makes
+//! the cut takes constant time, and replacing the minimum takes O(log k).
+
+use std::fmt;
+use heap::LeftistHeap;
+use list::List;
+use list::List::Nil;
+use pointer::{SharedPointerKind, RcKind};
+use traits::{Heap, Stack};
+
+/// The `k` largest values offered so far.
+pub struct TopK<T, P: SharedPointerKind = RcKind> {
+    limit: usize,
+    len: usize,
+    heap: LeftistHeap<T, P>
+}
+
+impl<T, P: SharedPointerKind> Clone for TopK<T, P> {
+    fn clone(&self) -> TopK<T, P> {
+        TopK {
+            limit: self.limit,
+            len: self.len,
+            heap: self.heap.clone()
+        }
+    }
+}
+
+impl<T: Ord> TopK<T> {
+    /// Return an empty tracker that keeps the `k` largest values, sharing
+    /// nodes using `Rc`.
+    pub fn new(k: usize) -> TopK<T> {
+        TopK::empty(k)
+    }
+}
+
+impl<T: Ord, P: SharedPointerKind> TopK<T, P> {
+    /// Return an empty tracker that keeps the `k` largest values.
+    pub fn empty(k: usize) -> TopK<T, P> {
+        TopK { limit: k, len: 0, heap: LeftistHeap::empty() }
+    }
+
+    /// Return a tracker with `value` added, if it is among the `k` largest.
+    /// When there are already `k` values, `value` replaces the smallest one
+    /// if it is larger; otherwise this returns a copy of `self`.
+    pub fn offer(&self, value: T) -> TopK<T, P> {
+        if self.len < self.limit {
+            return TopK { limit: self.limit, len: self.len + 1, heap: self.heap.insert(value) };
+        }
+        match self.heap.min() {
+            Some(min) if value > *min => TopK {
+                limit: self.limit,
+                len: self.len,
+                heap: self.heap.without_min().insert(value)
+            },
+            _ => self.clone()
+        }
+    }
+
+    /// The smallest value kept: the one the next larger value would replace.
+    pub fn min(&self) -> Option<&T> {
+        self.heap.min()
+    }
+
+    /// The number of values kept, at most `k`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if no values have been offered (or `k` is 0).
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `k` this tracker was created with.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl<T: Ord, P: SharedPointerKind> Extend<T> for TopK<T, P> {
+    /// Offer each value in turn. There's no `FromIterator` impl, since a
+    /// `TopK` needs to know its limit up front.
+    fn extend<Iterable: IntoIterator<Item=T>>(&mut self, iterator: Iterable) {
+        for v in iterator {
+            *self = self.offer(v);
+        }
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> TopK<T, P> {
+    /// Return the values kept, largest first.
+    pub fn into_sorted_list(self) -> List<T, P> {
+        // The heap gives the values smallest first, so consing each onto the
+        // front leaves the largest at the head.
+        let mut heap = self.heap;
+        let mut list = Nil;
+        while let Some(v) = heap.pop() {
+            list = List::cons(v, list);
+        }
+        list
+    }
+}
+
+impl<T: Ord + fmt::Debug, P: SharedPointerKind> fmt::Debug for TopK<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TopK")
+            .field("limit", &self.limit)
+            .field("heap", &self.heap)
+            .finish()
+    }
+}
@@ -1,6 +1,8 @@
 //! Abstract descriptions of various kinds of persistent collections.
 
 use std::mem::swap;
+use list::List;
+use pointer::ArcKind;
 
 /// A Stack is a first-in-first-out collection.
 ///
@@ -11,10 +13,17 @@ pub trait Stack: Sized {
     type Item;
     fn empty() -> Self;
     fn is_empty(&self) -> bool;
-    fn cons(Self::Item, Self) -> Self;
+    fn cons(head: Self::Item, tail: Self) -> Self;
     fn split(&self) -> Option<(&Self::Item, &Self)>;
 
-    fn head<'a>(&'a self) -> Option<&'a Self::Item>
+    /// Return this stack with `head` added on top: `s.prepend(x)` is
+    /// `Stack::cons(x, s)`, for use in method chains. (`cons` itself takes
+    /// its arguments in the usual functional order, head first.)
+    fn prepend(self, head: Self::Item) -> Self {
+        Self::cons(head, self)
+    }
+
+    fn head(&self) -> Option<&Self::Item>
     {
         self.split().map(|(h, _)| h)
     }
@@ -25,6 +34,30 @@ pub trait Stack: Sized {
         self.split().map(|(_, t)| t)
     }
 
+    /// Return an iterator over references to the items, top first.
+    fn iter_refs<'a>(&'a self) -> StackIter<'a, Self>
+        where Self::Item: 'a
+    {
+        StackIter(self)
+    }
+
+    /// Return a copy of this stack with the items in reverse order. This
+    /// builds a whole new stack, cloning every item.
+    fn reverse(&self) -> Self
+        where Self::Item: Clone
+    {
+        self.iter_refs().fold(Self::empty(), |s, v| Self::cons(v.clone(), s))
+    }
+
+    /// Return a stack of this stack's items followed by `other`'s. The items
+    /// of `self` are cloned; `other` is shared, not copied.
+    fn append(&self, other: Self) -> Self
+        where Self::Item: Clone
+    {
+        let items: Vec<&Self::Item> = self.iter_refs().collect();
+        items.into_iter().rev().fold(other, |s, v| Self::cons(v.clone(), s))
+    }
+
     // Mutators. Rust's restrictions on mutation are so good we're tempted to
     // add a few niceties.
 
@@ -34,6 +67,12 @@ pub trait Stack: Sized {
         *self = Self::cons(v, tmp);
     }
 
+    /// Remove the top item and return it, or None if the stack is empty.
+    ///
+    /// Items have to be `Clone` because the top node may be shared with
+    /// other stacks, which still need it. This default clones the item and
+    /// the rest of the stack; `List` instead moves the item out of a node no
+    /// other list shares.
     fn pop(&mut self) -> Option<Self::Item>
         where Self::Item: Clone, Self: Clone
     {
@@ -46,8 +85,28 @@ pub trait Stack: Sized {
     }
 }
 
+/// An iterator over references to the items of a `Stack`, top first. See
+/// `Stack::iter_refs`.
+pub struct StackIter<'a, S: 'a>(&'a S);
+
+impl<'a, S: Stack> Iterator for StackIter<'a, S>
+    where S::Item: 'a
+{
+    type Item = &'a S::Item;
+
+    fn next(&mut self) -> Option<&'a S::Item> {
+        let (head, tail) = self.0.split()?;
+        self.0 = tail;
+        Some(head)
+    }
+}
+
 /// A queue is a first-in-first-out collection.
-pub trait Queue: Clone {
+///
+/// Queues needn't be `Clone`: `split` borrows the front item and builds the
+/// rest of the queue as a new value, which for the queues in this crate
+/// means sharing nodes, not cloning items.
+pub trait Queue: Sized {
     type Item;
 
     /// Create an empty queue.
@@ -58,8 +117,9 @@ pub trait Queue: Clone {
         self.head().is_none()
     }
 
-    /// Add an item to the back of a queue.
-    fn snoc(Self, Self::Item) -> Self;
+    /// Add an item to the back of a queue. This can be called either way:
+    /// `queue.snoc(x)` or `Queue::snoc(queue, x)`.
+    fn snoc(self, value: Self::Item) -> Self;
 
     /// Return a reference to the front item of a queue.
     ///
@@ -78,6 +138,29 @@ pub trait Queue: Clone {
     }
     
     fn split(&self) -> Option<(&Self::Item, Self)>;
+
+    /// Divide this queue into two: the first `n` items, and the rest. If the
+    /// queue has `n` items or fewer, the second queue is empty.
+    ///
+    /// This default implementation moves the items one at a time, cloning
+    /// each of the first `n`. Implementations override it where their
+    /// representation allows something better.
+    ///
+    fn split_at(self, n: usize) -> (Self, Self)
+        where Self::Item: Clone
+    {
+        let mut first = Self::empty();
+        let mut rest = self;
+        for _ in 0..n {
+            let (item, tail) = match rest.split() {
+                None => break,
+                Some((item, tail)) => (item.clone(), tail)
+            };
+            first = Self::snoc(first, item);
+            rest = tail;
+        }
+        (first, rest)
+    }
     
     /* Mutators */
 
@@ -86,13 +169,15 @@ pub trait Queue: Clone {
     /// This runs in constant time and space.
     ///
     fn push_back(&mut self, value: Self::Item) {
-        *self = Self::snoc((*self).clone(), value);
+        let mut tmp = Self::empty();
+        swap(self, &mut tmp);
+        *self = tmp.snoc(value);
     }
 }
 
 /// A deque is a queue that supports adding and removing items at either end.
 pub trait Deque: Queue {
-    fn cons(Self::Item, Self) -> Self;
+    fn cons(value: Self::Item, deque: Self) -> Self;
 
     fn last(&self) -> Option<&Self::Item> {
         self.split_back().map(|pair| pair.1)
@@ -105,7 +190,9 @@ pub trait Deque: Queue {
     fn split_back(&self) -> Option<(Self, &Self::Item)>;
     
     fn push_front(&mut self, value: Self::Item) {
-        *self = Self::cons(value, (*self).clone());
+        let mut tmp = Self::empty();
+        swap(self, &mut tmp);
+        *self = Self::cons(value, tmp);
     }
 }
 
@@ -115,16 +202,94 @@ pub trait Deque: Queue {
 /// That is, you can use the `set.contains(value)` method to test whether a
 /// set contains a given value.
 ///
-pub trait Set: IntoIterator {
+pub trait Set {
+    /// The type of value the set contains.
+    type Item;
+
     /// Return an empty set.
     fn empty() -> Self;
 
+    /// The number of values in the set.
+    ///
+    /// This default implementation counts the values of a copy of the set,
+    /// which takes O(n) time. Implementations that keep a count override it.
+    fn len(&self) -> usize
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>
+    {
+        self.clone().into_iter().count()
+    }
+
+    /// Return true if the set is empty.
+    fn is_empty(&self) -> bool
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>
+    {
+        self.len() == 0
+    }
+
     /// Return the union of `self` and the singleton set containing `value`.
     fn plus(&self, value: Self::Item) -> Self;
 
     /// Return true if the given value is in this set.
     fn contains(&self, value: &Self::Item) -> bool;
 
+    /// Return a copy of this set with the value equal to `key` replaced by
+    /// `f(old_value)`. The new value must compare equal to the old one, so
+    /// that it belongs in the same place. Returns None if the set has no value
+    /// equal to `key`, or if the new value doesn't compare equal to it.
+    ///
+    /// This is useful for sets whose comparator looks at only part of each
+    /// value. The trees in this crate copy only the search path.
+    fn modify<F>(&self, key: &<Self as Set>::Item, f: F) -> Option<Self>
+        where Self: Sized, F: FnOnce(&<Self as Set>::Item) -> <Self as Set>::Item;
+
+    /// Return a copy of this set without any values equal to `values`.
+    ///
+    /// This default implementation collects `values` into a set of their own,
+    /// then rebuilds `self` without them, one value at a time.
+    /// Implementations override it where they can do better.
+    fn minus_all<I>(&self, values: I) -> Self
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>,
+              I: IntoIterator<Item=<Self as Set>::Item>
+    {
+        let removed = values.into_iter().fold(Self::empty(), |s, v| s.plus(v));
+        self.clone().into_iter()
+            .filter(|v| !removed.contains(v))
+            .fold(Self::empty(), |s, v| s.plus(v))
+    }
+
+    /// Return the union of two sets. Where both have equal values, the one
+    /// from `self` is kept.
+    ///
+    /// This default implementation adds `other`'s values to `self` one at a
+    /// time. Implementations override it where they can reuse whole parts of
+    /// both sets instead.
+    fn union(&self, other: &Self) -> Self
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>
+    {
+        other.clone().into_iter().fold(self.clone(), |s, v| s.plus(v))
+    }
+
+    /// Return the values of `self` that are also in `other`.
+    ///
+    /// This default implementation checks each value of `self` against
+    /// `other` and adds the ones it finds to a new set, one at a time.
+    fn intersection(&self, other: &Self) -> Self
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>
+    {
+        self.clone().into_iter()
+            .filter(|v| other.contains(v))
+            .fold(Self::empty(), |s, v| s.plus(v))
+    }
+
+    /// Return the values of `self` that are not in `other`.
+    ///
+    /// This default implementation is `self.minus_all(other)`.
+    fn difference(&self, other: &Self) -> Self
+        where Self: Sized + Clone + IntoIterator<Item=<Self as Set>::Item>
+    {
+        self.minus_all(other.clone())
+    }
+
     /* Mutating operations. */
 
     /// Modify this set in-place by adding an item.
@@ -140,9 +305,7 @@ pub trait Set: IntoIterator {
 /// A Heap is a collection that supports efficiently finding and removing the
 /// minimum element.
 ///
-pub trait Heap: Sized
-    where Self::Item: Clone
-{
+pub trait Heap: Sized {
     /// The type of value the heap contains.
     type Item;
 
@@ -153,10 +316,10 @@ pub trait Heap: Sized
     fn is_empty(&self) -> bool;
 
     /// Return a heap containing all the values in self, and also the given Item.
-    fn insert(&self, Self::Item) -> Self;
+    fn insert(&self, value: Self::Item) -> Self;
 
     /// Create a new heap by combining two existing heaps.
-    fn merge(Self, Self) -> Self;
+    fn merge(a: Self, b: Self) -> Self;
 
     /// Return the minimum item in this heap, without removing it.
     /// If `self.is_empty()`, this returns `None`.
@@ -175,7 +338,9 @@ pub trait Heap: Sized
     
     /// Remove and return the minimum item of this heap. If `self.is_empty()`,
     /// this does nothing and returns `None`.
-    fn pop(&mut self) -> Option<Self::Item> {
+    fn pop(&mut self) -> Option<Self::Item>
+        where Self::Item: Clone
+    {
         let mut tmp = Self::empty();
         swap(self, &mut tmp);
         match tmp.min() {
@@ -187,3 +352,37 @@ pub trait Heap: Sized
         }
     }
 }
+
+/// A Sortable collection accepts values one at a time and produces them all
+/// in sorted order on demand.
+///
+/// Implementations are built on suspensions, which are shared between
+/// threads, so the sorted values come back as an `Arc`-based list.
+///
+pub trait Sortable: Sized {
+    /// The type of value to be sorted.
+    type Item;
+
+    /// Return an empty collection.
+    fn empty() -> Self;
+
+    /// Return a collection with all the values in `self`, plus `value`.
+    fn add(&self, value: Self::Item) -> Self;
+
+    /// Return a sorted list of all the values added so far, including any
+    /// duplicates.
+    fn sort(&self) -> List<Self::Item, ArcKind>;
+}
+
+/// A map value that can itself hold a map, so that `update_in` can follow a
+/// path of keys through maps nested inside maps.
+///
+/// `Self` is usually an enum with one variant for nested maps and others for
+/// plain values.
+pub trait Nested<M> {
+    /// The map this value holds, or None if it holds something else.
+    fn as_map(&self) -> Option<&M>;
+
+    /// Return a value holding `map`.
+    fn from_map(map: M) -> Self;
+}
@@ -1,83 +1,824 @@
 //! Persistent set data structures.
 
 use std::cmp::Ordering::*;
+use std::convert::Infallible;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter::IntoIterator;
-use std::rc::Rc;
+use std::mem;
+use std::slice;
+use diff::{self, Diff, SortedTree};
+use edit::{self, Edit};
+use dot;
+use pointer::{SharedPointerKind, RcKind, ArcKind};
+use pretty::{pretty, Shape};
+use sharing::{self, Linked};
+use snapshot::{decoded, DecodeError, Encoder, Snapshot, TreeRecord};
+use stats::ShapeStats;
 use traits::Set;
+use validate::{InvariantError, Violation};
 
-struct TreeNode<V> {
-    value: V,
-    left: Tree<V>,
-    right: Tree<V>
+// Each value is stored behind its own pointer, so that copying a node (which
+// `plus` does all along the search path) never copies a value. That's what lets
+// a `Tree` hold values that aren't `Clone`.
+struct TreeNode<V, P: SharedPointerKind> {
+    value: P::Pointer<V>,
+    left: Tree<V, P>,
+    right: Tree<V, P>
 }
 
-#[derive(Clone)]
-enum TreeImpl<V> {
+enum TreeImpl<V, P: SharedPointerKind> {
     Empty,
-    NonEmpty(Rc<TreeNode<V>>)
+    NonEmpty(P::Pointer<TreeNode<V, P>>)
 }
 
 /// An unbalanced tree implementation. Use the `Set` methods.
-#[derive(Clone)]
-pub struct Tree<V>(TreeImpl<V>);
+pub struct Tree<V, P: SharedPointerKind = RcKind>(TreeImpl<V, P>);
 
-fn cons_tree<V>(value: V, left: Tree<V>, right: Tree<V>) -> Tree<V> {
-    Tree(NonEmpty(Rc::new(TreeNode {value: value, left: left, right: right})))
+// `derive(Clone)` would require `V: Clone` and `P: Clone`; neither is needed
+// to copy a pointer.
+impl<V, P: SharedPointerKind> Clone for Tree<V, P> {
+    fn clone(&self) -> Tree<V, P> {
+        match self.0 {
+            Empty => Tree(Empty),
+            NonEmpty(ref rc) => Tree(NonEmpty(rc.clone()))
+        }
+    }
+}
+
+impl<V> Tree<V> {
+    /// Return an empty tree that shares nodes using `Rc`.
+    pub fn new() -> Tree<V> { Tree(Empty) }
+}
+
+impl<V, P: SharedPointerKind> Default for Tree<V, P> {
+    fn default() -> Tree<V, P> { Tree(Empty) }
+}
+
+pub(crate) fn cons_tree<V, P: SharedPointerKind>(value: P::Pointer<V>, left: Tree<V, P>, right: Tree<V, P>) -> Tree<V, P> {
+    Tree(NonEmpty(P::new(TreeNode {value, left, right})))
 }
 
 use self::TreeImpl::*;
 
-impl<V: Ord + Clone> Set for Tree<V> {
-    fn empty() -> Tree<V> { Tree(Empty) }
+// The tree isn't balanced, so it can be as deep as it is large, and nothing
+// here may recurse once per level. That includes dropping it: the default
+// drop would. Instead, each node unlinks its children, and any child that
+// nothing else shares is taken apart the same way, from an explicit stack.
+impl<V, P: SharedPointerKind> Drop for TreeNode<V, P> {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        self.unlink_children(&mut stack);
+        while let Some(mut rc) = stack.pop() {
+            if let Some(node) = P::get_mut(&mut rc) {
+                node.unlink_children(&mut stack);
+            }
+        }
+    }
+}
 
-    fn plus(&self, v: V) -> Tree<V> {
-        match self.0 {
-            Empty => cons_tree(v, Tree(Empty), Tree(Empty)),
-            NonEmpty(ref rc) => {
-                let n = &**rc;
-                match v.cmp(&n.value) {
-                    Less => cons_tree(n.value.clone(), n.left.plus(v), n.right.clone()),
-                    Greater => cons_tree(n.value.clone(), n.left.clone(), n.right.plus(v)),
-                    Equal => self.clone()
+impl<V, P: SharedPointerKind> TreeNode<V, P> {
+    fn unlink_children(&mut self, stack: &mut Vec<P::Pointer<TreeNode<V, P>>>) {
+        for child in [&mut self.left, &mut self.right] {
+            if let NonEmpty(rc) = mem::replace(&mut child.0, Empty) {
+                stack.push(rc);
+            }
+        }
+    }
+}
+
+// Copy the nodes on `path`, which leads down from the root, replacing the
+// subtree at the end of it with `subtree`.
+fn rebuild_path<V, P: SharedPointerKind>(path: Vec<(&TreeNode<V, P>, Side)>, subtree: Tree<V, P>) -> Tree<V, P> {
+    path.into_iter().rev().fold(subtree, |t, (n, side)| match side {
+        Side::Left => cons_tree(n.value.clone(), t, n.right.clone()),
+        Side::Right => cons_tree(n.value.clone(), n.left.clone(), t)
+    })
+}
+
+// A step in walking a tree with an explicit stack: visit a subtree, call the
+// function on a value, or build a node from the last two subtrees built. See
+// `try_map_by`.
+enum Walk<'a, V: 'a, P: SharedPointerKind + 'a> {
+    Visit(&'a Tree<V, P>),
+    Map(&'a V),
+    Join
+}
+
+impl<V: Ord, P: SharedPointerKind> Set for Tree<V, P> {
+    type Item = V;
+
+    fn empty() -> Tree<V, P> { Tree(Empty) }
+
+    // Nodes don't record the sizes of their subtrees, so this counts them, in
+    // O(n) time.
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self.0, Empty)
+    }
+
+    fn plus(&self, v: V) -> Tree<V, P> {
+        let mut path = vec![];
+        let mut t = self;
+        while let NonEmpty(ref rc) = t.0 {
+            match v.cmp(&rc.value) {
+                Less => {
+                    path.push((&**rc, Side::Left));
+                    t = &rc.left;
                 }
+                Greater => {
+                    path.push((&**rc, Side::Right));
+                    t = &rc.right;
+                }
+                Equal => return self.clone()
             }
         }
+        let result = rebuild_path(path, cons_tree(P::new(v), Tree(Empty), Tree(Empty)));
+        strict_check!(result);
+        result
     }
 
     fn contains(&self, v: &V) -> bool {
-        match self.0 {
-            Empty => false,
-            NonEmpty(ref rc) => match v.cmp(&(*rc).value) {
-                Less => (*rc).left.contains(v),
-                Greater => (*rc).right.contains(v),
-                Equal => true
+        let mut t = self;
+        while let NonEmpty(ref rc) = t.0 {
+            t = match v.cmp(&rc.value) {
+                Less => &rc.left,
+                Greater => &rc.right,
+                Equal => return true
+            };
+        }
+        false
+    }
+
+    fn modify<F: FnOnce(&V) -> V>(&self, key: &V, f: F) -> Option<Tree<V, P>> {
+        let mut path = vec![];
+        let mut t = self;
+        loop {
+            let rc = match t.0 {
+                Empty => return None,
+                NonEmpty(ref rc) => rc
+            };
+            match key.cmp(&rc.value) {
+                Less => {
+                    path.push((&**rc, Side::Left));
+                    t = &rc.left;
+                }
+                Greater => {
+                    path.push((&**rc, Side::Right));
+                    t = &rc.right;
+                }
+                Equal => {
+                    let value = f(&rc.value);
+                    if value.cmp(&rc.value) != Equal {
+                        return None;
+                    }
+                    let node = cons_tree(P::new(value), rc.left.clone(), rc.right.clone());
+                    let result = rebuild_path(path, node);
+                    strict_check!(result);
+                    return Some(result);
+                }
             }
         }
     }
+
+    fn minus_all<I: IntoIterator<Item=V>>(&self, values: I) -> Tree<V, P> {
+        self.edit(|e| {
+            for v in values {
+                e.remove(v);
+            }
+        })
+    }
 }
 
-impl<V: Clone> Tree<V> {
-    fn copy_to_vec(&self, out: &mut Vec<V>) {
-        match self.0 {
-            Empty => (),
-            NonEmpty(ref rc) => {
-                let n = &**rc;
-                n.left.copy_to_vec(out);
-                out.push(n.value.clone());
-                n.right.copy_to_vec(out);
+
+impl<V: Ord, P: SharedPointerKind> Tree<V, P> {
+    /// Check that the values in this tree are in order. See the `validate`
+    /// module.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        // Subtrees still to check, each with the bounds its values must be
+        // strictly between. `path` leads to the subtree checked last; each
+        // entry says how much of it leads to the entry's parent, and the step
+        // from there.
+        let mut path = String::new();
+        let mut stack = vec![(self, None, None, 0, None)];
+        while let Some((tree, lo, hi, len, step)) = stack.pop() {
+            path.truncate(len);
+            path.extend(step);
+            if let NonEmpty(ref rc) = tree.0 {
+                let v = &*rc.value;
+                if lo.is_some_and(|lo| lo >= v) || hi.is_some_and(|hi| v >= hi) {
+                    return Err(InvariantError::new(Violation::OutOfOrder, &path));
+                }
+                stack.push((&rc.right, Some(v), hi, path.len(), Some('R')));
+                stack.push((&rc.left, lo, Some(v), path.len(), Some('L')));
             }
         }
+        Ok(())
+    }
+}
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    // Append pointers to all the values, in order, to `out`.
+    pub(crate) fn copy_pointers_to_vec(&self, out: &mut Vec<P::Pointer<V>>) {
+        let mut iter = self.iter();
+        while let Some(node) = iter.next_node() {
+            out.push(node.value.clone());
+        }
     }
 }
 
-impl<V: Clone> IntoIterator for Tree<V> {
+impl<V: Clone, P: SharedPointerKind> IntoIterator for Tree<V, P> {
     type Item = V;
     type IntoIter = <Vec<V> as IntoIterator>::IntoIter;
     fn into_iter(self) -> Self::IntoIter {
-        let mut v = vec![];
-        self.copy_to_vec(&mut v);
-        v.into_iter()
+        self.iter().cloned().collect::<Vec<V>>().into_iter()
+    }
+}
+
+
+
+/// An in-order iterator over references to the values in a `Tree`.
+pub struct TreeIter<'a, V: 'a, P: SharedPointerKind + 'a> {
+    // The nodes whose values haven't been visited yet, deepest last. The right
+    // subtree of each node is pushed only after the node itself is visited.
+    stack: Vec<&'a TreeNode<V, P>>
+}
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    /// Return an iterator over references to the values in this tree, in
+    /// order. Unlike `into_iter`, this doesn't require `V: Clone`.
+    pub fn iter(&self) -> TreeIter<'_, V, P> {
+        let mut iter = TreeIter { stack: vec![] };
+        iter.push_left_spine(self);
+        iter
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> TreeIter<'a, V, P> {
+    fn push_left_spine(&mut self, mut tree: &'a Tree<V, P>) {
+        while let NonEmpty(ref rc) = tree.0 {
+            self.stack.push(rc);
+            tree = &rc.left;
+        }
+    }
+
+    fn next_node(&mut self) -> Option<&'a TreeNode<V, P>> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        Some(node)
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> Iterator for TreeIter<'a, V, P> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.next_node().map(|node| &*node.value)
+    }
+}
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    /// Return the distribution of node depths in this tree. See the `stats`
+    /// module.
+    pub fn stats(&self) -> ShapeStats {
+        let mut stats = ShapeStats::default();
+        let mut stack = vec![(self, 0)];
+        while let Some((tree, depth)) = stack.pop() {
+            if let NonEmpty(ref rc) = tree.0 {
+                stats.add(depth);
+                stack.push((&rc.right, depth + 1));
+                stack.push((&rc.left, depth + 1));
+            }
+        }
+        stats
+    }
+}
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    /// Combine the values, in order, using `f`. This walks the tree by
+    /// reference, without cloning or collecting the values.
+    pub fn fold<A, F: FnMut(A, &V) -> A>(&self, init: A, f: F) -> A {
+        self.iter().fold(init, f)
+    }
+
+    /// Call `f` on each value, in order.
+    pub fn for_each<F: FnMut(&V)>(&self, f: F) {
+        self.iter().for_each(f)
+    }
+
+    /// The `k` least values, least first, or all of them if there are fewer
+    /// than `k`. This takes time proportional to the tree's depth plus `k`.
+    pub fn take_smallest(&self, k: usize) -> Vec<&V> {
+        self.iter().take(k).collect()
+    }
+
+    /// The `k` greatest values, greatest first, or all of them if there are
+    /// fewer than `k`. This takes time proportional to the tree's depth plus
+    /// `k`.
+    pub fn take_largest(&self, k: usize) -> Vec<&V> {
+        // An in-order walk, mirrored: the stack holds the nodes on the right
+        // spine of what's left, and the greatest is on top.
+        let mut out = Vec::new();
+        let mut stack = vec![];
+        let mut t = self;
+        while out.len() < k {
+            while let NonEmpty(ref rc) = t.0 {
+                stack.push(rc);
+                t = &rc.right;
+            }
+            match stack.pop() {
+                None => break,
+                Some(rc) => {
+                    out.push(&*rc.value);
+                    t = &rc.left;
+                }
+            }
+        }
+        out
+    }
+
+    /// Combine the values, in order, using `f`, stopping at the first error
+    /// and returning it.
+    pub fn try_fold<A, E, F>(&self, init: A, f: F) -> Result<A, E>
+        where F: FnMut(A, &V) -> Result<A, E>
+    {
+        self.iter().try_fold(init, f)
+    }
+
+    /// Return a tree of the same shape holding the results of calling `f` on
+    /// each value, in order, or the first error `f` returns.
+    ///
+    /// The result is a valid search tree only if `f` preserves the order of
+    /// the values.
+    ///
+    pub fn try_map<U, E, F>(&self, mut f: F) -> Result<Tree<U, P>, E>
+        where F: FnMut(&V) -> Result<U, E>
+    {
+        self.try_map_by(&mut f)
+    }
+
+    /// Return a tree of the same shape holding `f` of each value.
+    ///
+    /// This copies every node but does no comparisons, so `f` must be
+    /// strictly increasing: `a < b` must imply `f(a) < f(b)`. Otherwise the
+    /// result is not a valid search tree. With the `strict-checks` feature,
+    /// the result is validated.
+    ///
+    pub fn map_monotonic<U: Ord, F: FnMut(&V) -> U>(&self, mut f: F) -> Tree<U, P> {
+        let result = match self.try_map_by(&mut |v: &V| Ok::<U, Infallible>(f(v))) {
+            Ok(tree) => tree,
+            Err(never) => match never {}
+        };
+        strict_check!(result);
+        result
+    }
+
+    // Call `f` on the values in order, building a tree of the same shape
+    // that uses pointer kind `Q`.
+    fn try_map_by<U, Q, E, F>(&self, f: &mut F) -> Result<Tree<U, Q>, E>
+        where Q: SharedPointerKind, F: FnMut(&V) -> Result<U, E>
+    {
+        let mut walk = vec![Walk::Visit(self)];
+        let mut trees = vec![];
+        let mut values = vec![];
+        while let Some(step) = walk.pop() {
+            match step {
+                Walk::Visit(tree) => match tree.0 {
+                    Empty => trees.push(Tree(Empty)),
+                    NonEmpty(ref rc) => {
+                        walk.push(Walk::Join);
+                        walk.push(Walk::Visit(&rc.right));
+                        walk.push(Walk::Map(&rc.value));
+                        walk.push(Walk::Visit(&rc.left));
+                    }
+                },
+                Walk::Map(v) => values.push(f(v)?),
+                Walk::Join => {
+                    let right = trees.pop().expect("right subtree was built");
+                    let left = trees.pop().expect("left subtree was built");
+                    let value = values.pop().expect("value was mapped");
+                    trees.push(cons_tree(Q::new(value), left, right));
+                }
+            }
+        }
+        Ok(trees.pop().expect("tree was built"))
+    }
+}
+
+// Shows the values not yet visited.
+impl<'a, V: fmt::Debug, P: SharedPointerKind> fmt::Debug for TreeIter<'a, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TreeIter")?;
+        f.debug_list().entries(TreeIter { stack: self.stack.clone() }).finish()
+    }
+}
+
+impl<'a, V, P: SharedPointerKind> IntoIterator for &'a Tree<V, P> {
+    type Item = &'a V;
+    type IntoIter = TreeIter<'a, V, P>;
+    fn into_iter(self) -> TreeIter<'a, V, P> {
+        self.iter()
+    }
+}
+
+impl<V: Clone, P: SharedPointerKind> Tree<V, P> {
+    /// Return a copy of this tree, with the same shape, that shares nodes
+    /// using pointer kind `Q`.
+    ///
+    /// This copies every node and clones every value once.
+    ///
+    pub fn to_pointer_kind<Q: SharedPointerKind>(&self) -> Tree<V, Q> {
+        match self.try_map_by(&mut |v: &V| Ok::<V, Infallible>(v.clone())) {
+            Ok(tree) => tree,
+            Err(never) => match never {}
+        }
+    }
+}
+
+impl<V: Clone> Tree<V> {
+    /// Return a thread-safe copy of this tree.
+    pub fn to_shared(&self) -> Tree<V, ArcKind> { self.to_pointer_kind() }
+}
+
+impl<V: Clone> Tree<V, ArcKind> {
+    /// Return a copy of this tree that uses `Rc` to share nodes.
+    pub fn to_local(&self) -> Tree<V> { self.to_pointer_kind() }
+}
+
+impl<V: Clone, P: SharedPointerKind> Tree<V, P> {
+    /// Encode several versions of a tree as a `Snapshot`. Nodes and values
+    /// shared between versions are written only once.
+    pub fn encode(versions: &[Tree<V, P>]) -> Snapshot<V, TreeRecord> {
+        let mut encoder = Encoder::new();
+        for t in versions {
+            let id = t.encode_node(&mut encoder);
+            encoder.root(id);
+        }
+        encoder.finish()
+    }
+
+    // Encode the nodes of this tree that aren't already encoded, children
+    // first, and return the root's id.
+    fn encode_node(&self, encoder: &mut Encoder<V, TreeRecord>) -> Option<usize> {
+        // Each node is on the stack twice: once to visit it, and again
+        // (marked `true`) to encode it once its subtrees are done.
+        let mut stack = vec![(self, false)];
+        let mut ids = vec![];
+        while let Some((tree, subtrees_done)) = stack.pop() {
+            let node: &TreeNode<V, P> = match tree.0 {
+                Empty => {
+                    ids.push(None);
+                    continue;
+                }
+                NonEmpty(ref rc) => rc
+            };
+            if subtrees_done {
+                let right = ids.pop().expect("right subtree was encoded");
+                let left = ids.pop().expect("left subtree was encoded");
+                let value = encoder.value(&node.value);
+                ids.push(Some(encoder.node(node, TreeRecord { value, left, right })));
+            } else if let Some(id) = encoder.lookup(node) {
+                ids.push(Some(id));
+            } else {
+                stack.push((tree, true));
+                stack.push((&node.right, false));
+                stack.push((&node.left, false));
+            }
+        }
+        ids.pop().expect("root was encoded")
+    }
+
+    /// Rebuild the versions stored in a snapshot, restoring the sharing
+    /// between them.
+    pub fn decode(snapshot: &Snapshot<V, TreeRecord>) -> Result<Vec<Tree<V, P>>, DecodeError> {
+        let values: Vec<P::Pointer<V>> = snapshot.values.iter().map(|v| P::new(v.clone())).collect();
+        let mut trees: Vec<Tree<V, P>> = Vec::with_capacity(snapshot.nodes.len());
+        for (i, r) in snapshot.nodes.iter().enumerate() {
+            snapshot.check_value(i, r.value)?;
+            snapshot.check_child(i, r.left)?;
+            snapshot.check_child(i, r.right)?;
+            let tree = cons_tree(values[r.value].clone(),
+                                 decoded(&trees, r.left),
+                                 decoded(&trees, r.right));
+            trees.push(tree);
+        }
+        snapshot.roots.iter().map(|&root| {
+            snapshot.check_root(root)?;
+            Ok(decoded(&trees, root))
+        }).collect()
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> Shape for Tree<V, P> {
+    fn node(&self) -> Option<(String, &Tree<V, P>, &Tree<V, P>)> {
+        match self.0 {
+            Empty => None,
+            NonEmpty(ref rc) => Some((format!("{:?}", *rc.value), &rc.left, &rc.right))
+        }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> Tree<V, P> {
+    /// Draw the shape of this tree as indented text, one node per line, for
+    /// debugging. An empty subtree whose sibling isn't empty is shown as `·`.
+    pub fn pretty(&self) -> String {
+        pretty(self)
+    }
+}
+
+impl<V: Clone + fmt::Debug, P: SharedPointerKind> Tree<V, P> {
+    /// Render this tree as a Graphviz graph, in DOT format, with one vertex
+    /// per node.
+    pub fn to_dot(&self) -> String {
+        Tree::versions_to_dot(slice::from_ref(self))
+    }
+
+    /// Render several versions of a tree as one Graphviz graph, in DOT
+    /// format. Nodes shared by more than one version appear only once, so the
+    /// graph shows exactly how much structure the versions share.
+    ///
+    pub fn versions_to_dot(versions: &[Tree<V, P>]) -> String {
+        dot::render("Tree", &Tree::encode(versions))
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> fmt::Debug for Tree<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Tree")?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<V, P: SharedPointerKind> Linked for Tree<V, P> {
+    fn root_addr(&self) -> Option<usize> {
+        match self.0 {
+            Empty => None,
+            NonEmpty(ref rc) => Some(&**rc as *const TreeNode<V, P> as usize)
+        }
+    }
+
+    fn children(&self) -> (Option<&Tree<V, P>>, Option<&Tree<V, P>>) {
+        match self.0 {
+            Empty => (None, None),
+            NonEmpty(ref rc) => (Some(&rc.left), Some(&rc.right))
+        }
+    }
+}
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    /// Return true if `self` and `other` are the same version of the tree:
+    /// both empty, or with the same root node. This takes constant time. If it
+    /// returns true, the two are certainly equal; if not, they may still be
+    /// equal, and `==` will say.
+    pub fn same(&self, other: &Tree<V, P>) -> bool {
+        sharing::same(self, other)
+    }
+
+    /// Return true if `self` and `other` have any nodes in common, as when
+    /// one was made from the other. This takes time proportional to the
+    /// number of nodes in both.
+    ///
+    pub fn shares_structure_with(&self, other: &Tree<V, P>) -> bool {
+        sharing::shares_structure(&[self], &[other])
+    }
+
+    /// Return the number of nodes `self` and `other` have in common. This
+    /// takes time proportional to the number of nodes in both.
+    pub fn shared_node_count(&self, other: &Tree<V, P>) -> usize {
+        sharing::shared_count(&[self], &[other])
+    }
+
+    /// Return the number of nodes in `self` that are not also in `other`:
+    /// how much memory keeping `self` costs, on top of keeping `other`.
+    pub fn unique_node_count(&self, other: &Tree<V, P>) -> usize {
+        sharing::unique_count(&[self], &[other])
+    }
+}
+
+impl<V, P: SharedPointerKind> SortedTree for Tree<V, P> {
+    type Value = V;
+
+    fn root_value(&self) -> &V {
+        match self.0 {
+            Empty => panic!("root_value called on an empty tree"),
+            NonEmpty(ref rc) => &rc.value
+        }
+    }
+}
+
+impl<V: Ord, P: SharedPointerKind> Tree<V, P> {
+    /// Return the values added and removed between `old` and `new`, skipping
+    /// over any subtrees they share. See the `diff` module.
+    pub fn diff<'a>(old: &'a Tree<V, P>, new: &'a Tree<V, P>) -> Diff<'a, V> {
+        diff::diff(old, new, V::cmp, V::eq)
     }
 }
 
+impl<V: Ord + Clone, P: SharedPointerKind> Tree<V, P> {
+    /// Merge two versions `a` and `b` that were both made from `base`: the
+    /// result is `a` with the values `b` added put in, and the values `b`
+    /// removed taken out.
+    ///
+    /// Unlike `RBTree::merge3`, this needs no way to settle conflicts. Values
+    /// that compare equal are equal, so a value can only be added by both
+    /// versions, removed by both, or changed by one, and the changes never
+    /// disagree. This takes time proportional to the number of changes `b`
+    /// made, times log n, plus a rebuild if `b` removed anything, since a
+    /// `Tree` can't remove a single value.
+    pub fn merge3(base: &Tree<V, P>, a: &Tree<V, P>, b: &Tree<V, P>) -> Tree<V, P> {
+        let theirs = diff::changes(&Tree::diff(base, b), V::cmp);
+        a.edit(|e| {
+            for (key, in_b) in theirs {
+                match in_b {
+                    Some(value) => e.insert(value.clone()),
+                    None => e.remove(key.clone())
+                }
+            }
+        })
+    }
+}
+
+// Build a balanced tree from pointers to sorted, distinct values.
+fn build_sorted<V, P: SharedPointerKind>(values: &[P::Pointer<V>]) -> Tree<V, P> {
+    if values.is_empty() {
+        return Tree(Empty);
+    }
+    let mid = values.len() / 2;
+    cons_tree(values[mid].clone(), build_sorted(&values[..mid]), build_sorted(&values[mid + 1..]))
+}
+
+impl<V: Ord, P: SharedPointerKind> Tree<V, P> {
+    /// Return a copy of this tree with a batch of changes applied. `f` is
+    /// called once to record the changes. See the `edit` module.
+    ///
+    /// When the batch is applied by rebuilding, the result is balanced, even
+    /// if this tree wasn't.
+    pub fn edit<F: FnOnce(&mut Edit<V>)>(&self, f: F) -> Tree<V, P> {
+        let mut batch = Edit::new();
+        f(&mut batch);
+        let changes = edit::sorted(batch, V::cmp);
+        if !edit::should_rebuild(self, &changes, false) {
+            return changes.into_iter().fold(self.clone(), |t, (v, _)| t.plus(v));
+        }
+        let mut old = vec![];
+        self.copy_pointers_to_vec(&mut old);
+        let result = build_sorted(&edit::merge(old, changes, V::cmp, P::new));
+        strict_check!(result);
+        result
+    }
+}
+
+// Two trees are equal if they contain the same values, even if they have
+// different shapes because the values were inserted in a different order.
+impl<V: PartialEq, P: SharedPointerKind> PartialEq for Tree<V, P> {
+    fn eq(&self, other: &Tree<V, P>) -> bool {
+        match (&self.0, &other.0) {
+            (NonEmpty(a), NonEmpty(b)) if P::ptr_eq(a, b) => true,
+            _ => self.iter().eq(other.iter())
+        }
+    }
+}
+
+impl<V: Eq, P: SharedPointerKind> Eq for Tree<V, P> {}
+
+// Hash the values in order, then the count, so that trees holding the same
+// values hash alike whatever their shape.
+impl<V: Hash, P: SharedPointerKind> Hash for Tree<V, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for v in self.iter() {
+            v.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
+
+/// A position in a tree, for walking around it and making local changes.
+///
+/// A zipper holds the subtree in focus plus the path back to the root. Moving
+/// and editing take constant time; `rebuild` then copies only the nodes on
+/// the paths to the changes, and shares everything else with the original
+/// tree. Nothing stops an edit from putting the values out of order, so it's
+/// up to the caller to keep them sorted if the result will be used as a set.
+pub struct TreeZipper<V, P: SharedPointerKind = RcKind> {
+    focus: Tree<V, P>,
+    // The nodes above the focus, root first, and which way we went from each.
+    path: Vec<Step<V, P>>
+}
+
+type Step<V, P> = (<P as SharedPointerKind>::Pointer<TreeNode<V, P>>, Side);
 
+#[derive(Clone, Copy, PartialEq)]
+enum Side { Left, Right }
+
+impl<V, P: SharedPointerKind> Tree<V, P> {
+    /// Return a zipper focused on the root of this tree.
+    pub fn zipper(&self) -> TreeZipper<V, P> {
+        TreeZipper { focus: self.clone(), path: vec![] }
+    }
+}
+
+impl<V, P: SharedPointerKind> TreeZipper<V, P> {
+    /// The subtree in focus.
+    pub fn focus(&self) -> &Tree<V, P> {
+        &self.focus
+    }
+
+    /// The value at the root of the focus, or None if the focus is empty.
+    pub fn value(&self) -> Option<&V> {
+        match self.focus.0 {
+            Empty => None,
+            NonEmpty(ref rc) => Some(&rc.value)
+        }
+    }
+
+    /// How many steps the focus is below the root.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Move the focus to its left subtree. If the focus is empty, this does
+    /// nothing and returns false.
+    pub fn down_left(&mut self) -> bool {
+        self.down(Side::Left)
+    }
+
+    /// Move the focus to its right subtree. If the focus is empty, this does
+    /// nothing and returns false.
+    pub fn down_right(&mut self) -> bool {
+        self.down(Side::Right)
+    }
+
+    fn down(&mut self, side: Side) -> bool {
+        let node = match self.focus.0 {
+            Empty => return false,
+            NonEmpty(ref rc) => rc.clone()
+        };
+        self.focus = match side {
+            Side::Left => node.left.clone(),
+            Side::Right => node.right.clone()
+        };
+        self.path.push((node, side));
+        true
+    }
+
+    /// Move the focus to its parent. At the root, this does nothing and
+    /// returns false.
+    pub fn up(&mut self) -> bool {
+        let (parent, side) = match self.path.pop() {
+            None => return false,
+            Some(step) => step
+        };
+        let old = if side == Side::Left { &parent.left } else { &parent.right };
+        self.focus = if sharing::same(old, &self.focus) {
+            Tree(NonEmpty(parent))
+        } else {
+            let focus = mem::replace(&mut self.focus, Tree(Empty));
+            match side {
+                Side::Left => cons_tree(parent.value.clone(), focus, parent.right.clone()),
+                Side::Right => cons_tree(parent.value.clone(), parent.left.clone(), focus)
+            }
+        };
+        true
+    }
+
+    /// Replace the subtree in focus.
+    pub fn replace(&mut self, subtree: Tree<V, P>) {
+        self.focus = subtree;
+    }
+
+    /// Replace the value at the root of the focus, keeping its subtrees. If
+    /// the focus is empty, the value becomes a leaf.
+    pub fn set_value(&mut self, value: V) {
+        let (left, right) = match self.focus.0 {
+            Empty => (Tree(Empty), Tree(Empty)),
+            NonEmpty(ref rc) => (rc.left.clone(), rc.right.clone())
+        };
+        self.focus = cons_tree(P::new(value), left, right);
+    }
+
+    /// Move back up to the root and return the whole edited tree.
+    pub fn rebuild(mut self) -> Tree<V, P> {
+        while self.up() {}
+        self.focus
+    }
+}
+
+impl<V, P: SharedPointerKind> Clone for TreeZipper<V, P> {
+    fn clone(&self) -> TreeZipper<V, P> {
+        TreeZipper { focus: self.focus.clone(), path: self.path.clone() }
+    }
+}
+
+impl<V: fmt::Debug, P: SharedPointerKind> fmt::Debug for TreeZipper<V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TreeZipper")
+            .field("focus", &self.focus)
+            .field("depth", &self.path.len())
+            .finish()
+    }
+}
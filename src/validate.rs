@@ -0,0 +1,91 @@
+//! Checking the structural invariants of a data structure.
+//!
+//! Every structure's operations are supposed to preserve certain invariants:
+//! a red-black tree has no red node with a red child, a leftist heap's left
+//! subtrees have rank at least as great as the right ones, and so on. The
+//! `validate` method on each structure checks these and reports the first
+//! broken invariant it finds. It takes time proportional to the size of the
+//! structure, so it's meant for tests and debugging.
+//!
+//! With the `strict-checks` feature enabled, the structures also validate
+//! themselves: every operation that builds a new version checks it before
+//! returning it, and panics if an invariant is broken. A bug is then caught
+//! by the operation that caused it, rather than by some later operation that
+//! trips over the damage. This makes every operation take linear time, so
+//! it's only for debugging.
+
+use std::error::Error;
+use std::fmt;
+
+// Under the `strict-checks` feature, panic if `$result` fails validation.
+// Without the feature, the check is still type-checked, so it can't rot, but
+// it compiles to nothing.
+macro_rules! strict_check {
+    ($result:expr) => {
+        if cfg!(feature = "strict-checks") {
+            if let Err(err) = $result.validate() {
+                panic!("invariant broken: {}", err);
+            }
+        }
+    };
+}
+
+/// Which invariant is broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A search tree has a value that is not between the values of its
+    /// ancestors, or two equal values.
+    OutOfOrder,
+    /// A red node in a red-black tree has a red child.
+    RedRed,
+    /// The two subtrees of a red-black tree node have different black heights.
+    BlackHeight,
+    /// The left subtree of a leftist heap node has lower rank than the right.
+    Leftist,
+    /// A leftist heap node's stored rank isn't the length of its right spine.
+    Rank,
+    /// A tree node's stored size isn't the number of values in its subtree.
+    Size,
+    /// A heap node's value is less than its parent's.
+    HeapOrder,
+    /// A queue's front list is empty but its back list isn't.
+    EmptyFront
+}
+
+/// The error returned by `validate` when a structure is broken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvariantError {
+    pub violation: Violation,
+    /// Where the problem is: the path from the root to the bad node, as a
+    /// string of `L` and `R` steps. Empty for the root, and for structures
+    /// that aren't trees.
+    pub path: String
+}
+
+impl InvariantError {
+    pub(crate) fn new(violation: Violation, path: &str) -> InvariantError {
+        InvariantError { violation, path: path.to_string() }
+    }
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match self.violation {
+            Violation::OutOfOrder => "value out of order",
+            Violation::RedRed => "red node with a red child",
+            Violation::BlackHeight => "subtrees with different black heights",
+            Violation::Leftist => "left subtree with lower rank than right",
+            Violation::Rank => "incorrect rank",
+            Violation::Size => "incorrect size",
+            Violation::HeapOrder => "value less than its parent",
+            Violation::EmptyFront => "empty front with a nonempty back"
+        };
+        if self.path.is_empty() {
+            write!(f, "{}", what)
+        } else {
+            write!(f, "{} at {}", what, self.path)
+        }
+    }
+}
+
+impl Error for InvariantError {}
@@ -0,0 +1,109 @@
+//! Version ids, for tracing where a value came from.
+//!
+//! A `Versioned<T>` is a value together with a unique id and the id of the
+//! version it was derived from. Deriving a new version with `derive` stamps
+//! it with a fresh id and records the old id as its parent, so a debugger or
+//! log can reconstruct the whole derivation history from the ids alone,
+//! without any bookkeeping around each operation.
+//!
+//! ```ignore
+//! let v0 = Versioned::new(RBTree::new());
+//! let v1 = v0.derive(|s| s.plus(1));
+//! let v2 = v0.derive(|s| s.plus(2));
+//! assert_eq!(v1.parent(), Some(v0.id()));
+//! assert_eq!(v2.parent(), Some(v0.id()));
+//! assert!(v1.id() < v2.id());
+//! ```
+//!
+//! Ids come from a single counter shared by the whole program, so they are
+//! unique across threads and across types, and a version's id is always
+//! greater than its parent's.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A value stamped with a version id and the id of its parent version.
+///
+/// Cloning a `Versioned` copies the value and keeps the id: the clone is the
+/// same version, not a new one.
+#[derive(Clone)]
+pub struct Versioned<T> {
+    value: T,
+    id: u64,
+    parent: Option<u64>
+}
+
+impl<T> Versioned<T> {
+    /// Stamp `value` with a fresh id. It has no parent.
+    pub fn new(value: T) -> Versioned<T> {
+        Versioned { value, id: next_id(), parent: None }
+    }
+
+    /// Make a new version by applying `f` to this one. The result gets a
+    /// fresh id, and this version's id as its parent.
+    pub fn derive<U, F: FnOnce(&T) -> U>(&self, f: F) -> Versioned<U> {
+        let value = f(&self.value);
+        self.child(value)
+    }
+
+    /// Like `derive`, but for operations that can fail. If `f` returns an
+    /// error, no id is used up.
+    pub fn try_derive<U, E, F>(&self, f: F) -> Result<Versioned<U>, E>
+        where F: FnOnce(&T) -> Result<U, E>
+    {
+        let value = f(&self.value)?;
+        Ok(self.child(value))
+    }
+
+    /// Stamp `value` as a new version derived from this one, for values made
+    /// some other way than `derive`.
+    pub fn child<U>(&self, value: U) -> Versioned<U> {
+        Versioned { value, id: next_id(), parent: Some(self.id) }
+    }
+
+    /// This version's id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The id of the version this one was derived from, or None if it was
+    /// made with `new`.
+    pub fn parent(&self) -> Option<u64> {
+        self.parent
+    }
+
+    /// The value.
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Drop the version ids and return the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Versioned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Versioned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Versioned")
+            .field("id", &self.id)
+            .field("parent", &self.parent)
+            .field("value", &self.value)
+            .finish()
+    }
+}
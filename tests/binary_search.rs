@@ -0,0 +1,33 @@
+extern crate fundata;
+
+use fundata::rbtree::RBTree;
+
+#[test]
+fn binary_search_matches_a_sorted_vec() {
+    let sorted: Vec<u32> = (0..500).map(|v| v * 3 + 1).collect();
+    let tree: RBTree<u32> = sorted.iter().cloned().collect();
+    for probe in 0..1600 {
+        assert_eq!(tree.binary_search(&probe), sorted.binary_search(&probe));
+        assert_eq!(tree.partition_point(|&v| v < probe), sorted.partition_point(|&v| v < probe));
+        assert_eq!(tree.binary_search_by(|v| v.cmp(&probe)), sorted.binary_search_by(|v| v.cmp(&probe)));
+    }
+    for (i, v) in sorted.iter().enumerate() {
+        assert_eq!(tree.binary_search(v), Ok(i));
+        assert_eq!(tree.select_nth(i), Some(v));
+    }
+}
+
+#[test]
+fn binary_search_on_an_empty_tree() {
+    let tree = RBTree::<u32>::new();
+    assert_eq!(tree.binary_search(&5), Err(0));
+    assert_eq!(tree.partition_point(|_| true), 0);
+}
+
+#[test]
+fn binary_search_by_borrowed_key() {
+    let tree: RBTree<String> = ["apple", "fig", "kiwi"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(tree.binary_search("fig"), Ok(1));
+    assert_eq!(tree.binary_search("banana"), Err(1));
+    assert_eq!(tree.binary_search("zucchini"), Err(3));
+}
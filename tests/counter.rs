@@ -0,0 +1,49 @@
+extern crate fundata;
+
+use std::collections::BTreeMap;
+use fundata::counter::Counter;
+
+#[test]
+fn counter_matches_a_model() {
+    let mut counter = Counter::new();
+    let mut model: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut seed = 3u32;
+    for _ in 0..3000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let key = (seed >> 8) % 12;
+        if (seed >> 16) % 5 < 2 {
+            counter = counter.decrement_saturating(&key);
+            if let Some(count) = model.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    model.remove(&key);
+                }
+            }
+        } else {
+            let n = ((seed >> 20) % 3) as usize;
+            counter = counter.add(key, n);
+            if n > 0 {
+                *model.entry(key).or_insert(0) += n;
+            }
+        }
+        assert_eq!(counter.len(), model.len());
+        assert_eq!(counter.total(), model.values().sum::<usize>());
+        assert!(counter.iter().eq(model.iter().map(|(k, &n)| (k, n))));
+        for key in 0..12 {
+            assert_eq!(counter.count(&key), model.get(&key).cloned().unwrap_or(0));
+        }
+    }
+}
+
+#[test]
+fn counting_down_to_zero_forgets_the_value() {
+    let counter: Counter<&str> = ["a", "b", "a"].iter().cloned().collect();
+    let counter = counter.decrement_saturating(&"b").decrement_saturating(&"b");
+    assert_eq!(counter.len(), 1);
+    assert_eq!(counter.most_common(5), vec![(&"a", 2)]);
+    assert_eq!(format!("{:?}", counter), r#"Counter{"a": 2}"#);
+    let counter = counter.decrement_saturating(&"a").decrement_saturating(&"a");
+    assert!(counter.is_empty());
+    assert_eq!(counter.total(), 0);
+    assert_eq!(counter.merge(&Counter::new()).iter().count(), 0);
+}
@@ -0,0 +1,154 @@
+//! Build, walk and drop structures as deep as they are large, on a thread
+//! with a 64KB stack, where any recursion proportional to the size would
+//! overflow.
+
+extern crate fundata;
+
+use std::slice;
+use std::thread;
+use fundata::heap::LeftistHeap;
+use fundata::list::{self, List};
+use fundata::mergesort::{MergeSort, ScheduledMergeSort};
+use fundata::pairing::PairingHeap;
+use fundata::queue::BatchedQueue;
+use fundata::rbtree::RBTree;
+use fundata::traits::{Heap, Queue, Set, Sortable, Stack};
+use fundata::tree::Tree;
+
+// With `strict-checks`, every insert validates the whole structure, so
+// building one takes quadratic time. A smaller size is still far deeper than
+// the stack allows recursing.
+const N: u32 = if cfg!(feature = "strict-checks") { 10_000 } else { 1_000_000 };
+
+fn with_small_stack<F: FnOnce() + Send + 'static>(f: F) {
+    thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn deep_list() {
+    with_small_stack(|| {
+        let list: List<u32> = (0..N).collect();
+        assert_eq!(list.length(), N as usize);
+        let shared = list.to_shared();
+        assert_eq!(shared.iter().count(), N as usize);
+
+        let both = list::concat(&list, list.clone());
+        assert_eq!(both.iter().nth(N as usize), Some(&0));
+        let tails = list::suffixes(&list);
+        assert_eq!(tails.head().map(List::length), Some(N as usize));
+        drop(tails);
+
+        let snapshot = List::encode(&[list.clone(), both.clone()]);
+        let decoded: Vec<List<u32>> = List::decode(&snapshot).unwrap();
+        assert!(decoded[0].iter().eq(list.iter()));
+        drop(decoded);
+        drop(both);
+
+        assert_eq!(list.into_iter().count(), N as usize);
+    });
+}
+
+#[test]
+fn deep_leftist_heap() {
+    with_small_stack(|| {
+        // Decreasing values make each new root the parent of the old one, so
+        // the left spine is as long as the heap.
+        let heap = (0..N).rev().fold(LeftistHeap::new(), |h, v| h.insert(v));
+        heap.validate().unwrap();
+        let shared = heap.to_shared();
+        shared.validate().unwrap();
+        let doubled = heap.map_monotone(|v| u64::from(*v) * 2);
+        doubled.validate().unwrap();
+
+        let snapshot = LeftistHeap::encode(slice::from_ref(&heap));
+        let decoded: Vec<LeftistHeap<u32>> = LeftistHeap::decode(&snapshot).unwrap();
+        decoded[0].validate().unwrap();
+        drop(decoded);
+
+        assert_eq!(heap.without_min().min(), Some(&1));
+        assert_eq!(heap.into_vec_unordered().len(), N as usize);
+    });
+}
+
+#[test]
+fn deep_pairing_heap() {
+    with_small_stack(|| {
+        let heap = (0..N).rev().fold(PairingHeap::new(), |h, v| h.insert(v));
+        let rest = heap.without_min();
+        assert_eq!(rest.min(), Some(&1));
+        drop(rest);
+        assert_eq!(heap.clone().into_vec_unordered().len(), N as usize);
+    });
+}
+
+#[test]
+fn deep_mergesort() {
+    with_small_stack(|| {
+        // Every lazy `add` suspends a step that needs the one before it, so
+        // an unsorted collection is a chain of n suspensions. Sorting is
+        // O(n log n) in all, so this uses fewer values.
+        let n = N / 10;
+        let lazy = (0..n).rev().fold(MergeSort::new(), |s, v| s.add(v));
+        let sorted = lazy.sort();
+        assert_eq!(sorted.iter().count(), n as usize);
+        assert_eq!(sorted.head(), Some(&0));
+        drop(sorted);
+        drop(lazy);
+
+        let unforced = (0..n).fold(MergeSort::new(), |s, v| s.add(v));
+        drop(unforced);
+
+        let scheduled = (0..n).rev().fold(ScheduledMergeSort::empty(), |s, v| s.add(v));
+        assert!(scheduled.sort().iter().copied().eq(0..n));
+    });
+}
+
+#[test]
+fn deep_queue() {
+    with_small_stack(|| {
+        let queue = (0..N).fold(BatchedQueue::new(), |q, v| q.snoc(v));
+        queue.validate().unwrap();
+        let shared = queue.to_shared();
+        shared.validate().unwrap();
+        let mapped = queue.map(|v| v + 1);
+        mapped.validate().unwrap();
+        assert_eq!(Queue::head(&mapped), Some(&1));
+
+        let (_, rest) = Queue::split(&queue).unwrap();
+        assert_eq!(Queue::head(&rest), Some(&1));
+    });
+}
+
+#[test]
+fn deep_rbtree() {
+    with_small_stack(|| {
+        let tree: RBTree<u32> = RBTree::from_sorted_iter(0..N);
+        tree.validate().unwrap();
+        tree.to_shared().validate().unwrap();
+        let snapshot = RBTree::encode(&[tree.clone(), tree.without_min()]);
+        let decoded: Vec<RBTree<u32>> = RBTree::decode(&snapshot).unwrap();
+        decoded[1].validate().unwrap();
+        assert_eq!(decoded[1].len(), N as usize - 1);
+    });
+}
+
+#[test]
+fn deep_unbalanced_tree() {
+    with_small_stack(|| {
+        // Inserting in order makes a `Tree` one long right spine. Each insert
+        // walks the whole spine, so this uses fewer values.
+        let n = 5_000;
+        let tree = (0..n).fold(Tree::new(), |t, v| t.plus(v));
+        tree.validate().unwrap();
+        tree.to_shared().validate().unwrap();
+        let snapshot = Tree::encode(slice::from_ref(&tree));
+        let decoded: Vec<Tree<u32>> = Tree::decode(&snapshot).unwrap();
+        assert_eq!(decoded[0].len(), n as usize);
+        assert!(tree.contains(&(n - 1)));
+    });
+}
@@ -0,0 +1,42 @@
+extern crate fundata;
+
+use fundata::rbmap::RBMap;
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+
+fn rbtree(values: &[u32]) -> RBTree<u32> {
+    values.iter().fold(RBTree::new(), |t, &v| t.plus(v))
+}
+
+#[test]
+fn set_diff_reports_added_and_removed() {
+    let old = rbtree(&(0..100).collect::<Vec<u32>>());
+    let new = old.without_min().without_max().plus(200);
+    let d = RBTree::diff(&old, &new);
+    assert_eq!(d.added, vec![&200]);
+    assert_eq!(d.removed, vec![&0, &99]);
+    assert!(d.changed.is_empty());
+    assert!(RBTree::diff(&old, &old.clone()).is_empty());
+}
+
+#[test]
+fn map_diff_reports_changed_values() {
+    let old: RBMap<u32, &str> = (0..50).map(|k| (k, "old")).collect();
+    let new = old.insert(10, "new").insert(60, "added").remove(&20).insert(30, "old");
+    let d = RBMap::diff(&old, &new);
+    assert_eq!(d.added, vec![(&60, &"added")]);
+    assert_eq!(d.removed, vec![(&20, &"old")]);
+    assert_eq!(d.changed, vec![(&10, &"old", &"new")]);
+    assert!(!d.is_empty());
+    assert!(RBMap::diff(&new, &new).is_empty());
+}
+
+#[test]
+fn map_diff_of_unrelated_maps() {
+    let a: RBMap<u32, u32> = (0..20).map(|k| (k, k)).collect();
+    let b: RBMap<u32, u32> = (5..25).map(|k| (k, if k == 7 { 0 } else { k })).collect();
+    let d = RBMap::diff(&a, &b);
+    assert_eq!(d.added.len(), 5);
+    assert_eq!(d.removed.len(), 5);
+    assert_eq!(d.changed, vec![(&7, &7, &0)]);
+}
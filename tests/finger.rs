@@ -0,0 +1,34 @@
+extern crate fundata;
+
+use fundata::rbtree::RBTree;
+
+fn one_to_ten() -> RBTree<u32> {
+    RBTree::from_sorted_iter(1..=10)
+}
+
+#[test]
+fn seek_restarts_after_running_off_the_end() {
+    let tree = one_to_ten();
+    let mut finger = tree.finger();
+    assert_eq!(finger.seek(&100), None);
+    assert_eq!(finger.seek(&5), Some(&5));
+    assert_eq!(finger.advance(), Some(&6));
+
+    let mut finger = tree.finger();
+    assert!(!finger.contains(&20));
+    assert!(finger.contains(&3));
+    assert!(!finger.contains(&0));
+}
+
+#[test]
+fn seek_matches_ceiling() {
+    let tree: RBTree<u32> = (0..200).map(|v| v * 3).collect();
+    let mut finger = tree.finger();
+    // Forward, backward, and past the end, in a scrambled order.
+    let mut probe = 0u32;
+    for _ in 0..1000 {
+        probe = (probe * 7 + 13) % 650;
+        assert_eq!(finger.seek(&probe), tree.ceiling(&probe));
+        assert_eq!(finger.get(), tree.ceiling(&probe));
+    }
+}
@@ -0,0 +1,75 @@
+extern crate fundata;
+
+use std::collections::BTreeMap;
+use fundata::pairing::{Handle, HandleHeap};
+
+fn model_min(model: &BTreeMap<Handle, u32>) -> Option<(&u32, Handle)> {
+    model.iter().map(|(&h, k)| (k, h)).min()
+}
+
+#[test]
+fn handle_heap_matches_a_model() {
+    let mut heap = HandleHeap::new();
+    let mut model: BTreeMap<Handle, u32> = BTreeMap::new();
+    let mut handles = vec![];
+    let mut seed = 11u32;
+    for _ in 0..3000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let pick = match handles.len() {
+            0 => None,
+            n => Some(handles[(seed >> 4) as usize % n])
+        };
+        let key = (seed >> 12) % 1000;
+        match ((seed >> 24) % 5, pick) {
+            (0, Some(handle)) => {
+                let deleted = heap.delete(handle);
+                assert_eq!(deleted.is_some(), model.remove(&handle).is_some());
+                if let Some(h) = deleted {
+                    heap = h;
+                }
+                assert!(heap.delete(handle).is_none());
+            }
+            (1, Some(handle)) => {
+                let decreased = heap.decrease_key(handle, key);
+                match model.get_mut(&handle) {
+                    Some(old) if key <= *old => {
+                        *old = key;
+                        heap = decreased.unwrap();
+                    }
+                    _ => assert!(decreased.is_none())
+                }
+            }
+            (2, _) => {
+                if let Some((_, handle)) = model_min(&model) {
+                    model.remove(&handle);
+                }
+                heap = heap.without_min();
+            }
+            _ => {
+                let (h, handle) = heap.insert(key);
+                model.insert(handle, key);
+                handles.push(handle);
+                heap = h;
+            }
+        }
+        assert_eq!(heap.len(), model.len());
+        assert_eq!(heap.min(), model_min(&model));
+        for &handle in &handles {
+            assert_eq!(heap.get(handle), model.get(&handle));
+        }
+    }
+}
+
+#[test]
+fn old_versions_keep_their_entries() {
+    let (heap, a) = HandleHeap::new().insert(5);
+    let (heap, b) = heap.insert(3);
+    let deleted = heap.delete(b).unwrap();
+    let decreased = heap.decrease_key(a, 1).unwrap();
+    assert_eq!(heap.min(), Some((&3, b)));
+    assert_eq!(deleted.min(), Some((&5, a)));
+    assert_eq!(deleted.get(b), None);
+    assert_eq!(decreased.min(), Some((&1, a)));
+    assert_eq!(decreased.without_min().min(), Some((&3, b)));
+    assert!(deleted.delete(a).unwrap().is_empty());
+}
@@ -0,0 +1,90 @@
+extern crate fundata;
+
+use std::collections::HashSet as StdHashSet;
+use std::hash::{Hash, Hasher};
+use fundata::hashset::HashSet;
+use fundata::traits::Set;
+
+// A value whose hash only looks at `value / 4`, so groups of four collide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Colliding(u32);
+
+impl Hash for Colliding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 / 4).hash(state);
+    }
+}
+
+fn values(seed: u32, n: usize, range: u32) -> Vec<u32> {
+    let mut seed = seed;
+    (0..n).map(|_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 8) % range
+    }).collect()
+}
+
+fn check<T: Hash + Eq + Clone + std::fmt::Debug>(set: &HashSet<T>, model: &StdHashSet<T>) {
+    assert_eq!(set.len(), model.len());
+    let mut seen = StdHashSet::new();
+    for v in set.iter() {
+        assert!(model.contains(v), "unexpected value {:?}", v);
+        assert!(seen.insert(v.clone()), "value {:?} seen twice", v);
+    }
+}
+
+#[test]
+fn set_operations_match_std() {
+    for seed in 0..10 {
+        let a_values = values(seed, 400, 600);
+        let b_values = values(seed + 100, 400, 600);
+        let a: HashSet<u32> = a_values.iter().cloned().collect();
+        let b: HashSet<u32> = b_values.iter().cloned().collect();
+        let ma: StdHashSet<u32> = a_values.iter().cloned().collect();
+        let mb: StdHashSet<u32> = b_values.iter().cloned().collect();
+        check(&a, &ma);
+        check(&a.union(&b), &ma.union(&mb).cloned().collect());
+        check(&a.intersection(&b), &ma.intersection(&mb).cloned().collect());
+        check(&a.minus_all(b_values.iter().cloned()), &ma.difference(&mb).cloned().collect());
+        check(&Set::difference(&a, &b), &ma.difference(&mb).cloned().collect());
+        for v in 0..600 {
+            assert_eq!(a.contains(&v), ma.contains(&v));
+        }
+    }
+}
+
+#[test]
+fn colliding_values() {
+    let a_values: Vec<Colliding> = values(1, 300, 200).into_iter().map(Colliding).collect();
+    let b_values: Vec<Colliding> = values(2, 300, 200).into_iter().map(Colliding).collect();
+    let a: HashSet<Colliding> = a_values.iter().cloned().collect();
+    let b: HashSet<Colliding> = b_values.iter().cloned().collect();
+    let ma: StdHashSet<Colliding> = a_values.iter().cloned().collect();
+    let mb: StdHashSet<Colliding> = b_values.iter().cloned().collect();
+    check(&a, &ma);
+    check(&a.union(&b), &ma.union(&mb).cloned().collect());
+    check(&a.intersection(&b), &ma.intersection(&mb).cloned().collect());
+    check(&a.minus_all(b_values.iter().cloned()), &ma.difference(&mb).cloned().collect());
+}
+
+#[test]
+fn removing_everything_leaves_an_empty_set() {
+    let all: Vec<u32> = (0..1000).collect();
+    let set: HashSet<u32> = all.iter().cloned().collect();
+    let half = set.minus_all((0..1000).filter(|v| v % 2 == 1));
+    check(&half, &(0..1000).filter(|v| v % 2 == 0).collect());
+    assert_eq!(half, (0..500).map(|v| v * 2).collect());
+    let none = half.minus_all(all.iter().cloned());
+    assert!(none.is_empty());
+    assert_eq!(none, HashSet::new());
+    assert_eq!(none.plus(7).iter().collect::<Vec<_>>(), vec![&7]);
+    // Removing values that aren't there changes nothing.
+    assert_eq!(set.minus_all(vec![5000, 6000]), set);
+}
+
+#[test]
+fn modify_keeps_equal_values() {
+    let set: HashSet<u32> = (0..100).collect();
+    assert_eq!(set.modify(&5, |&v| v), Some(set.clone()));
+    assert_eq!(set.modify(&5, |&v| v + 1), None);
+    assert_eq!(set.modify(&500, |&v| v), None);
+}
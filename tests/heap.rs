@@ -0,0 +1,65 @@
+extern crate fundata;
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use fundata::heap::LeftistHeap;
+use fundata::pairing::PairingHeap;
+use fundata::traits::Heap;
+
+// A value that counts how many times it has been cloned.
+struct Counted(u32, Rc<Cell<usize>>);
+
+impl Clone for Counted {
+    fn clone(&self) -> Counted {
+        self.1.set(self.1.get() + 1);
+        Counted(self.0, self.1.clone())
+    }
+}
+
+impl PartialEq for Counted {
+    fn eq(&self, other: &Counted) -> bool { self.0 == other.0 }
+}
+
+impl Eq for Counted {}
+
+impl PartialOrd for Counted {
+    fn partial_cmp(&self, other: &Counted) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Counted {
+    fn cmp(&self, other: &Counted) -> Ordering { self.0.cmp(&other.0) }
+}
+
+fn sorted(values: Vec<Counted>) -> Vec<u32> {
+    let mut values: Vec<u32> = values.into_iter().map(|c| c.0).collect();
+    values.sort();
+    values
+}
+
+fn check_into_vec_unordered<H, F>(into_vec: F)
+    where H: Heap<Item=Counted> + Clone, F: Fn(H) -> Vec<Counted>
+{
+    let clones = Rc::new(Cell::new(0));
+    let heap = (0..1000u32).rev().fold(H::empty(), |h, i| h.insert(Counted(i, clones.clone())));
+    assert_eq!(sorted(into_vec(heap)), (0..1000).collect::<Vec<u32>>());
+    assert_eq!(clones.get(), 0);
+
+    // Values in nodes another heap still has are cloned; the rest are not.
+    let base = (0..10u32).fold(H::empty(), |h, i| h.insert(Counted(i, clones.clone())));
+    let heap = base.insert(Counted(100, clones.clone()));
+    let values = into_vec(heap);
+    assert_eq!(values.len(), 11);
+    assert!(clones.get() > 0 && clones.get() <= 10);
+    assert_eq!(sorted(into_vec(base)), (0..10).collect::<Vec<u32>>());
+}
+
+#[test]
+fn leftist_into_vec_unordered_moves_unshared_values() {
+    check_into_vec_unordered(LeftistHeap::<Counted>::into_vec_unordered);
+}
+
+#[test]
+fn pairing_into_vec_unordered_moves_unshared_values() {
+    check_into_vec_unordered(PairingHeap::<Counted>::into_vec_unordered);
+}
@@ -0,0 +1,68 @@
+extern crate fundata;
+
+use fundata::indexedqueue::IndexedQueue;
+use fundata::traits::Queue;
+
+fn items(queue: &IndexedQueue<u32, u32>) -> Vec<(u32, u32)> {
+    let mut items = vec![];
+    let mut queue = queue.clone();
+    while let Some((&item, rest)) = queue.split() {
+        items.push(item);
+        queue = rest;
+    }
+    items
+}
+
+#[test]
+fn indexed_queue_matches_a_model() {
+    let mut queue = IndexedQueue::new();
+    let mut model: Vec<(u32, u32)> = vec![];
+    let mut seed = 7u32;
+    for step in 0..2000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let key = (seed >> 8) % 10;
+        match (seed >> 16) % 4 {
+            0 => {
+                let removed = queue.remove(&key);
+                let position = model.iter().position(|&(k, _)| k == key);
+                assert_eq!(removed.is_some(), position.is_some());
+                if let (Some(rest), Some(i)) = (removed, position) {
+                    model.remove(i);
+                    queue = rest;
+                }
+            }
+            1 => {
+                if let Some((&item, rest)) = queue.split() {
+                    assert_eq!(item, model.remove(0));
+                    queue = rest;
+                } else {
+                    assert!(model.is_empty());
+                }
+            }
+            _ => {
+                model.retain(|&(k, _)| k != key);
+                model.push((key, step));
+                queue = queue.snoc((key, step));
+            }
+        }
+        assert_eq!(queue.len(), model.len());
+        assert_eq!(queue.head(), model.first());
+        for k in 0..10 {
+            assert_eq!(queue.contains_key(&k), model.iter().any(|&(mk, _)| mk == k));
+        }
+    }
+    assert_eq!(items(&queue), model);
+}
+
+#[test]
+fn removed_keys_can_come_back() {
+    let queue = IndexedQueue::new().snoc((1, "a")).snoc((2, "b"));
+    let queue = queue.remove(&1).unwrap();
+    assert!(queue.remove(&1).is_none());
+    let queue = queue.snoc((1, "c"));
+    assert_eq!(format!("{:?}", queue), r#"IndexedQueue{2: "b", 1: "c"}"#);
+    let (_, queue) = queue.split().unwrap();
+    let (_, queue) = queue.split().unwrap();
+    assert!(queue.is_empty());
+    assert!(!queue.contains_key(&1));
+}
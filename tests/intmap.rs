@@ -0,0 +1,52 @@
+extern crate fundata;
+
+use std::collections::BTreeMap;
+use fundata::intmap::{IntMap, KEY_LIMIT};
+
+#[test]
+fn intmap_matches_btreemap() {
+    let mut map = IntMap::new();
+    let mut model: BTreeMap<u32, u32> = BTreeMap::new();
+    let mut seed = 17u32;
+    for step in 0..5000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        // Keys cluster in a few ranges, and some are near the limit, so that
+        // nodes fill up and empty out at every level.
+        let key = match (seed >> 4) % 4 {
+            0 => (seed >> 8) % 64,
+            1 => 5000 + (seed >> 8) % 64,
+            2 => KEY_LIMIT - 1 - (seed >> 8) % 64,
+            _ => (seed >> 8) % KEY_LIMIT
+        };
+        if (seed >> 28) % 3 == 2 {
+            map = map.remove(key);
+            model.remove(&key);
+        } else {
+            map = map.insert(key, step);
+            model.insert(key, step);
+        }
+        assert_eq!(map.len(), model.len());
+        assert_eq!(map.get(key), model.get(&key));
+        assert_eq!(map.contains_key(key), model.contains_key(&key));
+    }
+    assert!(map.iter().eq(model.iter().map(|(&k, v)| (k, v))));
+    assert!(map.clone().into_keys().eq(model.keys().cloned()));
+    assert!(map.clone().into_values().eq(model.values().cloned()));
+    let rebuilt: IntMap<u32> = model.iter().map(|(&k, &v)| (k, v)).collect();
+    assert_eq!(rebuilt, map);
+
+    // Removing everything leaves an empty map.
+    let empty = model.keys().fold(map, |m, &k| m.remove(k));
+    assert!(empty.is_empty());
+    assert_eq!(empty, IntMap::new());
+}
+
+#[test]
+fn keys_out_of_range() {
+    let map = IntMap::new().insert(1, "one");
+    assert!(map.try_insert(KEY_LIMIT, "limit").is_err());
+    assert_eq!(map.get(KEY_LIMIT), None);
+    assert_eq!(map.remove(KEY_LIMIT), map);
+    assert_eq!(map.update(1, |v| v.map(|_| "uno")).get(1), Some(&"uno"));
+    assert!(map.update(1, |_| None).is_empty());
+}
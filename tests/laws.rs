@@ -0,0 +1,61 @@
+extern crate fundata;
+
+use fundata::hashset::HashSet;
+use fundata::heap::LeftistHeap;
+use fundata::laws::*;
+use fundata::list::List;
+use fundata::pairing::PairingHeap;
+use fundata::queue::BatchedQueue;
+use fundata::rbtree::RBTree;
+use fundata::sync;
+use fundata::tree::Tree;
+
+#[test]
+fn lists_obey_the_stack_laws() {
+    check_stack_laws::<List<u32>>().unwrap();
+    check_stack_laws::<sync::List<u32>>().unwrap();
+}
+
+#[test]
+fn batched_queues_obey_the_queue_laws() {
+    check_queue_laws::<BatchedQueue<u32>>().unwrap();
+    check_queue_laws::<sync::BatchedQueue<u32>>().unwrap();
+}
+
+#[test]
+fn sets_obey_the_set_laws() {
+    check_set_laws::<RBTree<u32>>().unwrap();
+    check_set_laws::<sync::RBTree<u32>>().unwrap();
+    check_set_laws::<Tree<u32>>().unwrap();
+    check_set_laws::<HashSet<u32>>().unwrap();
+}
+
+#[test]
+fn heaps_obey_the_heap_laws() {
+    check_heap_laws::<LeftistHeap<u32>>().unwrap();
+    check_heap_laws::<PairingHeap<u32>>().unwrap();
+    check_heap_laws::<sync::PairingHeap<u32>>().unwrap();
+}
+
+#[test]
+fn a_broken_set_breaks_the_laws() {
+    // A "set" that forgets everything it's given.
+    #[derive(Clone)]
+    struct Forgetful;
+
+    impl fundata::traits::Set for Forgetful {
+        type Item = u32;
+        fn empty() -> Forgetful { Forgetful }
+        fn plus(&self, _: u32) -> Forgetful { Forgetful }
+        fn contains(&self, _: &u32) -> bool { false }
+        fn modify<F>(&self, _: &u32, _: F) -> Option<Forgetful> { None }
+    }
+
+    impl IntoIterator for Forgetful {
+        type Item = u32;
+        type IntoIter = ::std::vec::IntoIter<u32>;
+        fn into_iter(self) -> ::std::vec::IntoIter<u32> { vec![].into_iter() }
+    }
+
+    assert!(check_set_laws::<Forgetful>().is_err());
+}
@@ -0,0 +1,89 @@
+extern crate fundata;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use fundata::list::List;
+use fundata::optics::{index, Lens};
+use fundata::traits::Stack;
+
+// A value that counts how many times it has been cloned.
+struct Counted(u32, Rc<Cell<usize>>);
+
+impl Clone for Counted {
+    fn clone(&self) -> Counted {
+        self.1.set(self.1.get() + 1);
+        Counted(self.0, self.1.clone())
+    }
+}
+
+fn counted_list(n: u32, clones: &Rc<Cell<usize>>) -> List<Counted> {
+    (0..n).map(|i| Counted(i, clones.clone())).collect()
+}
+
+#[test]
+fn pop_moves_items_out_of_unshared_nodes() {
+    let clones = Rc::new(Cell::new(0));
+    let mut list = counted_list(100, &clones);
+    let mut popped = vec![];
+    while let Some(Counted(i, _)) = list.pop() {
+        popped.push(i);
+    }
+    assert_eq!(popped, (0..100).collect::<Vec<u32>>());
+    assert_eq!(clones.get(), 0);
+
+    let items: Vec<u32> = counted_list(100, &clones).into_iter().map(|c| c.0).collect();
+    assert_eq!(items, (0..100).collect::<Vec<u32>>());
+    assert_eq!(clones.get(), 0);
+}
+
+#[test]
+fn pop_clones_items_from_shared_nodes() {
+    let clones = Rc::new(Cell::new(0));
+    let shared = counted_list(10, &clones);
+    let mut list = Stack::cons(Counted(100, clones.clone()), shared.clone());
+    let items: Vec<u32> = list.iter().map(|c| c.0).collect();
+    assert_eq!(items, vec![100, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(clones.get(), 0);
+
+    // The first node is this list's own; the rest belong to `shared` too.
+    while Stack::pop(&mut list).is_some() {}
+    assert_eq!(clones.get(), 10);
+    assert_eq!(shared.iter().map(|c| c.0).collect::<Vec<u32>>(), (0..10).collect::<Vec<u32>>());
+}
+
+#[test]
+fn positional_edits_match_vec() {
+    let values: Vec<u32> = (0..20).collect();
+    let list: List<u32> = values.iter().cloned().collect();
+    for i in 0..=values.len() {
+        let mut expected = values.clone();
+        expected.insert(i, 100);
+        let inserted = list.insert_at(i, 100).unwrap();
+        assert!(inserted.iter().eq(expected.iter()));
+    }
+    for i in 0..values.len() {
+        let mut expected = values.clone();
+        let removed = expected.remove(i);
+        let (item, rest) = list.remove_at(i).unwrap();
+        assert_eq!(*item, removed);
+        assert!(rest.iter().eq(expected.iter()));
+
+        let mut expected = values.clone();
+        expected[i] = 100;
+        let set = index(i).set(&list, 100).unwrap();
+        assert!(set.iter().eq(expected.iter()));
+    }
+}
+
+#[test]
+fn positional_edits_past_the_end() {
+    let list: List<u32> = (0..3).collect();
+    for &i in &[4, 1_000_000_000, usize::MAX] {
+        assert!(list.insert_at(i, 9).is_none());
+        assert!(list.remove_at(i).is_none());
+        assert!(index(i).set(&list, 9).is_none());
+        assert!(index(i).get(&list).is_none());
+    }
+    assert!(list.remove_at(3).is_none());
+    assert!(index(3).set(&list, 9).is_none());
+}
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate fundata;
+
+use fundata::heap::LeftistHeap;
+use fundata::rbtree::RBTree;
+use fundata::traits::{Heap, Queue};
+
+#[test]
+fn rbset_sorts_and_keeps_first_duplicate() {
+    let set: RBTree<(u32, char)> = rbset![(3, 'a'), (1, 'b'), (2, 'c')];
+    set.validate().unwrap();
+    assert_eq!(set.iter().map(|p| p.0).collect::<Vec<u32>>(), vec![1, 2, 3]);
+
+    let set: RBTree<u32> = rbset![5, 1, 5, 3, 1];
+    set.validate().unwrap();
+    assert_eq!(set.iter().cloned().collect::<Vec<u32>>(), vec![1, 3, 5]);
+
+    let empty: RBTree<u32> = rbset![];
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn heap_macro_builds_a_valid_heap() {
+    let mut heap: LeftistHeap<u32> = heap![5, 3, 8, 1, 9, 2];
+    heap.validate().unwrap();
+    let mut popped = vec![];
+    while let Some(&v) = heap.min() {
+        popped.push(v);
+        heap = heap.without_min();
+    }
+    assert_eq!(popped, vec![1, 2, 3, 5, 8, 9]);
+}
+
+#[test]
+fn list_and_queue_macros() {
+    let list = list![1, 2, 3];
+    assert_eq!(list.iter().cloned().collect::<Vec<u32>>(), vec![1, 2, 3]);
+    let mut queue = queue![1, 2, 3];
+    let mut items = vec![];
+    while let Some((&v, rest)) = Queue::split(&queue) {
+        items.push(v);
+        queue = rest;
+    }
+    assert_eq!(items, vec![1, 2, 3]);
+}
@@ -0,0 +1,87 @@
+extern crate fundata;
+
+use fundata::rbmap::RBMap;
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+use fundata::tree::Tree;
+
+fn rbtree(values: &[u32]) -> RBTree<u32> {
+    values.iter().fold(RBTree::new(), |t, &v| t.plus(v))
+}
+
+fn values(t: &RBTree<u32>) -> Vec<u32> {
+    t.iter().cloned().collect()
+}
+
+#[test]
+fn rbtree_merge3_keeps_removals_from_either_side() {
+    let base = rbtree(&[1, 2, 3]);
+    let a = base.plus(4);
+    let b = base.without_max();
+    let no_conflicts = |_: &u32, _: Option<&u32>, _: Option<&u32>| -> Option<u32> {
+        panic!("unexpected conflict")
+    };
+    assert_eq!(values(&RBTree::merge3(&base, &a, &b, no_conflicts)), vec![1, 2, 4]);
+    assert_eq!(values(&RBTree::merge3(&base, &b, &a, no_conflicts)), vec![1, 2, 4]);
+
+    // Both sides removing the same value isn't a conflict.
+    let c = base.without_min();
+    let merged = RBTree::merge3(&base, &b.without_min(), &c, no_conflicts);
+    assert_eq!(values(&merged), vec![2]);
+    merged.validate().unwrap();
+}
+
+#[test]
+fn rbtree_merge3_large() {
+    let base = rbtree(&(0..1000).collect::<Vec<u32>>());
+    let a = base.minus_all((0..1000).filter(|v| v % 3 == 0));
+    let b = (1000..1100).fold(base.without_min(), |t, v| t.plus(v));
+    let merged = RBTree::merge3(&base, &a, &b, |_, _, _| panic!("unexpected conflict"));
+    merged.validate().unwrap();
+    let expected: Vec<u32> = (1..1100).filter(|v| *v >= 1000 || v % 3 != 0).collect();
+    assert_eq!(values(&merged), expected);
+}
+
+#[test]
+fn tree_merge3_keeps_removals_from_either_side() {
+    let base = [1, 2, 3].iter().fold(Tree::new(), |t, &v| t.plus(v));
+    let a = base.plus(4);
+    let b = base.minus_all(vec![3]);
+    let expected = vec![&1, &2, &4];
+    assert_eq!(Tree::merge3(&base, &a, &b).iter().collect::<Vec<_>>(), expected);
+    assert_eq!(Tree::merge3(&base, &b, &a).iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn map_merge3_applies_both_sides() {
+    let base: RBMap<u32, &str> = (0..10).map(|k| (k, "base")).collect();
+    let a = base.insert(1, "a").remove(&2).insert(20, "a");
+    let b = base.insert(3, "b").remove(&4).insert(30, "b");
+    let merged = RBMap::merge3(&base, &a, &b, |_, _, _| panic!("unexpected conflict"));
+    let expected: RBMap<u32, &str> = vec![
+        (0, "base"), (1, "a"), (3, "b"), (5, "base"), (6, "base"),
+        (7, "base"), (8, "base"), (9, "base"), (20, "a"), (30, "b")
+    ].into_iter().collect();
+    assert_eq!(merged, expected);
+}
+
+#[test]
+fn map_merge3_calls_conflict_for_keys_both_sides_changed() {
+    let base: RBMap<u32, &str> = (0..5).map(|k| (k, "base")).collect();
+    let a = base.insert(1, "a").remove(&2).insert(3, "same").insert(7, "a");
+    let b = base.insert(1, "b").insert(2, "b").insert(3, "same").insert(7, "b");
+    let mut conflicts = vec![];
+    let merged = RBMap::merge3(&base, &a, &b, |&k, x, y| {
+        conflicts.push((k, x.cloned(), y.cloned()));
+        if k == 7 { None } else { y.cloned() }
+    });
+    assert_eq!(conflicts, vec![
+        (1, Some("a"), Some("b")),
+        (2, None, Some("b")),
+        (7, Some("a"), Some("b"))
+    ]);
+    let expected: RBMap<u32, &str> = vec![
+        (0, "base"), (1, "b"), (2, "b"), (3, "same"), (4, "base")
+    ].into_iter().collect();
+    assert_eq!(merged, expected);
+}
@@ -0,0 +1,81 @@
+extern crate fundata;
+
+use std::cmp::Ordering;
+use fundata::evaluation::Eager;
+use fundata::mergesort::{MergeSort, ScheduledMergeSort};
+use fundata::traits::Sortable;
+
+// A value that sorts by `key` alone, so the test can see whether the sort
+// is stable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Keyed {
+    key: u32,
+    serial: u32
+}
+
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Keyed) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Keyed {
+    fn cmp(&self, other: &Keyed) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+// Add values one at a time, sorting now and then, and also branch off older
+// versions, checking each sort against a stable sort of a Vec.
+fn check_sortable<S: Sortable<Item=Keyed> + Clone>() {
+    let mut seed = 31u32;
+    let mut versions: Vec<(S, Vec<Keyed>)> = vec![(S::empty(), vec![])];
+    for serial in 0..1500 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        // Usually extend the newest version, sometimes an older one.
+        let i = match (seed >> 4) % 8 {
+            7 => (seed >> 8) as usize % versions.len(),
+            _ => versions.len() - 1
+        };
+        let value = Keyed { key: (seed >> 16) % 40, serial };
+        let (sorter, values) = versions[i].clone();
+        let mut values = values;
+        values.push(value);
+        let sorter = sorter.add(value);
+        if serial % 50 == 0 {
+            let mut expected = values.clone();
+            expected.sort();
+            assert!(sorter.sort().iter().eq(expected.iter()));
+        }
+        versions.push((sorter, values));
+    }
+    for (sorter, mut values) in versions.into_iter().step_by(97) {
+        values.sort();
+        assert!(sorter.sort().iter().eq(values.iter()));
+        // Sorting again gives the same answer.
+        assert!(sorter.sort().iter().eq(values.iter()));
+    }
+}
+
+#[test]
+fn lazy_mergesort_sorts_stably() {
+    check_sortable::<MergeSort<Keyed>>();
+}
+
+#[test]
+fn eager_mergesort_sorts_stably() {
+    check_sortable::<MergeSort<Keyed, Eager>>();
+}
+
+#[test]
+fn len_counts_duplicates() {
+    let sorter = [3, 1, 3, 2].iter().fold(MergeSort::new(), |s, &v| s.add(v));
+    assert_eq!(sorter.len(), 4);
+    assert!(sorter.sort().iter().cloned().eq(vec![1, 2, 3, 3]));
+    assert_eq!(MergeSort::<u32>::new().sort().iter().count(), 0);
+}
+
+#[test]
+fn scheduled_mergesort_sorts_stably() {
+    check_sortable::<ScheduledMergeSort<Keyed>>();
+}
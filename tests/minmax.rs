@@ -0,0 +1,65 @@
+extern crate fundata;
+
+use fundata::minmax::MinMaxHeap;
+
+#[test]
+fn minmax_heap_matches_a_sorted_vec() {
+    let mut heap = MinMaxHeap::new();
+    let mut model: Vec<u32> = vec![];
+    let mut versions = vec![];
+    let mut seed = 23u32;
+    for step in 0..3000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        match (seed >> 24) % 5 {
+            0 => {
+                heap = heap.without_min();
+                if !model.is_empty() {
+                    model.remove(0);
+                }
+            }
+            1 => {
+                heap = heap.without_max();
+                model.pop();
+            }
+            _ => {
+                // A small range, so there are plenty of duplicates.
+                let value = (seed >> 8) % 50;
+                heap = heap.insert(value);
+                let i = model.partition_point(|&v| v <= value);
+                model.insert(i, value);
+            }
+        }
+        assert_eq!(heap.len(), model.len());
+        assert_eq!(heap.min(), model.first());
+        assert_eq!(heap.max(), model.last());
+        if step % 100 == 0 {
+            versions.push((heap.clone(), model.clone()));
+        }
+    }
+
+    // Old versions are unchanged, and drain in order from both ends.
+    for (heap, model) in versions {
+        let (mut heap, mut model) = (heap, model);
+        while let Some(&min) = heap.min() {
+            assert_eq!(Some(&min), model.first());
+            assert_eq!(heap.max(), model.last());
+            model.remove(0);
+            heap = heap.without_min();
+            if let Some(&max) = heap.max() {
+                assert_eq!(model.pop(), Some(max));
+                heap = heap.without_max();
+            }
+        }
+        assert!(model.is_empty());
+        assert!(heap.is_empty());
+    }
+}
+
+#[test]
+fn single_value_is_both_min_and_max() {
+    let heap: MinMaxHeap<u32> = vec![7].into_iter().collect();
+    assert_eq!((heap.min(), heap.max()), (Some(&7), Some(&7)));
+    assert!(heap.without_min().is_empty());
+    assert!(heap.without_max().is_empty());
+    assert!(MinMaxHeap::<u32>::new().without_max().is_empty());
+}
@@ -0,0 +1,24 @@
+extern crate fundata;
+
+use fundata::hashset::HashSet;
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+use fundata::tree::Tree;
+
+fn check_modify<S>(set: S)
+    where S: Set<Item=u32> + Clone + IntoIterator<Item=u32>
+{
+    let same = set.modify(&5, |&v| v).unwrap();
+    let mut values: Vec<u32> = same.into_iter().collect();
+    values.sort();
+    assert_eq!(values, (0..10).collect::<Vec<u32>>());
+    assert!(set.modify(&5, |&v| v + 1).is_none());
+    assert!(set.modify(&50, |&v| v).is_none());
+}
+
+#[test]
+fn modify_on_every_set() {
+    check_modify((0..10).collect::<RBTree<u32>>());
+    check_modify((0..10).fold(Tree::new(), |t, v| t.plus(v)));
+    check_modify((0..10).collect::<HashSet<u32>>());
+}
@@ -0,0 +1,59 @@
+extern crate fundata;
+
+use std::collections::BTreeMap;
+use fundata::multimap::MultiMap;
+
+#[test]
+fn multimap_matches_a_model() {
+    let mut map = MultiMap::new();
+    let mut model: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    let mut seed = 5u32;
+    for _ in 0..3000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let key = (seed >> 8) % 8;
+        let value = (seed >> 20) % 4;
+        match (seed >> 16) % 6 {
+            0 => {
+                map = map.remove_all(&key);
+                model.remove(&key);
+            }
+            1 | 2 => {
+                map = map.remove_one(&key, &value);
+                if let Some(values) = model.get_mut(&key) {
+                    if let Some(i) = values.iter().position(|&v| v == value) {
+                        values.remove(i);
+                    }
+                    if values.is_empty() {
+                        model.remove(&key);
+                    }
+                }
+            }
+            _ => {
+                map = map.insert(key, value);
+                model.entry(key).or_default().push(value);
+            }
+        }
+        assert_eq!(map.len(), model.values().map(Vec::len).sum::<usize>());
+        assert_eq!(map.key_count(), model.len());
+        let expected: Vec<(&u32, &u32)> = model.iter()
+            .flat_map(|(k, vs)| vs.iter().map(move |v| (k, v)))
+            .collect();
+        assert_eq!(map.iter().collect::<Vec<_>>(), expected);
+        for key in 0..8 {
+            assert_eq!(map.contains_key(&key), model.contains_key(&key));
+        }
+    }
+    assert!(map.clone().into_keys().eq(model.keys().cloned()));
+    assert!(map.into_values().eq(model.values().flatten().cloned()));
+}
+
+#[test]
+fn removing_the_last_value_removes_the_key() {
+    let map: MultiMap<&str, u32> = vec![("a", 1), ("b", 2), ("b", 3)].into_iter().collect();
+    let map = map.remove_one(&"a", &1).remove_all(&"b");
+    assert!(map.is_empty());
+    assert_eq!(map.key_count(), 0);
+    assert!(!map.contains_key(&"a"));
+    assert_eq!(format!("{:?}", map), "MultiMap{}");
+    assert_eq!(map.into_keys().count(), 0);
+}
@@ -0,0 +1,73 @@
+extern crate fundata;
+
+use fundata::numeric::{DenseBinary, Numeral, SkewBinary, SparseBinary, ZerolessBinary};
+
+// Count up to `n` and back down, checking every value, then check `add` and
+// `from_u64` against ordinary addition.
+fn check_arithmetic<N: Numeral>(n: u64) {
+    let mut x = N::zero();
+    assert!(x.is_zero());
+    assert!(x.decrement().is_none());
+    let mut ups = vec![];
+    for i in 0..n {
+        assert_eq!(x.to_u64(), i);
+        assert_eq!(N::from_u64(i).to_u64(), i);
+        assert_eq!(N::from_u64(i).weights(), x.weights());
+        ups.push(x.weights());
+        x = x.increment();
+    }
+    for i in (0..n).rev() {
+        x = x.decrement().unwrap();
+        assert_eq!(x.to_u64(), i);
+        assert_eq!(x.weights(), ups[i as usize]);
+    }
+    assert!(x.is_zero());
+    for a in (0..200).step_by(13) {
+        for b in (0..200).step_by(7) {
+            let sum = N::from_u64(a).add(&N::from_u64(b));
+            assert_eq!(sum.to_u64(), a + b);
+        }
+    }
+}
+
+#[test]
+fn dense_binary() {
+    check_arithmetic::<DenseBinary>(600);
+    for i in 0..600 {
+        let n = <DenseBinary>::from_u64(i);
+        let digits: Vec<u8> = n.digits().iter().cloned().collect();
+        assert!(digits.iter().all(|&d| d <= 1));
+        assert_ne!(digits.last(), Some(&0), "no leading zeros");
+    }
+}
+
+#[test]
+fn sparse_binary() {
+    check_arithmetic::<SparseBinary>(600);
+    for i in 0..600 {
+        let weights = <SparseBinary>::from_u64(i).weights();
+        assert!(weights.iter().all(|w| w.is_power_of_two()));
+        assert!(weights.windows(2).all(|w| w[0] < w[1]));
+    }
+}
+
+#[test]
+fn skew_binary() {
+    check_arithmetic::<SkewBinary>(600);
+    for i in 0..600 {
+        let weights = <SkewBinary>::from_u64(i).weights();
+        assert!(weights.iter().all(|w| (w + 1).is_power_of_two()));
+        // Only the smallest weight may appear twice.
+        assert!(weights.windows(2).all(|w| w[0] <= w[1]));
+        assert!(weights.windows(2).skip(1).all(|w| w[0] < w[1]));
+    }
+}
+
+#[test]
+fn zeroless_binary() {
+    check_arithmetic::<ZerolessBinary>(600);
+    for i in 0..600 {
+        let digits: Vec<u8> = <ZerolessBinary>::from_u64(i).digits().iter().cloned().collect();
+        assert!(digits.iter().all(|&d| d == 1 || d == 2));
+    }
+}
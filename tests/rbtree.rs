@@ -0,0 +1,75 @@
+extern crate fundata;
+
+use std::collections::BTreeSet;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+
+fn values(seed: u32, n: usize, range: u32) -> Vec<u32> {
+    let mut seed = seed;
+    (0..n).map(|_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 8) % range
+    }).collect()
+}
+
+#[test]
+fn nearest_values_match_btreeset() {
+    for seed in 0..5 {
+        let model: BTreeSet<u32> = values(seed, 200, 1000).into_iter().collect();
+        let tree: RBTree<u32> = model.iter().cloned().collect();
+        for probe in 0..1010 {
+            let floor = model.range((Unbounded, Included(probe))).next_back();
+            let ceiling = model.range((Included(probe), Unbounded)).next();
+            let predecessor = model.range((Unbounded, Excluded(probe))).next_back();
+            let successor = model.range((Excluded(probe), Unbounded)).next();
+            assert_eq!(tree.floor(&probe), floor);
+            assert_eq!(tree.ceiling(&probe), ceiling);
+            assert_eq!(tree.predecessor(&probe), predecessor);
+            assert_eq!(tree.successor(&probe), successor);
+        }
+    }
+    let empty = RBTree::<u32>::new();
+    assert_eq!(empty.floor(&1), None);
+    assert_eq!(empty.successor(&1), None);
+}
+
+#[test]
+fn from_sorted_iter_builds_valid_trees_of_every_size() {
+    for n in 0..300 {
+        let tree: RBTree<u32> = RBTree::from_sorted_iter(0..n);
+        tree.validate().unwrap();
+        assert_eq!(tree.len(), n as usize);
+        assert!(tree.iter().cloned().eq(0..n));
+    }
+}
+
+#[test]
+fn from_sorted_iter_drops_duplicates() {
+    let mut sorted = values(1, 500, 100);
+    sorted.sort();
+    let tree: RBTree<u32> = RBTree::from_sorted_iter(sorted.clone());
+    tree.validate().unwrap();
+    let model: BTreeSet<u32> = sorted.into_iter().collect();
+    assert!(tree.iter().eq(model.iter()));
+    assert_eq!(tree, model.iter().fold(RBTree::new(), |t, &v| t.plus(v)));
+}
+
+#[test]
+fn split_matches_btreeset() {
+    for seed in 0..5 {
+        let model: BTreeSet<u32> = values(seed, 300, 1000).into_iter().collect();
+        let tree: RBTree<u32> = model.iter().cloned().collect();
+        for pivot in (0..1010).step_by(7) {
+            let (less, found, greater) = tree.split(&pivot);
+            less.validate().unwrap();
+            greater.validate().unwrap();
+            assert!(less.iter().eq(model.range(..pivot)));
+            assert!(greater.iter().eq(model.range(pivot + 1..)));
+            assert_eq!(found, model.get(&pivot).cloned());
+            assert_eq!(less.len() + greater.len() + found.iter().count(), tree.len());
+        }
+    }
+    let (less, found, greater) = RBTree::<u32>::new().split(&5);
+    assert!(less.is_empty() && found.is_none() && greater.is_empty());
+}
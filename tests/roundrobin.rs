@@ -0,0 +1,73 @@
+extern crate fundata;
+
+use std::collections::{BTreeMap, VecDeque};
+use fundata::queue::BatchedQueue;
+use fundata::roundrobin::RoundRobin;
+use fundata::traits::Queue;
+
+fn items(queue: &BatchedQueue<u32>) -> Vec<u32> {
+    let mut items = vec![];
+    let mut queue = queue.clone();
+    while let Some((&item, rest)) = Queue::split(&queue) {
+        items.push(item);
+        queue = rest;
+    }
+    items
+}
+
+#[test]
+fn streams_take_turns_and_rejoin_after_emptying() {
+    let rr = RoundRobin::new().enqueue("a", 1).enqueue("a", 2).enqueue("b", 10);
+    let (id, item, rr) = rr.next().unwrap();
+    assert_eq!((*id, *item), ("a", 1));
+    let (id, item, rr) = rr.next().unwrap();
+    assert_eq!((*id, *item), ("b", 10));
+    assert!(rr.stream(&"b").is_empty());
+
+    // "b" emptied out, so it rejoins behind "a".
+    let rr = rr.enqueue("b", 11);
+    let (id, _, rr) = rr.next().unwrap();
+    assert_eq!(*id, "a");
+    let (id, item, rr) = rr.next().unwrap();
+    assert_eq!((*id, *item), ("b", 11));
+    assert!(rr.is_empty());
+    assert!(rr.next().is_none());
+    assert_eq!(format!("{:?}", rr), "RoundRobin{}");
+}
+
+#[test]
+fn round_robin_matches_a_model() {
+    let mut rr = RoundRobin::new();
+    let mut streams: BTreeMap<u32, VecDeque<u32>> = BTreeMap::new();
+    let mut turns: VecDeque<u32> = VecDeque::new();
+    let mut seed = 1u32;
+    for step in 0..2000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        if (seed >> 16) % 3 == 2 {
+            match rr.next() {
+                None => assert!(turns.is_empty()),
+                Some((&id, &item, rest)) => {
+                    assert_eq!(turns.pop_front(), Some(id));
+                    let queue = streams.get_mut(&id).unwrap();
+                    assert_eq!(queue.pop_front(), Some(item));
+                    if !queue.is_empty() {
+                        turns.push_back(id);
+                    }
+                    rr = rest;
+                }
+            }
+        } else {
+            let id = (seed >> 8) % 7;
+            let queue = streams.entry(id).or_default();
+            if queue.is_empty() {
+                turns.push_back(id);
+            }
+            queue.push_back(step);
+            rr = rr.enqueue(id, step);
+        }
+        assert_eq!(rr.len(), streams.values().map(VecDeque::len).sum::<usize>());
+        for (id, queue) in &streams {
+            assert!(items(&rr.stream(id)).iter().eq(queue.iter()));
+        }
+    }
+}
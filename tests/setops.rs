@@ -0,0 +1,84 @@
+extern crate fundata;
+
+use std::collections::BTreeSet;
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+use fundata::tree::Tree;
+
+fn values(seed: u32, n: usize, range: u32) -> Vec<u32> {
+    let mut seed = seed;
+    (0..n).map(|_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 8) % range
+    }).collect()
+}
+
+// Pairs of sets to combine: overlapping, disjoint by range, one empty, and
+// one a slightly changed copy of the other.
+fn cases() -> Vec<(Vec<u32>, Vec<u32>)> {
+    let mut cases = vec![];
+    for seed in 0..10 {
+        cases.push((values(seed, 300, 500), values(seed + 50, 200, 500)));
+    }
+    cases.push(((0..300).collect(), (300..600).collect()));
+    cases.push(((0..300).collect(), vec![]));
+    cases.push((vec![], (0..300).collect()));
+    let base = values(99, 500, 2000);
+    let mut changed = base.clone();
+    changed.extend(vec![3, 2500, 777]);
+    cases.push((base, changed));
+    cases
+}
+
+fn rbtree(values: &[u32]) -> RBTree<u32> {
+    values.iter().cloned().collect()
+}
+
+fn model(values: &[u32]) -> BTreeSet<u32> {
+    values.iter().cloned().collect()
+}
+
+fn check(tree: &RBTree<u32>, expected: &BTreeSet<u32>) {
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), expected.len());
+    assert!(tree.iter().eq(expected.iter()));
+}
+
+#[test]
+fn union_matches_btreeset() {
+    for (a, b) in cases() {
+        let (ta, tb) = (rbtree(&a), rbtree(&b));
+        let (ma, mb) = (model(&a), model(&b));
+        let expected = ma.union(&mb).cloned().collect();
+        check(&ta.union(&tb), &expected);
+        check(&tb.union(&ta), &expected);
+        check(&ta.union(&ta), &ma);
+
+        // Tree uses the trait's default.
+        let ua = a.iter().fold(Tree::new(), |t, &v| t.plus(v));
+        let ub = b.iter().fold(Tree::new(), |t, &v| t.plus(v));
+        let union = Set::union(&ua, &ub);
+        union.validate().unwrap();
+        assert!(union.iter().eq(expected.iter()));
+    }
+}
+
+#[test]
+fn intersection_and_difference_match_btreeset() {
+    for (a, b) in cases() {
+        let (ta, tb) = (rbtree(&a), rbtree(&b));
+        let (ma, mb) = (model(&a), model(&b));
+        check(&ta.intersection(&tb), &ma.intersection(&mb).cloned().collect());
+        check(&tb.intersection(&ta), &ma.intersection(&mb).cloned().collect());
+        check(&ta.difference(&tb), &ma.difference(&mb).cloned().collect());
+        check(&tb.difference(&ta), &mb.difference(&ma).cloned().collect());
+        check(&ta.intersection(&ta), &ma);
+        check(&ta.difference(&ta), &BTreeSet::new());
+
+        // Tree uses the trait's defaults.
+        let ua = a.iter().fold(Tree::new(), |t, &v| t.plus(v));
+        let ub = b.iter().fold(Tree::new(), |t, &v| t.plus(v));
+        assert!(Set::intersection(&ua, &ub).iter().eq(ma.intersection(&mb)));
+        assert!(Set::difference(&ua, &ub).iter().eq(ma.difference(&mb)));
+    }
+}
@@ -0,0 +1,101 @@
+extern crate fundata;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use fundata::stream::Stream;
+
+// The stream n, n + 1, n + 2, ..., counting the cells forced in `forced`.
+fn naturals_from(n: u64, forced: Arc<AtomicUsize>) -> Stream<u64> {
+    Stream::delay(move || {
+        forced.fetch_add(1, Ordering::SeqCst);
+        Some((n, naturals_from(n + 1, forced)))
+    })
+}
+
+fn counted() -> (Stream<u64>, Arc<AtomicUsize>) {
+    let forced = Arc::new(AtomicUsize::new(0));
+    (naturals_from(0, forced.clone()), forced)
+}
+
+fn count(forced: &AtomicUsize) -> usize {
+    forced.load(Ordering::SeqCst)
+}
+
+fn items(s: &Stream<u64>) -> Vec<u64> {
+    s.iter().cloned().collect()
+}
+
+#[test]
+fn building_a_pipeline_forces_nothing() {
+    let (s, forced) = counted();
+    let _ = s.take(3);
+    let _ = s.take_while(|&n| n < 5);
+    let _ = s.drop_while(|&n| n < 5);
+    let _ = s.filter(|&n| n % 2 == 0);
+    let _ = s.flat_map(|&n| Stream::cons(n, Stream::empty()));
+    let _ = s.filter(|&n| n > 100).take_while(|&n| n < 200).take(10);
+    assert_eq!(count(&forced), 0);
+}
+
+#[test]
+fn take_forces_only_the_demanded_cells() {
+    let (s, forced) = counted();
+    let first = s.take(3);
+    assert_eq!(first.head(), Some(&0));
+    assert_eq!(count(&forced), 1);
+    assert_eq!(items(&first), vec![0, 1, 2]);
+    assert_eq!(count(&forced), 3);
+
+    // Cells are memoized: walking the stream again forces nothing new.
+    assert_eq!(items(&first), vec![0, 1, 2]);
+    assert_eq!(items(&s.take(2)), vec![0, 1]);
+    assert_eq!(count(&forced), 3);
+}
+
+#[test]
+fn take_while_forces_one_cell_past_the_prefix() {
+    let (s, forced) = counted();
+    let small = s.take_while(|&n| n < 5);
+    assert_eq!(small.head(), Some(&0));
+    assert_eq!(count(&forced), 1);
+    assert_eq!(items(&small), vec![0, 1, 2, 3, 4]);
+    assert_eq!(count(&forced), 6);
+}
+
+#[test]
+fn drop_while_forces_the_prefix_when_first_demanded() {
+    let (s, forced) = counted();
+    let rest = s.drop_while(|&n| n < 10);
+    assert_eq!(count(&forced), 0);
+    assert_eq!(rest.head(), Some(&10));
+    assert_eq!(count(&forced), 11);
+    assert_eq!(items(&rest.take(3)), vec![10, 11, 12]);
+    assert_eq!(count(&forced), 13);
+}
+
+#[test]
+fn filter_forces_up_to_the_next_match() {
+    let (s, forced) = counted();
+    let multiples = s.filter(|&n| n % 5 == 0);
+    assert_eq!(multiples.head(), Some(&0));
+    assert_eq!(count(&forced), 1);
+    assert_eq!(items(&multiples.take(3)), vec![0, 5, 10]);
+    assert_eq!(count(&forced), 11);
+}
+
+#[test]
+fn flat_map_calls_f_only_as_needed() {
+    let (s, forced) = counted();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let doubled = {
+        let calls = calls.clone();
+        s.flat_map(move |&n| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Stream::cons(n, Stream::cons(n, Stream::empty()))
+        })
+    };
+    assert_eq!(count(&calls), 0);
+    assert_eq!(items(&doubled.take(3)), vec![0, 0, 1]);
+    assert_eq!(count(&forced), 2);
+    assert_eq!(count(&calls), 2);
+}
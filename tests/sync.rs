@@ -0,0 +1,136 @@
+extern crate fundata;
+
+use std::sync::{Arc, Barrier};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use fundata::lazy::Lazy;
+use fundata::sync::{self, Stream};
+use fundata::traits::{Heap, Set, Sortable};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn sync_types_are_send_and_sync() {
+    assert_send_sync::<sync::List<u32>>();
+    assert_send_sync::<sync::Tree<u32>>();
+    assert_send_sync::<sync::TreeZipper<u32>>();
+    assert_send_sync::<sync::RBTree<u32>>();
+    assert_send_sync::<sync::RBTreeZipper<u32>>();
+    assert_send_sync::<sync::RBMap<u32, String>>();
+    assert_send_sync::<sync::HashSet<u32>>();
+    assert_send_sync::<sync::IntMap<String>>();
+    assert_send_sync::<sync::MultiMap<u32, String>>();
+    assert_send_sync::<sync::Counter<u32>>();
+    assert_send_sync::<sync::Graph<u32, String>>();
+    assert_send_sync::<sync::LeftistHeap<u32>>();
+    assert_send_sync::<sync::PairingHeap<u32>>();
+    assert_send_sync::<sync::HandleHeap<u32>>();
+    assert_send_sync::<sync::MinMaxHeap<u32>>();
+    assert_send_sync::<sync::TopK<u32>>();
+    assert_send_sync::<sync::BatchedQueue<u32>>();
+    assert_send_sync::<sync::IndexedQueue<u32, String>>();
+    assert_send_sync::<sync::RoundRobin<u32, String>>();
+    assert_send_sync::<sync::DenseBinary>();
+    assert_send_sync::<sync::SparseBinary>();
+    assert_send_sync::<sync::SkewBinary>();
+    assert_send_sync::<sync::ZerolessBinary>();
+    assert_send_sync::<sync::Stream<u32>>();
+    assert_send_sync::<sync::MergeSort<u32>>();
+    assert_send_sync::<sync::ScheduledMergeSort<u32>>();
+}
+
+fn naturals_from(n: u64, forced: Arc<AtomicUsize>) -> Stream<u64> {
+    Stream::delay(move || {
+        forced.fetch_add(1, Ordering::SeqCst);
+        Some((n, naturals_from(n + 1, forced)))
+    })
+}
+
+#[test]
+fn lazy_value_is_computed_once_across_threads() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let lazy = {
+        let runs = runs.clone();
+        Arc::new(Lazy::new(move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            (0..1000u64).sum::<u64>()
+        }))
+    };
+    let barrier = Arc::new(Barrier::new(8));
+    let handles: Vec<_> = (0..8).map(|_| {
+        let (lazy, barrier) = (lazy.clone(), barrier.clone());
+        thread::spawn(move || {
+            barrier.wait();
+            *lazy.force()
+        })
+    }).collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), 499_500);
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn stream_cells_are_forced_once_across_threads() {
+    let forced = Arc::new(AtomicUsize::new(0));
+    let stream = naturals_from(0, forced.clone());
+    let handles: Vec<_> = (0..8).map(|i| {
+        let stream = stream.clone();
+        thread::spawn(move || stream.iter().take(1000 + i).sum::<u64>())
+    }).collect();
+    for (i, h) in handles.into_iter().enumerate() {
+        let n = 1000 + i as u64;
+        assert_eq!(h.join().unwrap(), n * (n - 1) / 2);
+    }
+    assert_eq!(forced.load(Ordering::SeqCst), 1007);
+}
+
+#[test]
+fn merge_sorts_can_be_shared_between_threads() {
+    let values: Vec<u32> = (0..500).map(|i| (i * 7919) % 500).collect();
+    let lazy = values.iter().fold(sync::MergeSort::<u32>::empty(), |s, &v| s.add(v));
+    let scheduled = values.iter().fold(sync::ScheduledMergeSort::empty(), |s, &v| s.add(v));
+    let handles: Vec<_> = (0..4).map(|_| {
+        let (lazy, scheduled) = (lazy.clone(), scheduled.clone());
+        thread::spawn(move || (lazy.sort().iter().cloned().collect::<Vec<u32>>(),
+                               scheduled.sort().iter().cloned().collect::<Vec<u32>>()))
+    }).collect();
+    let expected: Vec<u32> = (0..500).collect();
+    for h in handles {
+        let (a, b) = h.join().unwrap();
+        assert_eq!(a, expected);
+        assert_eq!(b, expected);
+    }
+}
+
+#[test]
+fn persistent_versions_can_be_shared_between_threads() {
+    let map: sync::RBMap<u32, String> = (0..100).map(|k| (k, k.to_string())).collect();
+    let set: sync::HashSet<u32> = (0..100).collect();
+    let ints: sync::IntMap<u32> = (0..100).map(|k| (k, k * k)).collect();
+    let heap = (0..100).fold(sync::PairingHeap::empty(), |h, v| h.insert(v));
+
+    // Each thread makes its own new version from the shared ones.
+    let handles: Vec<_> = (0..4u32).map(|t| {
+        let (map, set, ints, heap) = (map.clone(), set.clone(), ints.clone(), heap.clone());
+        thread::spawn(move || {
+            let map = map.insert(1000 + t, "new".to_string()).remove(&t);
+            let set = set.plus(1000 + t);
+            let ints = ints.update(t, |v| v.map(|v| v + 1));
+            let heap = heap.without_min();
+            (map.len(), map.contains_key(&t), set.contains(&(1000 + t)),
+             ints.get(t).cloned(), heap.min().cloned())
+        })
+    }).collect();
+    for (t, h) in handles.into_iter().enumerate() {
+        let t = t as u32;
+        assert_eq!(h.join().unwrap(), (100, false, true, Some(t * t + 1), Some(1)));
+    }
+
+    // The shared versions are unchanged.
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.get(&0).map(String::as_str), Some("0"));
+    assert!(!set.contains(&1000));
+    assert_eq!(ints.get(3), Some(&9));
+    assert_eq!(heap.min(), Some(&0));
+}
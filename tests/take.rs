@@ -0,0 +1,36 @@
+extern crate fundata;
+
+use fundata::rbtree::RBTree;
+use fundata::traits::Set;
+use fundata::tree::Tree;
+
+#[test]
+fn take_matches_sorted_values() {
+    let values: Vec<u32> = (0..300).map(|i| (i * 37) % 1000).collect();
+    let rbtree: RBTree<u32> = values.iter().cloned().collect();
+    let tree = values.iter().fold(Tree::new(), |t, &v| t.plus(v));
+    let mut sorted = values.clone();
+    sorted.sort();
+
+    for &k in &[0, 1, 2, 50, 299, 300, 301] {
+        let smallest: Vec<&u32> = sorted.iter().take(k).collect();
+        let largest: Vec<&u32> = sorted.iter().rev().take(k).collect();
+        assert_eq!(rbtree.take_smallest(k), smallest);
+        assert_eq!(rbtree.take_largest(k), largest);
+        assert_eq!(tree.take_smallest(k), smallest);
+        assert_eq!(tree.take_largest(k), largest);
+    }
+}
+
+#[test]
+fn take_more_than_the_tree_holds() {
+    let rbtree: RBTree<u32> = (1..=3).collect();
+    let tree = (1..=3).fold(Tree::new(), |t, v| t.plus(v));
+    for &k in &[usize::MAX, 1_000_000_000] {
+        assert_eq!(rbtree.take_largest(k), vec![&3, &2, &1]);
+        assert_eq!(rbtree.take_smallest(k), vec![&1, &2, &3]);
+        assert_eq!(tree.take_largest(k), vec![&3, &2, &1]);
+        assert_eq!(tree.take_smallest(k), vec![&1, &2, &3]);
+    }
+    assert!(RBTree::<u32>::new().take_largest(usize::MAX).is_empty());
+}
@@ -0,0 +1,98 @@
+extern crate fundata;
+
+use fundata::hashset::HashSet;
+use fundata::heap::LeftistHeap;
+use fundata::pairing::PairingHeap;
+use fundata::pointer::RcKind;
+use fundata::queue::BatchedQueue;
+use fundata::rbtree::RBTree;
+use fundata::sync;
+use fundata::testing::*;
+use fundata::traits::{Queue, Set};
+use fundata::tree::Tree;
+
+const SEEDS: u64 = 20;
+const LEN: usize = 500;
+
+fn run_random<S, F>(make_op: F)
+    where S: Spec, F: Fn(u64) -> S::Op + Copy
+{
+    for seed in 0..SEEDS {
+        let commands = random_commands(seed, LEN, make_op);
+        if let Err(failure) = run::<S>(&commands) {
+            panic!("seed {}: {}", seed, failure);
+        }
+    }
+}
+
+#[test]
+fn sets_agree_with_btreeset() {
+    run_random::<SetSpec<RBTree<u64>>, _>(SetOp::random);
+    run_random::<SetSpec<sync::RBTree<u64>>, _>(SetOp::random);
+    run_random::<SetSpec<Tree<u64>>, _>(SetOp::random);
+    run_random::<SetSpec<HashSet<u64>>, _>(SetOp::random);
+}
+
+#[test]
+fn queues_agree_with_vecdeque() {
+    run_random::<QueueSpec<BatchedQueue<u64>>, _>(QueueOp::random);
+    run_random::<QueueSpec<sync::BatchedQueue<u64>>, _>(QueueOp::random);
+}
+
+#[test]
+fn heaps_agree_with_binaryheap() {
+    run_random::<HeapSpec<LeftistHeap<u64>>, _>(HeapOp::random);
+    run_random::<HeapSpec<PairingHeap<u64>>, _>(HeapOp::random);
+    run_random::<HeapSpec<sync::LeftistHeap<u64>>, _>(HeapOp::random);
+}
+
+#[test]
+fn run_reports_the_failing_step() {
+    // A "set" that forgets everything it's given.
+    #[derive(Clone)]
+    struct Forgetful;
+
+    impl Set for Forgetful {
+        type Item = u64;
+        fn empty() -> Forgetful { Forgetful }
+        fn plus(&self, _: u64) -> Forgetful { Forgetful }
+        fn contains(&self, _: &u64) -> bool { false }
+        fn modify<F>(&self, _: &u64, _: F) -> Option<Forgetful> { None }
+    }
+
+    impl IntoIterator for Forgetful {
+        type Item = u64;
+        type IntoIter = ::std::vec::IntoIter<u64>;
+        fn into_iter(self) -> ::std::vec::IntoIter<u64> { vec![].into_iter() }
+    }
+
+    let commands = vec![
+        Command::Apply(SetOp::Plus(3)),
+        Command::Apply(SetOp::Contains(4)),
+        Command::Apply(SetOp::Contains(3))
+    ];
+    let failure = run::<SetSpec<Forgetful>>(&commands).unwrap_err();
+    assert_eq!(failure.step, Some(2));
+}
+
+#[test]
+fn every_small_structure_is_valid() {
+    let trees = all_trees::<RcKind>(6);
+    assert_eq!(trees.len(), 1 + 1 + 2 + 5 + 14 + 42 + 132);
+    for t in &trees {
+        t.validate().unwrap();
+    }
+    for t in all_rbtrees::<RcKind>(8) {
+        t.validate().unwrap();
+    }
+    for q in all_queues::<RcKind>(6) {
+        q.validate().unwrap();
+        let mut items = vec![];
+        let mut rest = q;
+        while let Some((&v, tail)) = Queue::split(&rest) {
+            items.push(v);
+            rest = tail;
+        }
+        assert_eq!(items, (0..items.len() as u32).collect::<Vec<u32>>());
+    }
+}
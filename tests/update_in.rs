@@ -0,0 +1,107 @@
+extern crate fundata;
+
+use fundata::intmap::IntMap;
+use fundata::rbmap::RBMap;
+use fundata::traits::Nested;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Config {
+    Number(i32),
+    Table(RBMap<&'static str, Config>)
+}
+
+impl Nested<RBMap<&'static str, Config>> for Config {
+    fn as_map(&self) -> Option<&RBMap<&'static str, Config>> {
+        match *self {
+            Config::Table(ref map) => Some(map),
+            Config::Number(_) => None
+        }
+    }
+
+    fn from_map(map: RBMap<&'static str, Config>) -> Config {
+        Config::Table(map)
+    }
+}
+
+fn number(c: Option<&Config>) -> Option<i32> {
+    match c {
+        Some(&Config::Number(n)) => Some(n),
+        _ => None
+    }
+}
+
+#[test]
+fn rbmap_update_in_rebuilds_the_path() {
+    let root = RBMap::new()
+        .update_in(&["server", "port"], |_| Some(Config::Number(80)))
+        .update_in(&["server", "threads"], |_| Some(Config::Number(4)))
+        .update_in(&["client", "retries"], |_| Some(Config::Number(3)));
+    let updated = root.update_in(&["server", "port"], |old| number(old).map(|n| Config::Number(n + 8000)));
+
+    let server = updated.get("server").and_then(Config::as_map).unwrap();
+    assert_eq!(number(server.get("port")), Some(8080));
+    assert_eq!(number(server.get("threads")), Some(4));
+
+    // The old version is unchanged, and the branch that wasn't on the path is
+    // the same value in both.
+    let old_server = root.get("server").and_then(Config::as_map).unwrap();
+    assert_eq!(number(old_server.get("port")), Some(80));
+    assert_eq!(root.get("client"), updated.get("client"));
+
+    // Removing the last key leaves an empty table behind.
+    let removed = updated.update_in(&["client", "retries"], |_| None);
+    assert!(removed.get("client").and_then(Config::as_map).unwrap().is_empty());
+}
+
+#[test]
+fn rbmap_update_in_replaces_plain_values_on_the_path() {
+    let root = RBMap::new().insert("server", Config::Number(1));
+    let updated = root.update_in(&["server", "port"], |old| {
+        assert_eq!(old, None);
+        Some(Config::Number(80))
+    });
+    let server = updated.get("server").and_then(Config::as_map).unwrap();
+    assert_eq!(server.len(), 1);
+    assert_eq!(number(server.get("port")), Some(80));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Value(u32),
+    Grid(IntMap<Cell>)
+}
+
+impl Nested<IntMap<Cell>> for Cell {
+    fn as_map(&self) -> Option<&IntMap<Cell>> {
+        match *self {
+            Cell::Grid(ref map) => Some(map),
+            Cell::Value(_) => None
+        }
+    }
+
+    fn from_map(map: IntMap<Cell>) -> Cell {
+        Cell::Grid(map)
+    }
+}
+
+#[test]
+fn intmap_update_in() {
+    let grid = (0..10).fold(IntMap::new(), |g, i| g.update_in(&[i % 3, i], |_| Some(Cell::Value(i))));
+    assert_eq!(grid.len(), 3);
+    let row = grid.get(1).and_then(Cell::as_map).unwrap();
+    assert_eq!(row.iter().map(|(k, _)| k).collect::<Vec<u32>>(), vec![1, 4, 7]);
+
+    let doubled = grid.update_in(&[1, 4], |old| match old {
+        Some(&Cell::Value(n)) => Some(Cell::Value(n * 2)),
+        _ => None
+    });
+    let row = doubled.get(1).and_then(Cell::as_map).unwrap();
+    assert_eq!(row.get(4), Some(&Cell::Value(8)));
+    assert_eq!(grid.get(2), doubled.get(2));
+}
+
+#[test]
+#[should_panic(expected = "empty path")]
+fn update_in_with_empty_path_panics() {
+    IntMap::<Cell>::new().update_in(&[], |_| None);
+}